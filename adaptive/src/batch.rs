@@ -0,0 +1,279 @@
+use crate::decoder::{try_kernel, DecodeError, Kernel};
+use common::min_heap::TieBreakPolicy;
+use common::packet::Packet;
+use common::with_tie_break;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// Threads to spread the packets over, 1 decodes on the calling thread.
+    pub threads: usize,
+    /// See `DecodeOptions::tie_break`.
+    pub tie_break: TieBreakPolicy,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            threads: 1,
+            tie_break: TieBreakPolicy::Classic,
+        }
+    }
+}
+
+/// The result of decoding one packet of a batch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchItem {
+    pub message: Result<String, DecodeError>,
+    /// The kernel used, `None` for invalid packets.
+    pub kernel: Option<Kernel>,
+    /// The time spent validating and decoding the packet, including building
+    /// its tables unless they were cached.
+    pub elapsed: Duration,
+}
+
+/// Decodes each packet on the calling thread, see `decode_batch_with`.
+pub fn decode_batch(contents: &[&[u8]]) -> Vec<Result<String, DecodeError>> {
+    decode_batch_with(contents, BatchOptions::default())
+        .into_iter()
+        .map(|item| item.message)
+        .collect()
+}
+
+/// Validates and decodes each packet, returning the results in input order.
+///
+/// Packets are decoded grouped by kernel and symbol table so consecutive
+/// packets sharing a symbol table reuse its lookup tables, and each thread
+/// reuses the scratch channels of the FSM.
+pub fn decode_batch_with(contents: &[&[u8]], options: BatchOptions) -> Vec<BatchItem> {
+    let mut items: Vec<Option<BatchItem>> = vec![None; contents.len()];
+    let mut order = Vec::with_capacity(contents.len());
+    for (index, &content) in contents.iter().enumerate() {
+        let start = Instant::now();
+        let packet = Packet::try_new(content).map_err(DecodeError::from);
+        match packet.and_then(|packet| Ok((try_kernel(&packet, options.tie_break)?, packet))) {
+            Ok((kernel, packet)) => order.push((kernel, packet, index, start.elapsed())),
+            Err(err) => {
+                items[index] = Some(BatchItem {
+                    message: Err(err),
+                    kernel: None,
+                    elapsed: start.elapsed(),
+                })
+            }
+        }
+    }
+    order.sort_unstable_by_key(|(kernel, packet, ..)| (*kernel, packet.symbol_frequency_bytes));
+
+    let next = AtomicUsize::new(0);
+    let worker = || {
+        let mut decoder = BatchDecoder::new(options.tie_break);
+        let mut decoded = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some((kernel, packet, index, validated)) = order.get(i) else {
+                break;
+            };
+            let start = Instant::now();
+            let message = decoder.decode(*kernel, packet, contents[*index]);
+            let item = BatchItem {
+                message: Ok(message),
+                kernel: Some(*kernel),
+                elapsed: *validated + start.elapsed(),
+            };
+            decoded.push((*index, item));
+        }
+        decoded
+    };
+
+    let threads = options.threads.clamp(1, order.len().max(1));
+    let decoded = if threads == 1 {
+        vec![worker()]
+    } else {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads).map(|_| scope.spawn(worker)).collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    };
+    for (index, item) in decoded.into_iter().flatten() {
+        items[index] = Some(item);
+    }
+    items.into_iter().map(Option::unwrap).collect()
+}
+
+// The last decoder per kernel and the FSM channels of one thread.
+struct BatchDecoder {
+    tie_break: TieBreakPolicy,
+    table: Option<table_unsafe_ptr::Decoder>,
+    fsm: Option<fsm_unsafe_4channel::Decoder>,
+    channels: [Vec<u8>; 4],
+}
+
+impl BatchDecoder {
+    fn new(tie_break: TieBreakPolicy) -> Self {
+        BatchDecoder {
+            tie_break,
+            table: None,
+            fsm: None,
+            channels: Default::default(),
+        }
+    }
+
+    fn decode(&mut self, kernel: Kernel, packet: &Packet, content: &[u8]) -> String {
+        let symbols = packet.symbol_frequency_bytes;
+        let tie_break = self.tie_break;
+        match kernel {
+            // Building the tree is most of the work for small packets.
+            Kernel::Flat => {
+                return with_tie_break!(tie_break, flat_unsafe_ptr::decode_packet_with(content))
+            }
+            Kernel::Table => {
+                let decoder = cached(&mut self.table, symbols, |decoder| {
+                    decoder.symbol_frequency_bytes()
+                })
+                .get_or_insert_with(|| {
+                    with_tie_break!(tie_break, table_unsafe_ptr::Decoder::new_with(packet))
+                });
+                decoder.decode_into(packet, &mut self.channels[0]);
+            }
+            Kernel::Fsm => {
+                let decoder = cached(&mut self.fsm, symbols, |decoder| {
+                    decoder.symbol_frequency_bytes()
+                })
+                .get_or_insert_with(|| {
+                    with_tie_break!(tie_break, fsm_unsafe_4channel::Decoder::new_with(packet))
+                });
+                decoder.decode_into(packet, &mut self.channels);
+            }
+        }
+        // The channels stay as scratch for the next packet, the message gets
+        // an exact-size copy. Validated packets only hold ASCII symbols.
+        String::from_utf8(self.channels[0].clone()).unwrap()
+    }
+}
+
+// Clears `slot` unless it holds the decoder for `symbols`.
+fn cached<'a, T>(
+    slot: &'a mut Option<T>,
+    symbols: &[u8],
+    symbols_of: impl Fn(&T) -> &[u8],
+) -> &'a mut Option<T> {
+    if slot.as_ref().map(symbols_of) != Some(symbols) {
+        *slot = None;
+    }
+    slot
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::try_decode_packet;
+    use common::packet::PacketError;
+    use common::test_cases::*;
+
+    fn samples() -> Vec<Vec<u8>> {
+        common::corpus::samples()
+            .iter()
+            .map(|sample| sample.request())
+            .collect()
+    }
+
+    #[test]
+    fn decodes_batch_in_input_order() {
+        let samples = samples();
+        let mut contents: Vec<&[u8]> = samples.iter().map(Vec::as_slice).collect();
+        contents.insert(3, &TEST_BYTES[..100]);
+
+        for threads in [1, 4] {
+            let items = decode_batch_with(
+                &contents,
+                BatchOptions {
+                    threads,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(items.len(), contents.len());
+            for (item, content) in items.iter().zip(&contents) {
+                assert_eq!(item.message, try_decode_packet(content));
+                if let Ok(message) = &item.message {
+                    assert_eq!(message.capacity(), message.len());
+                }
+                let packet = Packet::try_new(content).ok();
+                let expected =
+                    packet.and_then(|packet| try_kernel(&packet, TieBreakPolicy::Classic).ok());
+                assert_eq!(item.kernel, expected);
+            }
+        }
+        assert!(matches!(
+            decode_batch(&contents)[3],
+            Err(DecodeError::Packet(PacketError::Truncated { .. }))
+        ));
+    }
+
+    #[test]
+    fn decodes_batch_with_tie_break() {
+        use common::min_heap::ReverseInsertionOrder;
+        use common::synthetic::encode_packet_with;
+
+        // Repeats of the test message, which has tied frequencies.
+        let messages = [1, 100, 300].map(|repeats| EXPECTED_MESSAGE.repeat(repeats));
+        let encoded: Vec<_> = messages
+            .iter()
+            .map(|message| encode_packet_with::<ReverseInsertionOrder>(message.as_bytes()))
+            .collect();
+        let contents: Vec<&[u8]> = encoded.iter().map(Vec::as_slice).collect();
+        let options = BatchOptions {
+            tie_break: TieBreakPolicy::ReverseInsertionOrder,
+            ..Default::default()
+        };
+        let items = decode_batch_with(&contents, options);
+        for (item, message) in items.iter().zip(&messages) {
+            assert_eq!(item.message.as_ref(), Ok(message));
+        }
+    }
+
+    #[test]
+    fn decodes_empty_batch() {
+        assert!(decode_batch(&[]).is_empty());
+        assert!(decode_batch_with(
+            &[],
+            BatchOptions {
+                threads: 4,
+                ..Default::default()
+            }
+        )
+        .is_empty());
+    }
+}
+
+// MARK: Benches
+
+use common::test_cases::BENCH_SAMPLE_COUNT;
+#[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT / 1000)]
+mod bench {
+    use super::*;
+    use common::corpus::samples;
+
+    use divan::{black_box, Bencher};
+
+    #[divan::bench(args = [1, 4])]
+    fn decode_batch_with(bencher: Bencher, threads: usize) {
+        let samples: Vec<_> = samples().iter().map(|sample| sample.request()).collect();
+        let contents: Vec<&[u8]> = samples.iter().map(Vec::as_slice).collect();
+        bencher.bench_local(move || {
+            super::decode_batch_with(
+                black_box(&contents),
+                BatchOptions {
+                    threads,
+                    ..Default::default()
+                },
+            );
+        });
+    }
+}
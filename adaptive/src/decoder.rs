@@ -0,0 +1,331 @@
+use common::min_heap::TieBreakPolicy;
+use common::packet::{Packet, PacketError};
+use common::records::{parse_records, MarketRecord, RecordError};
+use common::tree::{validate_packet_with, TreeError, MAX_DEPTH, MAX_TABLE_DEPTH};
+use common::with_tie_break;
+
+// Crossover points from the README table, tables don't amortise below
+// `FLAT_MAX_DECODED_LEN` and the FSM wins from `FSM_MIN_DECODED_LEN`.
+pub const FLAT_MAX_DECODED_LEN: u32 = 300;
+pub const FSM_MIN_DECODED_LEN: u32 = 10_000;
+
+/// The decoder used for a packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Kernel {
+    Flat,
+    Table,
+    Fsm,
+}
+
+impl Kernel {
+    /// The crate implementing the kernel.
+    pub fn name(self) -> &'static str {
+        match self {
+            Kernel::Flat => "flat_unsafe_ptr",
+            Kernel::Table => "table_unsafe_ptr",
+            Kernel::Fsm => "fsm_unsafe_4channel",
+        }
+    }
+
+    /// The longest code the kernel can decode.
+    pub fn max_depth(self) -> u32 {
+        match self {
+            Kernel::Table => MAX_TABLE_DEPTH,
+            Kernel::Flat | Kernel::Fsm => MAX_DEPTH,
+        }
+    }
+
+    /// Decodes a packet whose kernel `try_kernel` picked with `tie_break`.
+    pub fn decode_packet(self, content: &[u8], tie_break: TieBreakPolicy) -> String {
+        match self {
+            Kernel::Flat => {
+                with_tie_break!(tie_break, flat_unsafe_ptr::decode_packet_with(content))
+            }
+            Kernel::Table => {
+                with_tie_break!(tie_break, table_unsafe_ptr::decode_packet_with(content))
+            }
+            Kernel::Fsm => {
+                with_tie_break!(tie_break, fsm_unsafe_4channel::decode_packet_with(content))
+            }
+        }
+    }
+}
+
+/// Picks the fastest decoder for the packet's decoded size.
+pub fn kernel(packet: &Packet) -> Kernel {
+    match packet.decoded_bytes_len {
+        len if len <= FLAT_MAX_DECODED_LEN => Kernel::Flat,
+        len if len >= FSM_MIN_DECODED_LEN => Kernel::Fsm,
+        _ => Kernel::Table,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// How the encoder broke frequency ties when building its tree, `Classic`
+    /// for the BDO servers.
+    pub tie_break: TieBreakPolicy,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    Packet(PacketError),
+    Tree(TreeError),
+    Record(RecordError),
+    /// The bitstream decodes to `decoded_len` symbols instead of the header's
+    /// `len_decoded_data`, which validation can't tell without decoding it.
+    MessageLength {
+        decoded_len: usize,
+        expected_len: usize,
+    },
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Packet(err) => write!(f, "invalid packet: {err}"),
+            DecodeError::Tree(err) => write!(f, "invalid symbol table: {err}"),
+            DecodeError::Record(err) => write!(f, "invalid message: {err}"),
+            DecodeError::MessageLength {
+                decoded_len,
+                expected_len,
+            } => write!(
+                f,
+                "the message decodes to {decoded_len} symbols but len_decoded_data is {expected_len}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Packet(err) => Some(err),
+            DecodeError::Tree(err) => Some(err),
+            DecodeError::Record(err) => Some(err),
+            DecodeError::MessageLength { .. } => None,
+        }
+    }
+}
+
+impl From<PacketError> for DecodeError {
+    fn from(err: PacketError) -> Self {
+        DecodeError::Packet(err)
+    }
+}
+
+impl From<TreeError> for DecodeError {
+    fn from(err: TreeError) -> Self {
+        DecodeError::Tree(err)
+    }
+}
+
+impl From<RecordError> for DecodeError {
+    fn from(err: RecordError) -> Self {
+        DecodeError::Record(err)
+    }
+}
+
+pub fn decode_packet(content: &[u8]) -> String {
+    let packet = &Packet::new(content);
+    kernel(packet).decode_packet(content, TieBreakPolicy::Classic)
+}
+
+/// Validates the packet with `Packet::try_new` and its tree and bitstream
+/// length with `validate_packet` before decoding it.
+pub fn try_decode_packet(content: &[u8]) -> Result<String, DecodeError> {
+    try_decode_packet_with(content, DecodeOptions::default())
+}
+
+/// `try_decode_packet` with the options of another encoder.
+pub fn try_decode_packet_with(
+    content: &[u8],
+    options: DecodeOptions,
+) -> Result<String, DecodeError> {
+    let packet = &Packet::try_new(content)?;
+    let kernel = try_kernel(packet, options.tie_break)?;
+    Ok(kernel.decode_packet(content, options.tie_break))
+}
+
+/// The kernel of a packet checked by `Packet::try_new`, after checking its tree
+/// and bitstream length with `validate_packet`.
+///
+/// Trees with codes longer than the table kernel decodes go to the FSM, which
+/// decodes a byte of any code at a time.
+pub fn try_kernel(packet: &Packet, tie_break: TieBreakPolicy) -> Result<Kernel, TreeError> {
+    let depth = with_tie_break!(tie_break, validate_packet_with(&packet.header(), MAX_DEPTH))?;
+    Ok(match kernel(packet) {
+        kernel if depth > kernel.max_depth() => Kernel::Fsm,
+        kernel => kernel,
+    })
+}
+
+pub fn decode_records(content: &[u8]) -> Result<Vec<MarketRecord>, DecodeError> {
+    let message = try_decode_packet(content)?;
+    let records = parse_records(&message).collect::<Result<_, _>>()?;
+    Ok(records)
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::min_heap::Classic;
+    use common::test_cases::*;
+
+    #[test]
+    fn decodes_packet() {
+        let decoded_message = super::decode_packet(&TEST_BYTES);
+        assert_eq!(decoded_message, EXPECTED_MESSAGE);
+    }
+
+    #[test]
+    fn selects_kernel_by_size() {
+        let kernels: Vec<_> = ALL_CASES
+            .iter()
+            .map(|case| kernel(&Packet::new(&case.request())))
+            .collect();
+        use Kernel::*;
+        assert_eq!(kernels, [Fsm, Fsm, Fsm, Fsm, Table, Flat]);
+    }
+
+    #[test]
+    fn decodes_records() {
+        let records = super::decode_records(&TEST_BYTES).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].item, 53802);
+        assert_eq!(records[1].cumulative_count, 70000);
+
+        assert!(matches!(
+            super::decode_records(&TEST_BYTES[..100]),
+            Err(DecodeError::Packet(PacketError::Truncated { .. }))
+        ));
+    }
+
+    #[test]
+    fn validates_trees() {
+        // `len_decoded_data` of the 12 symbol test packet.
+        let mut content = TEST_BYTES;
+        content[12 + 8 * 12 + 8] += 1;
+        assert!(matches!(
+            super::try_decode_packet(&content),
+            Err(DecodeError::Tree(TreeError::FrequencySum { .. }))
+        ));
+
+        // Fibonacci symbol counts give an 11 bit code, too long for the tables.
+        let message = fibonacci_message();
+        let content = common::synthetic::encode_packet(&message);
+        let packet = &Packet::new(&content);
+        assert_eq!(kernel(packet), Kernel::Table);
+        assert_eq!(try_kernel(packet, TieBreakPolicy::Classic), Ok(Kernel::Fsm));
+        assert_eq!(
+            super::try_decode_packet(&content).as_deref(),
+            Ok(std::str::from_utf8(&message).unwrap())
+        );
+    }
+
+    #[test]
+    fn bounds_malformed_bitstreams() {
+        use common::packet::PacketBuilder;
+
+        // Both pass `Packet::try_new`, the zero bytes repeat the all-zero code
+        // well past `len_decoded_data` and the empty message has no bits.
+        let packet = Packet::new(&TEST_BYTES);
+        let decoded_len = packet.decoded_bytes_len as usize;
+        let zeros = PacketBuilder::from_packet(&packet)
+            .message(&[0; 4000])
+            .build();
+        let empty = PacketBuilder::from_packet(&packet).message(&[]).build();
+
+        for kernel in [Kernel::Flat, Kernel::Table, Kernel::Fsm] {
+            let decoded = kernel.decode_packet(&zeros, TieBreakPolicy::Classic);
+            assert_eq!(decoded.len(), decoded_len, "{kernel:?}");
+        }
+        assert!(matches!(
+            super::try_decode_packet(&zeros),
+            Err(DecodeError::Tree(TreeError::BitstreamMismatch { .. }))
+        ));
+        assert_eq!(
+            super::try_decode_packet(&empty),
+            Err(DecodeError::Tree(TreeError::EmptyBitstream))
+        );
+    }
+
+    #[test]
+    fn decodes_with_tie_break_policies() {
+        use common::min_heap::{InsertionOrder, ReverseInsertionOrder};
+        use common::synthetic::encode_packet_with;
+
+        // The test message has tied frequencies, repeated for each kernel.
+        for repeats in [1, 100, 300] {
+            let message = EXPECTED_MESSAGE.repeat(repeats);
+            let encoded = [
+                encode_packet_with::<Classic>(message.as_bytes()),
+                encode_packet_with::<InsertionOrder>(message.as_bytes()),
+                encode_packet_with::<ReverseInsertionOrder>(message.as_bytes()),
+            ];
+            for (tie_break, content) in TieBreakPolicy::ALL.into_iter().zip(&encoded) {
+                let options = DecodeOptions { tie_break };
+                let decoded = try_decode_packet_with(content, options);
+                assert_eq!(decoded.as_deref(), Ok(message.as_str()), "{tie_break:?}");
+            }
+            // The codes differ, so decoding needs the encoder's policy.
+            assert!(encoded[0] != encoded[1] && encoded[0] != encoded[2]);
+        }
+    }
+
+    // Run with `cargo test -p adaptive --features metrics`.
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn records_phase_timings() {
+        use common::metrics::{set_recorder, Phase};
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::time::Duration;
+
+        static CALLS: [AtomicU32; 5] = [const { AtomicU32::new(0) }; 5];
+        fn record(phase: Phase, _: Duration) {
+            CALLS[phase as usize].fetch_add(1, Ordering::Relaxed);
+        }
+
+        set_recorder(Some(record));
+        for case in ALL_CASES {
+            super::decode_packet(&case.request());
+        }
+        set_recorder(None);
+        let calls = CALLS.each_ref().map(|calls| calls.load(Ordering::Relaxed));
+        // Flat decodes without tables, the packet phase is never recorded.
+        assert!(calls[Phase::Parse as usize] >= 6);
+        assert!(calls[Phase::Tree as usize] >= 6);
+        assert!(calls[Phase::Table as usize] >= 5);
+        assert!(calls[Phase::Decode as usize] >= 6);
+        assert_eq!(calls[Phase::Packet as usize], 0);
+    }
+}
+
+// MARK: Benches
+
+use common::test_cases::BENCH_SAMPLE_COUNT;
+#[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
+mod bench {
+    use common::test_cases::*;
+
+    use divan::{black_box, Bencher};
+
+    #[divan::bench(args = ALL_CASES)]
+    fn decode_packet(bencher: Bencher, case: &Case) {
+        let content = case.request();
+        bencher.bench_local(move || {
+            super::decode_packet(black_box(&content));
+        });
+    }
+
+    #[divan::bench(args = ALL_CASES)]
+    fn try_decode_packet(bencher: Bencher, case: &Case) {
+        let content = case.request();
+        bencher.bench_local(move || {
+            super::try_decode_packet(black_box(&content)).unwrap();
+        });
+    }
+}
@@ -0,0 +1,12 @@
+pub(crate) mod batch;
+pub(crate) mod decoder;
+pub use batch::{decode_batch, decode_batch_with, BatchItem, BatchOptions};
+pub use decoder::{
+    decode_packet, decode_records, kernel, try_decode_packet, try_decode_packet_with, try_kernel,
+    DecodeError, DecodeOptions, Kernel, FLAT_MAX_DECODED_LEN, FSM_MIN_DECODED_LEN,
+};
+
+#[cfg(feature = "tokio")]
+pub(crate) mod stream;
+#[cfg(feature = "tokio")]
+pub use stream::{decode_from_reader, record_stream, RecordStream};
@@ -0,0 +1,414 @@
+use crate::decoder::{try_decode_packet, DecodeError};
+use common::packet::{PacketError, PacketHeader, MAX_SYMBOLS};
+use common::records::{parse_record, MarketRecord, RecordError};
+use common::tree::{packet_tree, HuffmanTree, MAX_DEPTH};
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+const READ_CHUNK_LEN: usize = 8 * 1024;
+
+/// Reads a packet from `reader` and decodes it.
+///
+/// The header is checked as soon as it arrives. Invalid packets are returned
+/// as `InvalidData` errors, or `UnexpectedEof` when the packet is truncated,
+/// wrapping the `DecodeError`. Nothing past the packet is read.
+pub async fn decode_from_reader<R: AsyncRead + Unpin>(mut reader: R) -> io::Result<String> {
+    let mut content = Vec::new();
+    fill(&mut reader, &mut content, PacketHeader::PREFIX_LEN).await?;
+    if content.len() == PacketHeader::PREFIX_LEN {
+        let len = header_len(&content);
+        fill(&mut reader, &mut content, len).await?;
+    }
+    let header = PacketHeader::try_new(&content).map_err(into_io_error)?;
+    let len = header.header_len() + header.encoded_bytes_len as usize;
+    fill(&mut reader, &mut content, len).await?;
+    try_decode_packet(&content).map_err(into_io_error)
+}
+
+/// Streams the records of the packet read from `reader`, decoding the message
+/// as it arrives.
+///
+/// The header is checked once its `24 + 8 * symbol_count` bytes arrived and
+/// every record is yielded as soon as its '|' is decoded. Errors are reported
+/// like `decode_from_reader` and end the stream.
+pub fn record_stream<R: AsyncRead + Unpin>(reader: R) -> RecordStream<R> {
+    RecordStream {
+        reader,
+        buf: Vec::with_capacity(PacketHeader::len_for(MAX_SYMBOLS as u32)),
+        state: State::Header,
+        pending: VecDeque::new(),
+    }
+}
+
+pub struct RecordStream<R> {
+    reader: R,
+    buf: Vec<u8>,
+    state: State,
+    pending: VecDeque<MarketRecord>,
+}
+
+enum State {
+    Header,
+    Message(MessageDecoder),
+    Done,
+}
+
+impl<R: AsyncRead + Unpin> Stream for RecordStream<R> {
+    type Item = io::Result<MarketRecord>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(record) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(record)));
+            }
+            let result = match &mut this.state {
+                State::Done => return Poll::Ready(None),
+                State::Header => {
+                    let needed = match this.buf.len() {
+                        len if len < PacketHeader::PREFIX_LEN => PacketHeader::PREFIX_LEN,
+                        _ => header_len(&this.buf),
+                    };
+                    if this.buf.len() < needed
+                        && ready!(poll_fill(&mut this.reader, cx, &mut this.buf, needed))? > 0
+                    {
+                        continue;
+                    }
+                    PacketHeader::try_new(&this.buf)
+                        .map_err(DecodeError::from)
+                        .and_then(MessageDecoder::new)
+                        .map(|decoder| {
+                            this.buf.clear();
+                            this.state = State::Message(decoder);
+                        })
+                }
+                State::Message(decoder) if decoder.remaining_bytes == 0 => {
+                    decoder.finish(&mut this.pending).map(|()| {
+                        this.state = State::Done;
+                    })
+                }
+                State::Message(decoder) => {
+                    let len = decoder.remaining_bytes.min(READ_CHUNK_LEN);
+                    this.buf.clear();
+                    match ready!(poll_fill(&mut this.reader, cx, &mut this.buf, len))? {
+                        0 => Err(decoder.truncated().into()),
+                        _ => decoder.decode(&this.buf, &mut this.pending),
+                    }
+                }
+            };
+            if let Err(err) = result {
+                this.state = State::Done;
+                return Poll::Ready(Some(Err(into_io_error(err))));
+            }
+        }
+    }
+}
+
+// Decodes the message bit by bit as its bytes arrive.
+struct MessageDecoder {
+    tree: HuffmanTree,
+    node: u8,
+    header_len: usize,
+    encoded_bytes_len: usize,
+    remaining_bytes: usize,
+    decoded_len: usize,
+    remaining_symbols: usize,
+    record: String,
+    record_index: usize,
+    record_offset: usize,
+}
+
+impl MessageDecoder {
+    fn new(header: PacketHeader) -> Result<Self, DecodeError> {
+        header.check_bitstream_len()?;
+        let header_len = header.header_len();
+        let encoded_bytes_len = header.encoded_bytes_len as usize;
+        if header.len != (header_len + encoded_bytes_len) as u64 {
+            return Err(PacketError::LengthMismatch {
+                len: header.len,
+                content_len: header_len + encoded_bytes_len,
+            }
+            .into());
+        }
+        let tree = packet_tree(&header, MAX_DEPTH)?;
+        Ok(MessageDecoder {
+            node: tree.root(),
+            tree,
+            header_len,
+            encoded_bytes_len,
+            remaining_bytes: encoded_bytes_len,
+            decoded_len: header.decoded_bytes_len as usize,
+            remaining_symbols: header.decoded_bytes_len as usize,
+            record: String::new(),
+            record_index: 0,
+            record_offset: 0,
+        })
+    }
+
+    fn decode(
+        &mut self,
+        bytes: &[u8],
+        records: &mut VecDeque<MarketRecord>,
+    ) -> Result<(), DecodeError> {
+        self.remaining_bytes -= bytes.len();
+        for &byte in bytes {
+            let mut bits = byte;
+            for _ in 0..8 {
+                if self.remaining_symbols == 0 {
+                    return Ok(());
+                }
+                let (node, symbol) = self.tree.child(self.node, bits >> 7);
+                self.node = node;
+                bits <<= 1;
+                if let Some(symbol) = symbol {
+                    self.node = self.tree.root();
+                    self.remaining_symbols -= 1;
+                    match symbol {
+                        b'|' => records.push_back(self.parse_record()?),
+                        _ => self.record.push(symbol as char),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // A message that doesn't end with '|' still holds a last record.
+    fn finish(&mut self, records: &mut VecDeque<MarketRecord>) -> Result<(), DecodeError> {
+        if self.remaining_symbols > 0 {
+            return Err(DecodeError::MessageLength {
+                decoded_len: self.decoded_len - self.remaining_symbols,
+                expected_len: self.decoded_len,
+            });
+        }
+        if !self.record.is_empty() {
+            records.push_back(self.parse_record()?);
+        }
+        Ok(())
+    }
+
+    fn parse_record(&mut self) -> Result<MarketRecord, RecordError> {
+        let record = parse_record(&self.record, self.record_index, self.record_offset)?;
+        self.record_index += 1;
+        self.record_offset += self.record.len() + 1;
+        self.record.clear();
+        Ok(record)
+    }
+
+    fn truncated(&self) -> PacketError {
+        PacketError::Truncated {
+            field: "message",
+            offset: self.header_len,
+            needed: self.encoded_bytes_len,
+            available: self.encoded_bytes_len - self.remaining_bytes,
+        }
+    }
+}
+
+// The header length once `len_symbol_table` is known, or just the prefix for
+// an invalid count so `PacketHeader::try_new` reports it.
+fn header_len(prefix: &[u8]) -> usize {
+    let symbol_count = u32::from_le_bytes(prefix[8..12].try_into().unwrap());
+    if (2..=MAX_SYMBOLS as u32).contains(&symbol_count) {
+        PacketHeader::len_for(symbol_count)
+    } else {
+        PacketHeader::PREFIX_LEN
+    }
+}
+
+fn into_io_error(err: impl Into<DecodeError>) -> io::Error {
+    let err = err.into();
+    let kind = match err {
+        DecodeError::Packet(PacketError::Truncated { .. }) => io::ErrorKind::UnexpectedEof,
+        _ => io::ErrorKind::InvalidData,
+    };
+    io::Error::new(kind, err)
+}
+
+// Reads until `content` holds `len` bytes or the reader is exhausted.
+//
+// `len` comes from the header, so `content` only grows by `READ_CHUNK_LEN` at a
+// time as bytes arrive instead of allocating it all up front.
+async fn fill<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    content: &mut Vec<u8>,
+    len: usize,
+) -> io::Result<()> {
+    let mut filled = content.len();
+    while filled < len {
+        content.resize(len.min(filled + READ_CHUNK_LEN), 0);
+        match reader.read(&mut content[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    content.truncate(filled);
+    Ok(())
+}
+
+// Reads once into `buf` up to `len` bytes, at most `READ_CHUNK_LEN` of them,
+// returning the number of bytes read.
+fn poll_fill<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    buf: &mut Vec<u8>,
+    len: usize,
+) -> Poll<io::Result<usize>> {
+    let start = buf.len();
+    buf.resize(len.min(start + READ_CHUNK_LEN), 0);
+    let mut read_buf = ReadBuf::new(&mut buf[start..]);
+    let result = Pin::new(reader).poll_read(cx, &mut read_buf);
+    let n = read_buf.filled().len();
+    buf.truncate(start + n);
+    ready!(result)?;
+    Poll::Ready(Ok(n))
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::decode_records;
+    use common::packet::{Packet, PacketBuilder};
+    use common::test_cases::*;
+    use std::time::Duration;
+    use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+    // Writes `content` in small chunks through a small duplex buffer so the
+    // reader sees partial headers and messages.
+    fn feed(content: Vec<u8>) -> DuplexStream {
+        let (reader, mut writer) = duplex(64);
+        tokio::spawn(async move {
+            for chunk in content.chunks(37) {
+                writer.write_all(chunk).await.unwrap();
+            }
+        });
+        reader
+    }
+
+    async fn collect<R: AsyncRead + Unpin>(
+        mut stream: RecordStream<R>,
+    ) -> io::Result<Vec<MarketRecord>> {
+        let mut records = Vec::new();
+        while let Some(record) =
+            std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+        {
+            records.push(record?);
+        }
+        Ok(records)
+    }
+
+    #[tokio::test]
+    async fn decodes_from_reader() {
+        for case in ALL_CASES {
+            let content = case.request();
+            let decoded = decode_from_reader(feed(content.clone())).await.unwrap();
+            assert_eq!(decoded, try_decode_packet(&content).unwrap(), "{case}");
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_records() {
+        for case in ALL_CASES {
+            let content = case.request();
+            let records = collect(record_stream(feed(content.clone()))).await.unwrap();
+            assert_eq!(records, decode_records(&content).unwrap(), "{case}");
+        }
+    }
+
+    #[tokio::test]
+    async fn yields_records_before_the_message_ends() {
+        let content = ALL_CASES[0].request();
+        let (reader, mut writer) = duplex(content.len());
+        writer
+            .write_all(&content[..content.len() / 2])
+            .await
+            .unwrap();
+
+        let mut stream = record_stream(reader);
+        let first = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx));
+        let first = tokio::time::timeout(Duration::from_secs(5), first)
+            .await
+            .expect("no record before the rest of the message");
+        assert_eq!(
+            first.unwrap().unwrap(),
+            decode_records(&content).unwrap()[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_truncated_and_invalid_packets() {
+        let err = decode_from_reader(&TEST_BYTES[..100]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let err = collect(record_stream(&TEST_BYTES[..128]))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(matches!(
+            err.into_inner()
+                .unwrap()
+                .downcast::<DecodeError>()
+                .as_deref(),
+            Ok(DecodeError::Packet(PacketError::Truncated {
+                field: "message",
+                offset: 120,
+                needed: 16,
+                available: 8,
+            }))
+        ));
+
+        let mut corrupted = TEST_BYTES;
+        corrupted[16] = b'a';
+        let err = collect(record_stream(&corrupted[..])).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn reports_messages_ending_early() {
+        // The 6 bit code of '9' over the whole bitstream, 21 symbols instead
+        // of the 40 of the header.
+        let bits: Vec<u8> = b"110111"
+            .iter()
+            .cycle()
+            .take(128)
+            .map(|bit| bit - b'0')
+            .collect();
+        let message: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, bit| acc << 1 | bit))
+            .collect();
+        let content = PacketBuilder::from_packet(&Packet::new(&TEST_BYTES))
+            .message(&message)
+            .build();
+        let err = collect(record_stream(&content[..])).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.into_inner()
+                .unwrap()
+                .downcast::<DecodeError>()
+                .as_deref(),
+            Ok(DecodeError::MessageLength {
+                decoded_len: 21,
+                expected_len: 40,
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn grows_the_buffer_as_bytes_arrive() {
+        // As if the header claimed a ~4GB message but only 136 bytes arrive.
+        let mut filled = Vec::new();
+        fill(&mut &TEST_BYTES[..], &mut filled, u32::MAX as usize)
+            .await
+            .unwrap();
+        assert_eq!(filled, TEST_BYTES);
+        assert!(filled.capacity() <= 2 * READ_CHUNK_LEN);
+    }
+}
@@ -1,11 +1,29 @@
 use bit_vec::BitVec;
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::Packet;
+use common::profile::Phase;
+use common::tree::parent_frequency;
+
+use crate::trace::{TraceEvent, TreePath};
 
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
-    let tree = &huffman_tree(packet);
-    decode_message(packet, tree)
+    let tree = &time(Phase::Tree, || huffman_tree(packet));
+    time(Phase::Decode, || decode_message(packet, tree))
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || None,
+        |packet, tree| *tree = Some(huffman_tree(packet)),
+        |_| (),
+        |packet, tree, _| decode_message(packet, tree.as_ref().unwrap()),
+    );
 }
 
 fn decode_message(packet: &Packet, tree: &HeapNode) -> String {
@@ -36,6 +54,39 @@ fn decode_message(packet: &Packet, tree: &HeapNode) -> String {
     decoded
 }
 
+/// Decodes `packet` like `decode_packet`, recording every symbol's code.
+pub fn decode_trace(packet: &Packet) -> Vec<TraceEvent> {
+    let tree = &huffman_tree(packet);
+    let mut trace = Vec::with_capacity(packet.decoded_bytes_len as usize);
+    let mut current = tree;
+    let mut node = TreePath::ROOT;
+
+    let mut bits = BitVec::from_bytes(packet.encoded_message);
+    bits.truncate(packet.bitstream_len as usize);
+
+    for (bit_offset, bit) in bits.iter().enumerate() {
+        let child = if bit {
+            &current.right_child
+        } else {
+            &current.left_child
+        };
+        current = child.as_deref().expect("Should have child!");
+        node = node.child(bit);
+
+        if let Some(symbol) = current.symbol {
+            trace.push(TraceEvent {
+                bit_offset: bit_offset + 1 - node.len as usize,
+                bits_consumed: node.len,
+                symbol,
+                node,
+            });
+            current = tree;
+            node = TreePath::ROOT;
+        }
+    }
+    trace
+}
+
 fn huffman_tree(packet: &Packet) -> HeapNode {
     let mut heap = symbols_heap(packet);
     let mut size = heap.len();
@@ -49,8 +100,8 @@ fn huffman_tree(packet: &Packet) -> HeapNode {
     heap.pop()
 }
 
-fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
+fn symbols_heap(packet: &Packet) -> MinHeapChecked<HeapNode> {
+    let mut heap = MinHeapChecked::<HeapNode>::new();
     let bytes = &packet.symbol_frequency_bytes;
     for i in 0..packet.symbol_count {
         let pos = (i as usize) * 8;
@@ -98,7 +149,7 @@ impl HeapNode {
     fn new_parent(left: Self, right: Self) -> Self {
         HeapNode {
             symbol: None,
-            frequency: left.frequency + right.frequency,
+            frequency: parent_frequency(left.frequency, right.frequency),
             left_child: Some(Box::new(left)),
             right_child: Some(Box::new(right)),
         }
@@ -110,6 +161,8 @@ impl HeapNode {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::trace::first_divergence;
     use common::test_cases::*;
 
     #[test]
@@ -117,6 +170,76 @@ mod tests {
         let decoded_message = super::decode_packet(&TEST_BYTES);
         assert_eq!(decoded_message, EXPECTED_MESSAGE);
     }
+
+    #[test]
+    fn traces_packet() {
+        let packet = &Packet::new(&TEST_BYTES);
+        let trace = decode_trace(packet);
+        let symbols: Vec<u8> = trace.iter().map(|event| event.symbol).collect();
+        assert_eq!(symbols, EXPECTED_MESSAGE.as_bytes());
+
+        let mut bit_offset = 0;
+        for event in &trace {
+            assert_eq!(event.bit_offset, bit_offset);
+            assert_eq!(event.node.len, event.bits_consumed);
+            let (_, prefix) = EXPECTED_PREFIXES
+                .iter()
+                .find(|(symbol, _)| symbol.as_bytes() == [event.symbol])
+                .unwrap();
+            assert_eq!(&event.node.to_string(), prefix);
+            bit_offset += event.bits_consumed as usize;
+        }
+        assert_eq!(first_divergence(&trace, EXPECTED_MESSAGE.as_bytes()), None);
+    }
+
+    fn step(node: &HeapNode, right: bool) -> &HeapNode {
+        let child = if right {
+            &node.right_child
+        } else {
+            &node.left_child
+        };
+        child.as_deref().unwrap()
+    }
+
+    #[test]
+    fn finds_first_divergence() {
+        let packet = &Packet::new(&TEST_BYTES);
+        let trace = decode_trace(packet);
+        let expected = EXPECTED_MESSAGE.as_bytes();
+
+        for index in [0, 7, 20, expected.len() - 1] {
+            let mut actual = expected.to_vec();
+            actual[index] = b'?';
+            let divergence = first_divergence(&trace, &actual).unwrap();
+            assert_eq!(divergence.index, index);
+            assert_eq!(divergence.expected, Some(trace[index]));
+            assert_eq!(divergence.actual, Some(b'?'));
+
+            // Decoding the byte's bits from `byte_state` ends on the symbol.
+            let tree = &huffman_tree(packet);
+            let bits = BitVec::from_bytes(packet.encoded_message);
+            let state = divergence.byte_state;
+            let mut current = (0..state.len)
+                .rev()
+                .fold(tree, |node, i| step(node, state.bits >> i & 1 == 1));
+            let event = trace[index];
+            let last_bit = event.bit_offset + event.bits_consumed as usize - 1;
+            let mut last_symbol = None;
+            for bit in (last_bit / 8 * 8..=last_bit).map(|i| bits[i]) {
+                current = step(current, bit);
+                if let Some(symbol) = current.symbol {
+                    last_symbol = Some(symbol);
+                    current = tree;
+                }
+            }
+            assert_eq!(last_symbol, Some(event.symbol));
+        }
+
+        let short = first_divergence(&trace, &expected[..10]).unwrap();
+        assert_eq!((short.index, short.actual), (10, None));
+        let long = first_divergence(&trace, format!("{EXPECTED_MESSAGE}|").as_bytes()).unwrap();
+        assert_eq!((long.index, long.expected), (expected.len(), None));
+    }
 }
 
 // MARK: Benches
@@ -1,2 +1,6 @@
+#![forbid(unsafe_code)]
+
 pub(crate) mod decoder;
-pub use decoder::decode_packet;
+pub(crate) mod trace;
+pub use decoder::{decode_packet, decode_trace, profile};
+pub use trace::{first_divergence, Divergence, TraceEvent, TreePath};
@@ -0,0 +1,131 @@
+// Symbol level traces of the reference decoder for pinpointing where another
+// decoder's output diverges from it.
+
+use std::fmt;
+
+/// A tree node as its path from the root, the path of a leaf is its code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TreePath {
+    /// The path's directions, the last one in the lowest bit.
+    pub bits: u32,
+    pub len: u8,
+}
+
+impl TreePath {
+    pub const ROOT: TreePath = TreePath { bits: 0, len: 0 };
+
+    pub fn child(self, right: bool) -> Self {
+        TreePath {
+            bits: self.bits << 1 | right as u32,
+            len: self.len + 1,
+        }
+    }
+
+    /// The node `len` steps from the root along this path.
+    pub fn prefix(self, len: u8) -> Self {
+        TreePath {
+            bits: self.bits >> (self.len - len),
+            len,
+        }
+    }
+}
+
+impl fmt::Display for TreePath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.len == 0 {
+            return f.write_str("root");
+        }
+        write!(f, "{:0width$b}", self.bits, width = self.len as usize)
+    }
+}
+
+/// One decoded symbol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Offset of the code's first bit in the encoded message.
+    pub bit_offset: usize,
+    pub bits_consumed: u8,
+    pub symbol: u8,
+    /// The symbol's leaf.
+    pub node: TreePath,
+}
+
+/// Where a decoder's output first differs from the reference trace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the first differing output byte.
+    pub index: usize,
+    /// The reference's symbol, `None` if the output is longer than the trace.
+    pub expected: Option<TraceEvent>,
+    /// The output's byte, `None` if it ends early.
+    pub actual: Option<u8>,
+    /// The node a byte at a time decoder, e.g. an FSM, starts the byte holding
+    /// the expected code's last bit from.
+    pub byte_state: TreePath,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol =
+            |symbol: Option<u8>| symbol.map_or("end".to_owned(), |s| format!("{:?}", s as char));
+        write!(
+            f,
+            "output byte {}: expected {}, got {}",
+            self.index,
+            symbol(self.expected.map(|event| event.symbol)),
+            symbol(self.actual)
+        )?;
+        if let Some(event) = self.expected {
+            write!(
+                f,
+                " (code {} at bit {}, ending in byte {} entered at node {})",
+                event.node,
+                event.bit_offset,
+                (event.bit_offset + event.bits_consumed as usize - 1) / 8,
+                self.byte_state
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The first byte of `actual` differing from the symbols of `trace`, `None` if
+/// they match.
+pub fn first_divergence(trace: &[TraceEvent], actual: &[u8]) -> Option<Divergence> {
+    let expected = trace.iter().map(|event| Some(event.symbol));
+    let index = expected
+        .chain(core::iter::repeat(None))
+        .zip(
+            actual
+                .iter()
+                .copied()
+                .map(Some)
+                .chain(core::iter::repeat(None)),
+        )
+        .take(trace.len().max(actual.len()))
+        .position(|(expected, actual)| expected != actual)?;
+
+    let expected = trace.get(index).copied();
+    let byte_state = expected.map_or(TreePath::ROOT, |event| byte_state(trace, index, event));
+    Some(Divergence {
+        index,
+        expected,
+        actual: actual.get(index).copied(),
+        byte_state,
+    })
+}
+
+// The byte's first bit is the root if a code starts there, otherwise the node
+// that the bits of the code holding it reach.
+fn byte_state(trace: &[TraceEvent], index: usize, event: TraceEvent) -> TreePath {
+    let last_bit = event.bit_offset + event.bits_consumed as usize - 1;
+    let byte_start = last_bit / 8 * 8;
+    trace[..=index]
+        .iter()
+        .rev()
+        .take_while(|code| code.bit_offset + code.bits_consumed as usize > byte_start)
+        .find(|code| code.bit_offset < byte_start)
+        .map_or(TreePath::ROOT, |code| {
+            code.node.prefix((byte_start - code.bit_offset) as u8)
+        })
+}
@@ -0,0 +1,122 @@
+/* Generated with cbindgen from bdo_huffman_c/src/lib.rs, do not edit. */
+
+#ifndef BDO_HUFFMAN_H
+#define BDO_HUFFMAN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Result codes of the `bdo_huffman_*` functions.
+ *
+ * The packet and tree errors mirror `PacketError` and `TreeError`.
+ */
+typedef enum BdoHuffmanStatus {
+  BDO_HUFFMAN_STATUS_OK = 0,
+  /**
+   * A required pointer argument was null.
+   */
+  BDO_HUFFMAN_STATUS_NULL_POINTER = 1,
+  /**
+   * `out_cap` is smaller than the decoded message, `*out_len` is set to the
+   * required capacity.
+   */
+  BDO_HUFFMAN_STATUS_BUFFER_TOO_SMALL = 2,
+  /**
+   * The content ends before a field could be read.
+   */
+  BDO_HUFFMAN_STATUS_TRUNCATED = 3,
+  /**
+   * `len_content` doesn't match the content length.
+   */
+  BDO_HUFFMAN_STATUS_LENGTH_MISMATCH = 4,
+  /**
+   * The symbol table has fewer than 2 or more than 12 entries.
+   */
+  BDO_HUFFMAN_STATUS_SYMBOL_COUNT = 5,
+  /**
+   * A symbol isn't one of `-0123456789|`.
+   */
+  BDO_HUFFMAN_STATUS_INVALID_SYMBOL = 6,
+  /**
+   * A symbol appears more than once.
+   */
+  BDO_HUFFMAN_STATUS_DUPLICATE_SYMBOL = 7,
+  /**
+   * A symbol's strz padding bytes aren't zero.
+   */
+  BDO_HUFFMAN_STATUS_NON_ZERO_PADDING = 8,
+  /**
+   * `len_bitstream` is larger than the encoded message.
+   */
+  BDO_HUFFMAN_STATUS_BITSTREAM_LENGTH = 9,
+  /**
+   * Building the Huffman tree overflows the `u32` frequencies.
+   */
+  BDO_HUFFMAN_STATUS_FREQUENCY_OVERFLOW = 10,
+  /**
+   * A code is longer than the selected decoder can decode.
+   */
+  BDO_HUFFMAN_STATUS_TREE_DEPTH = 11,
+  /**
+   * The symbol frequencies don't add up to `len_decoded_data`.
+   */
+  BDO_HUFFMAN_STATUS_FREQUENCY_SUM = 12,
+  /**
+   * `len_encoded_data` is 0.
+   */
+  BDO_HUFFMAN_STATUS_EMPTY_BITSTREAM = 13,
+  /**
+   * `len_bitstream` doesn't match the codes of the symbol frequencies.
+   */
+  BDO_HUFFMAN_STATUS_BITSTREAM_MISMATCH = 14,
+  /**
+   * The bitstream doesn't decode to `len_decoded_data` symbols. Validation
+   * only checks the header, so a corrupted message is found while decoding.
+   */
+  BDO_HUFFMAN_STATUS_MESSAGE_LENGTH = 15,
+  /**
+   * The decoder panicked on a validated packet, this is a bug.
+   */
+  BDO_HUFFMAN_STATUS_PANIC = 255,
+} BdoHuffmanStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Decodes the packet in `content[0..content_len]` into `out[0..out_cap]`.
+ *
+ * On success `*out_len` is the length of the decoded message, which is not
+ * null terminated. When `out_cap` is too small nothing is written to `out`
+ * and `*out_len` is set to the capacity needed, so passing an `out_cap` of 0
+ * queries the decoded length from the packet's header without decoding it.
+ * `out` may be null when `out_cap` is 0.
+ *
+ * The packet's header is validated before it is decoded and a message that
+ * decodes to another length than `len_decoded_data` is reported as
+ * `MessageLength`, nothing is written to `out` then. A panic while decoding is
+ * caught and reported as `Panic` instead of unwinding into C.
+ *
+ * # Safety
+ *
+ * `content` must be valid for reads of `content_len` bytes, `out` must be
+ * valid for writes of `out_cap` bytes and `out_len` must be valid for a write.
+ */
+enum BdoHuffmanStatus bdo_huffman_decode(const uint8_t *content,
+                                         size_t content_len,
+                                         uint8_t *out,
+                                         size_t out_cap,
+                                         size_t *out_len);
+
+/**
+ * A static, null terminated description of `status`.
+ */
+const char *bdo_huffman_status_message(enum BdoHuffmanStatus status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BDO_HUFFMAN_H */
@@ -0,0 +1,162 @@
+// C ABI for the `adaptive` decoder, see `include/bdo_huffman.h`.
+//
+// The header is generated by cbindgen and `tests/c_api.rs` checks that it is
+// up to date, regenerate it with:
+//     cbindgen --config cbindgen.toml --output include/bdo_huffman.h
+
+use common::min_heap::TieBreakPolicy;
+use common::packet::{Packet, PacketError};
+use common::tree::TreeError;
+
+/// Result codes of the `bdo_huffman_*` functions.
+///
+/// The packet and tree errors mirror `PacketError` and `TreeError`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BdoHuffmanStatus {
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer = 1,
+    /// `out_cap` is smaller than the decoded message, `*out_len` is set to the
+    /// required capacity.
+    BufferTooSmall = 2,
+    /// The content ends before a field could be read.
+    Truncated = 3,
+    /// `len_content` doesn't match the content length.
+    LengthMismatch = 4,
+    /// The symbol table has fewer than 2 or more than 12 entries.
+    SymbolCount = 5,
+    /// A symbol isn't one of `-0123456789|`.
+    InvalidSymbol = 6,
+    /// A symbol appears more than once.
+    DuplicateSymbol = 7,
+    /// A symbol's strz padding bytes aren't zero.
+    NonZeroPadding = 8,
+    /// `len_bitstream` is larger than the encoded message.
+    BitstreamLength = 9,
+    /// Building the Huffman tree overflows the `u32` frequencies.
+    FrequencyOverflow = 10,
+    /// A code is longer than the selected decoder can decode.
+    TreeDepth = 11,
+    /// The symbol frequencies don't add up to `len_decoded_data`.
+    FrequencySum = 12,
+    /// `len_encoded_data` is 0.
+    EmptyBitstream = 13,
+    /// `len_bitstream` doesn't match the codes of the symbol frequencies.
+    BitstreamMismatch = 14,
+    /// The bitstream doesn't decode to `len_decoded_data` symbols. Validation
+    /// only checks the header, so a corrupted message is found while decoding.
+    MessageLength = 15,
+    /// The decoder panicked on a validated packet, this is a bug.
+    Panic = 255,
+}
+
+impl From<&PacketError> for BdoHuffmanStatus {
+    fn from(err: &PacketError) -> Self {
+        match err {
+            PacketError::Truncated { .. } => BdoHuffmanStatus::Truncated,
+            PacketError::LengthMismatch { .. } => BdoHuffmanStatus::LengthMismatch,
+            PacketError::SymbolCount { .. } => BdoHuffmanStatus::SymbolCount,
+            PacketError::InvalidSymbol { .. } => BdoHuffmanStatus::InvalidSymbol,
+            PacketError::DuplicateSymbol { .. } => BdoHuffmanStatus::DuplicateSymbol,
+            PacketError::NonZeroPadding { .. } => BdoHuffmanStatus::NonZeroPadding,
+            PacketError::BitstreamLength { .. } => BdoHuffmanStatus::BitstreamLength,
+        }
+    }
+}
+
+impl From<&TreeError> for BdoHuffmanStatus {
+    fn from(err: &TreeError) -> Self {
+        match err {
+            TreeError::SymbolCount { .. } => BdoHuffmanStatus::SymbolCount,
+            TreeError::FrequencyOverflow { .. } => BdoHuffmanStatus::FrequencyOverflow,
+            TreeError::Depth { .. } => BdoHuffmanStatus::TreeDepth,
+            TreeError::FrequencySum { .. } => BdoHuffmanStatus::FrequencySum,
+            TreeError::EmptyBitstream => BdoHuffmanStatus::EmptyBitstream,
+            TreeError::BitstreamMismatch { .. } => BdoHuffmanStatus::BitstreamMismatch,
+        }
+    }
+}
+
+/// Decodes the packet in `content[0..content_len]` into `out[0..out_cap]`.
+///
+/// On success `*out_len` is the length of the decoded message, which is not
+/// null terminated. When `out_cap` is too small nothing is written to `out`
+/// and `*out_len` is set to the capacity needed, so passing an `out_cap` of 0
+/// queries the decoded length from the packet's header without decoding it.
+/// `out` may be null when `out_cap` is 0.
+///
+/// The packet's header is validated before it is decoded and a message that
+/// decodes to another length than `len_decoded_data` is reported as
+/// `MessageLength`, nothing is written to `out` then. A panic while decoding is
+/// caught and reported as `Panic` instead of unwinding into C.
+///
+/// # Safety
+///
+/// `content` must be valid for reads of `content_len` bytes, `out` must be
+/// valid for writes of `out_cap` bytes and `out_len` must be valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn bdo_huffman_decode(
+    content: *const u8,
+    content_len: usize,
+    out: *mut u8,
+    out_cap: usize,
+    out_len: *mut usize,
+) -> BdoHuffmanStatus {
+    if content.is_null() || out_len.is_null() || (out.is_null() && out_cap > 0) {
+        return BdoHuffmanStatus::NullPointer;
+    }
+    let content = std::slice::from_raw_parts(content, content_len);
+
+    let packet = match Packet::try_new(content) {
+        Ok(packet) => packet,
+        Err(err) => return (&err).into(),
+    };
+    let kernel = match adaptive::try_kernel(&packet, TieBreakPolicy::Classic) {
+        Ok(kernel) => kernel,
+        Err(err) => return (&err).into(),
+    };
+
+    // Validation checks the header against the symbol frequencies, not the
+    // bitstream, so a corrupted message can decode to a different length.
+    let len = packet.decoded_bytes_len as usize;
+    *out_len = len;
+    if len > out_cap {
+        return BdoHuffmanStatus::BufferTooSmall;
+    }
+    let decoded =
+        std::panic::catch_unwind(|| kernel.decode_packet(content, TieBreakPolicy::Classic));
+    let Ok(decoded) = decoded else {
+        return BdoHuffmanStatus::Panic;
+    };
+    if decoded.len() != len {
+        return BdoHuffmanStatus::MessageLength;
+    }
+    std::ptr::copy_nonoverlapping(decoded.as_ptr(), out, len);
+    BdoHuffmanStatus::Ok
+}
+
+/// A static, null terminated description of `status`.
+#[no_mangle]
+pub extern "C" fn bdo_huffman_status_message(status: BdoHuffmanStatus) -> *const std::ffi::c_char {
+    let message: &'static std::ffi::CStr = match status {
+        BdoHuffmanStatus::Ok => c"ok",
+        BdoHuffmanStatus::NullPointer => c"null pointer argument",
+        BdoHuffmanStatus::BufferTooSmall => c"output buffer too small",
+        BdoHuffmanStatus::Truncated => c"truncated packet",
+        BdoHuffmanStatus::LengthMismatch => c"len_content doesn't match the content length",
+        BdoHuffmanStatus::SymbolCount => c"invalid symbol table length",
+        BdoHuffmanStatus::InvalidSymbol => c"invalid symbol",
+        BdoHuffmanStatus::DuplicateSymbol => c"duplicate symbol",
+        BdoHuffmanStatus::NonZeroPadding => c"non-zero symbol padding",
+        BdoHuffmanStatus::BitstreamLength => c"bitstream longer than the encoded message",
+        BdoHuffmanStatus::FrequencyOverflow => c"symbol frequencies overflow",
+        BdoHuffmanStatus::TreeDepth => c"code too long for the decoder",
+        BdoHuffmanStatus::FrequencySum => c"symbol frequencies don't sum to the decoded length",
+        BdoHuffmanStatus::EmptyBitstream => c"empty encoded message",
+        BdoHuffmanStatus::BitstreamMismatch => c"bitstream length doesn't match the symbol codes",
+        BdoHuffmanStatus::MessageLength => c"message doesn't decode to len_decoded_data symbols",
+        BdoHuffmanStatus::Panic => c"decoder panicked",
+    };
+    message.as_ptr()
+}
@@ -0,0 +1,95 @@
+/*
+ * Decodes each packet file given on the command line through the C ABI and
+ * prints every decoded message on its own line. Exits non-zero when a call
+ * doesn't return the expected status.
+ */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "bdo_huffman.h"
+
+#define CHECK(expr, expected)                                                  \
+    do {                                                                       \
+        BdoHuffmanStatus status_ = (expr);                                     \
+        if (status_ != (expected)) {                                           \
+            fprintf(stderr, "%s:%d: %s returned %d (%s), expected %d\n",       \
+                    __FILE__, __LINE__, #expr, (int)status_,                   \
+                    bdo_huffman_status_message(status_), (int)(expected));     \
+            exit(1);                                                           \
+        }                                                                      \
+    } while (0)
+
+static uint8_t *read_file(const char *path, size_t *len) {
+    FILE *file = fopen(path, "rb");
+    if (!file) {
+        perror(path);
+        exit(1);
+    }
+    fseek(file, 0, SEEK_END);
+    *len = (size_t)ftell(file);
+    fseek(file, 0, SEEK_SET);
+    uint8_t *content = malloc(*len);
+    if (fread(content, 1, *len, file) != *len) {
+        perror(path);
+        exit(1);
+    }
+    fclose(file);
+    return content;
+}
+
+static void check_errors(const uint8_t *content, size_t len) {
+    uint8_t out[16];
+    size_t out_len = 0;
+
+    CHECK(bdo_huffman_decode(NULL, len, out, sizeof(out), &out_len),
+          BDO_HUFFMAN_STATUS_NULL_POINTER);
+    CHECK(bdo_huffman_decode(content, len, out, sizeof(out), NULL),
+          BDO_HUFFMAN_STATUS_NULL_POINTER);
+    CHECK(bdo_huffman_decode(content, 10, out, sizeof(out), &out_len),
+          BDO_HUFFMAN_STATUS_TRUNCATED);
+    CHECK(bdo_huffman_decode(content, len - 1, out, sizeof(out), &out_len),
+          BDO_HUFFMAN_STATUS_TRUNCATED);
+
+    uint8_t *corrupted = malloc(len);
+    memcpy(corrupted, content, len);
+    corrupted[16] = 'x';
+    CHECK(bdo_huffman_decode(corrupted, len, out, sizeof(out), &out_len),
+          BDO_HUFFMAN_STATUS_INVALID_SYMBOL);
+    corrupted[16] = content[16];
+    corrupted[17] = 1;
+    CHECK(bdo_huffman_decode(corrupted, len, out, sizeof(out), &out_len),
+          BDO_HUFFMAN_STATUS_NON_ZERO_PADDING);
+    free(corrupted);
+}
+
+int main(int argc, char **argv) {
+    for (int i = 1; i < argc; i++) {
+        size_t len;
+        uint8_t *content = read_file(argv[i], &len);
+
+        /* Query the decoded length first. */
+        size_t out_len = 0;
+        CHECK(bdo_huffman_decode(content, len, NULL, 0, &out_len),
+              BDO_HUFFMAN_STATUS_BUFFER_TOO_SMALL);
+
+        size_t out_cap = out_len;
+        uint8_t *out = malloc(out_cap);
+        CHECK(bdo_huffman_decode(content, len, out, out_cap, &out_len),
+              BDO_HUFFMAN_STATUS_OK);
+        if (out_len != out_cap) {
+            fprintf(stderr, "%s: decoded %zu bytes, expected %zu\n", argv[i],
+                    out_len, out_cap);
+            return 1;
+        }
+        fwrite(out, 1, out_len, stdout);
+        fputc('\n', stdout);
+
+        if (i == 1) {
+            check_errors(content, len);
+        }
+        free(out);
+        free(content);
+    }
+    return 0;
+}
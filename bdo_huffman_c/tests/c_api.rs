@@ -0,0 +1,108 @@
+use bdo_huffman_c::{bdo_huffman_decode, BdoHuffmanStatus};
+use common::corpus::samples;
+use common::packet::Packet;
+use common::test_cases::TEST_BYTES;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const CRATE_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+// The captures in `_sample_data`, or in `BDO_SAMPLES` if set.
+fn sample_files() -> Vec<PathBuf> {
+    samples().iter().map(|sample| sample.path.clone()).collect()
+}
+
+// Integration tests run from `target/<profile>/deps`, next to the cdylib.
+fn library_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn header_is_up_to_date() {
+    let config = cbindgen::Config::from_file(Path::new(CRATE_DIR).join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::generate_with_config(CRATE_DIR, config)
+        .unwrap()
+        .write(&mut generated);
+    let committed = std::fs::read(Path::new(CRATE_DIR).join("include/bdo_huffman.h")).unwrap();
+    assert!(
+        generated == committed,
+        "include/bdo_huffman.h is stale, regenerate it with cbindgen:\n{}",
+        String::from_utf8_lossy(&generated)
+    );
+}
+
+#[test]
+fn c_program_decodes_samples() {
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let program = out_dir.join("decode_samples");
+    let lib_dir = library_dir();
+
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let status = Command::new(&compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(Path::new(CRATE_DIR).join("include"))
+        .arg(Path::new(CRATE_DIR).join("tests/c/decode_samples.c"))
+        .arg("-o")
+        .arg(&program)
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lbdo_huffman_c")
+        .status()
+        .unwrap_or_else(|err| panic!("failed to run {compiler}: {err}"));
+    assert!(status.success(), "compiling decode_samples.c failed");
+
+    let files = sample_files();
+    let output = Command::new(&program).args(&files).output().unwrap();
+    assert!(
+        output.status.success(),
+        "decode_samples failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let decoded: Vec<&str> = stdout.lines().collect();
+    assert_eq!(decoded.len(), files.len());
+    for (path, decoded) in files.iter().zip(decoded) {
+        let content = std::fs::read(path).unwrap();
+        assert_eq!(
+            decoded,
+            baseline::decode_packet(&content),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn flipped_message_bits_report_the_length() {
+    let packet = Packet::new(&TEST_BYTES);
+    let message_start = TEST_BYTES.len() - packet.encoded_message.len();
+    let mut out = [0u8; 64];
+    let mut mismatches = 0;
+    for bit in message_start * 8..TEST_BYTES.len() * 8 {
+        let mut content = TEST_BYTES;
+        content[bit / 8] ^= 0x80 >> (bit % 8);
+        let mut out_len = 0;
+        let status = unsafe {
+            bdo_huffman_decode(
+                content.as_ptr(),
+                content.len(),
+                out.as_mut_ptr(),
+                out.len(),
+                &mut out_len,
+            )
+        };
+        match status {
+            BdoHuffmanStatus::Ok => assert_eq!(out_len, packet.decoded_bytes_len as usize),
+            BdoHuffmanStatus::MessageLength => mismatches += 1,
+            status => panic!("bit {bit}: {status:?}"),
+        }
+    }
+    assert!(mismatches > 0);
+}
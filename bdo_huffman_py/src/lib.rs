@@ -0,0 +1,59 @@
+use adaptive::DecodeError;
+use common::min_heap::TieBreakPolicy;
+use common::packet::Packet;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+fn value_error(err: impl Into<DecodeError>) -> PyErr {
+    PyValueError::new_err(err.into().to_string())
+}
+
+/// Decodes a GetWorldMarketList packet into its message.
+#[pyfunction]
+fn decode_packet(py: Python<'_>, content: &[u8]) -> PyResult<String> {
+    py.allow_threads(|| adaptive::try_decode_packet(content))
+        .map_err(value_error)
+}
+
+/// Decodes a packet into `(item, count, price, cumulative_count)` tuples.
+#[pyfunction]
+fn decode_records(py: Python<'_>, content: &[u8]) -> PyResult<Vec<(u32, u64, u64, u64)>> {
+    let records = py
+        .allow_threads(|| adaptive::decode_records(content))
+        .map_err(value_error)?;
+    Ok(records
+        .into_iter()
+        .map(|r| (r.item, r.count, r.price, r.cumulative_count))
+        .collect())
+}
+
+/// The packet header fields, the symbol table and the decoder that would be used.
+#[pyfunction]
+fn packet_info<'py>(py: Python<'py>, content: &[u8]) -> PyResult<Bound<'py, PyDict>> {
+    let packet = Packet::try_new(content).map_err(value_error)?;
+
+    let symbols = PyDict::new(py);
+    for (symbol, frequency) in packet.symbol_frequencies() {
+        symbols.set_item((symbol as char).to_string(), frequency)?;
+    }
+
+    let info = PyDict::new(py);
+    info.set_item("len", packet.len)?;
+    info.set_item("symbol_count", packet.symbol_count)?;
+    info.set_item("symbols", symbols)?;
+    info.set_item("bitstream_len", packet.bitstream_len)?;
+    info.set_item("encoded_bytes_len", packet.encoded_bytes_len)?;
+    info.set_item("decoded_bytes_len", packet.decoded_bytes_len)?;
+    let kernel = adaptive::try_kernel(&packet, TieBreakPolicy::Classic).map_err(value_error)?;
+    info.set_item("decoder", kernel.name())?;
+    Ok(info)
+}
+
+#[pymodule]
+fn bdo_huffman(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(decode_packet, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_records, m)?)?;
+    m.add_function(wrap_pyfunction!(packet_info, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,122 @@
+import struct
+from pathlib import Path
+
+import pytest
+
+import bdo_huffman
+
+SAMPLE_DIR = Path(__file__).resolve().parents[2] / "_sample_data"
+SAMPLES = sorted(SAMPLE_DIR.glob("GetWorldMarketList_*.bin"))
+
+TEST_BYTES = bytes(
+    [
+        136, 0, 0, 0, 0, 0, 0, 0, 12, 0, 0, 0, 6, 0, 0, 0, 45, 0, 0, 0, 11, 0, 0, 0, 48, 0, 0, 0, 3, 0,
+        0, 0, 49, 0, 0, 0, 1, 0, 0, 0, 50, 0, 0, 0, 2, 0, 0, 0, 51, 0, 0, 0, 1, 0, 0, 0, 52, 0, 0, 0,
+        6, 0, 0, 0, 53, 0, 0, 0, 2, 0, 0, 0, 54, 0, 0, 0, 2, 0, 0, 0, 55, 0, 0, 0, 3, 0, 0, 0, 56, 0,
+        0, 0, 1, 0, 0, 0, 57, 0, 0, 0, 2, 0, 0, 0, 124, 0, 0, 0, 128, 0, 0, 0, 16, 0, 0, 0, 40, 0, 0,
+        0, 229, 144, 115, 255, 244, 122, 27, 209, 242, 203, 103, 48, 153, 43, 90, 163,
+    ]
+)
+EXPECTED_MESSAGE = "53801-0-55556-41900|53802-0-16807-70000|"
+
+
+# The heap based decoder from the README, used as the reference.
+class Node:
+    def __init__(self, frequency, symbol=None, left=None, right=None):
+        self.frequency = frequency
+        self.symbol = symbol
+        self.left = left
+        self.right = right
+
+    def __lt__(self, other):
+        return self.frequency < other.frequency
+
+
+def push(heap, node):
+    heap.append(node)
+    child = len(heap) - 1
+    while child > 0:
+        parent = (child - 1) >> 1
+        if heap[child] < heap[parent]:
+            heap[parent], heap[child] = heap[child], heap[parent]
+        child = parent
+
+
+def pop(heap):
+    node = heap.pop()
+    if heap:
+        node, heap[0] = heap[0], node
+        parent, child, end = 0, 1, len(heap)
+        while child < end:
+            i = child if heap[child] < heap[parent] else parent
+            child += 1
+            i = child if (child < end and heap[child] < heap[i]) else i
+            if parent == i:
+                break
+            heap[parent], heap[i] = heap[i], heap[parent]
+            parent, child = i, 2 * i + 1
+    return node
+
+
+def reference_decode(content):
+    (symbol_count,) = struct.unpack_from("<I", content, 8)
+    heap = []
+    for i in range(symbol_count):
+        frequency, symbol = struct.unpack_from("<I4s", content, 12 + 8 * i)
+        push(heap, Node(frequency, chr(symbol[0])))
+    while len(heap) > 1:
+        left, right = pop(heap), pop(heap)
+        push(heap, Node(left.frequency + right.frequency, left=left, right=right))
+    root = pop(heap)
+
+    pos = 12 + 8 * symbol_count
+    bitstream_len, encoded_len, _ = struct.unpack_from("<III", content, pos)
+    message = content[pos + 12 : pos + 12 + encoded_len]
+
+    decoded, node = [], root
+    for i in range(bitstream_len):
+        bit = (message[i // 8] >> (7 - i % 8)) & 1
+        node = node.right if bit else node.left
+        if node.symbol is not None:
+            decoded.append(node.symbol)
+            node = root
+    return "".join(decoded)
+
+
+def test_decode_packet():
+    assert bdo_huffman.decode_packet(TEST_BYTES) == EXPECTED_MESSAGE
+
+
+def test_decode_records():
+    assert bdo_huffman.decode_records(TEST_BYTES) == [
+        (53801, 0, 55556, 41900),
+        (53802, 0, 16807, 70000),
+    ]
+
+
+def test_packet_info():
+    info = bdo_huffman.packet_info(TEST_BYTES)
+    assert info["len"] == 136
+    assert info["symbol_count"] == 12
+    assert info["symbols"]["0"] == 11
+    assert info["bitstream_len"] == 128
+    assert info["encoded_bytes_len"] == 16
+    assert info["decoded_bytes_len"] == 40
+    assert info["decoder"] == "flat_unsafe_ptr"
+
+
+def test_invalid_packets_raise():
+    with pytest.raises(ValueError, match="truncated"):
+        bdo_huffman.decode_packet(TEST_BYTES[:100])
+    corrupted = bytearray(TEST_BYTES)
+    corrupted[16] = ord("a")
+    with pytest.raises(ValueError, match="invalid symbol"):
+        bdo_huffman.packet_info(bytes(corrupted))
+
+
+@pytest.mark.parametrize("path", SAMPLES, ids=lambda path: path.stem)
+def test_samples_match_reference(path):
+    content = path.read_bytes()
+    decoded = bdo_huffman.decode_packet(content)
+    assert decoded == reference_decode(content)
+    assert len(bdo_huffman.decode_records(content)) == decoded.count("|")
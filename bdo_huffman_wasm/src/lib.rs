@@ -0,0 +1,199 @@
+// WebAssembly bindings using the safe `table_index` and `fsm` decoders.
+//
+// Build the package with:
+//     wasm-pack build bdo_huffman_wasm --target web
+
+use common::packet::{Packet, PacketError};
+use common::records::{parse_records, MarketRecord, RecordError};
+use common::tree::{validate_packet, TreeError, MAX_DEPTH, MAX_TABLE_DEPTH};
+use wasm_bindgen::prelude::*;
+
+// The single channel FSM only catches up with the table decoder on the
+// largest captures, see the README table.
+const FSM_MIN_DECODED_LEN: u32 = 100_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum DecodeError {
+    Packet(PacketError),
+    Tree(TreeError),
+    Record(RecordError),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Packet(err) => write!(f, "invalid packet: {err}"),
+            DecodeError::Tree(err) => write!(f, "invalid symbol table: {err}"),
+            DecodeError::Record(err) => write!(f, "invalid message: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<PacketError> for DecodeError {
+    fn from(err: PacketError) -> Self {
+        DecodeError::Packet(err)
+    }
+}
+
+impl From<TreeError> for DecodeError {
+    fn from(err: TreeError) -> Self {
+        DecodeError::Tree(err)
+    }
+}
+
+impl From<RecordError> for DecodeError {
+    fn from(err: RecordError) -> Self {
+        DecodeError::Record(err)
+    }
+}
+
+fn decode(content: &[u8]) -> Result<String, DecodeError> {
+    let packet = Packet::try_new(content)?;
+    let depth = validate_packet(&packet.header(), MAX_DEPTH)?;
+    // The FSM decodes a byte of any code at a time, the table only codes of up
+    // to `MAX_TABLE_DEPTH` bits.
+    if packet.decoded_bytes_len >= FSM_MIN_DECODED_LEN || depth > MAX_TABLE_DEPTH {
+        Ok(fsm::decode_packet(content))
+    } else {
+        Ok(table_index::decode_packet(content))
+    }
+}
+
+fn decode_records(content: &[u8]) -> Result<Vec<Record>, DecodeError> {
+    let message = decode(content)?;
+    let records = parse_records(&message)
+        .map(|record| record.map(Record::from))
+        .collect::<Result<_, _>>()?;
+    Ok(records)
+}
+
+/// Decodes a captured GetWorldMarketList response, throws an `Error` for
+/// invalid packets.
+#[wasm_bindgen(js_name = decodePacket)]
+pub fn decode_packet(content: &[u8]) -> Result<String, JsError> {
+    Ok(decode(content)?)
+}
+
+/// Decodes a captured GetWorldMarketList response into its records.
+#[wasm_bindgen(js_name = decodeRecords)]
+pub fn decode_packet_records(content: &[u8]) -> Result<Vec<Record>, JsError> {
+    Ok(decode_records(content)?)
+}
+
+/// One `item-count-price-cumulative_count` record, the counts and price are
+/// `bigint`s. Exported as `MarketRecord` as `Record` is a TypeScript builtin.
+#[wasm_bindgen(js_name = MarketRecord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub item: u32,
+    pub count: u64,
+    pub price: u64,
+    #[wasm_bindgen(js_name = cumulativeCount)]
+    pub cumulative_count: u64,
+}
+
+impl From<MarketRecord> for Record {
+    fn from(record: MarketRecord) -> Self {
+        Record {
+            item: record.item,
+            count: record.count,
+            price: record.price,
+            cumulative_count: record.cumulative_count,
+        }
+    }
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+// Run in a headless browser with `wasm-bindgen-test-runner`, see README.md.
+// `unsupported = test` also runs them natively as plain `#[test]`s.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::test_cases::*;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    // `include_bytes!` as there's no filesystem to read the samples from.
+    const LARGE_BYTES: &[u8] = include_bytes!("../../_sample_data/GetWorldMarketList_55_4.bin");
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decodes_packet() {
+        assert_eq!(decode_packet(&TEST_BYTES).unwrap(), EXPECTED_MESSAGE);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decodes_large_packet() {
+        assert_eq!(
+            decode_packet(LARGE_BYTES).unwrap(),
+            fsm::decode_packet(LARGE_BYTES)
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decodes_records() {
+        assert_eq!(
+            decode_packet_records(&TEST_BYTES).unwrap(),
+            [
+                Record {
+                    item: 53801,
+                    count: 0,
+                    price: 55556,
+                    cumulative_count: 41900,
+                },
+                Record {
+                    item: 53802,
+                    count: 0,
+                    price: 16807,
+                    cumulative_count: 70000,
+                },
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_invalid_packets() {
+        assert!(matches!(
+            decode(&TEST_BYTES[..100]),
+            Err(DecodeError::Packet(PacketError::Truncated { .. }))
+        ));
+        let mut corrupted = TEST_BYTES;
+        corrupted[16] = b'a';
+        assert!(matches!(
+            decode_records(&corrupted),
+            Err(DecodeError::Packet(PacketError::InvalidSymbol { .. }))
+        ));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn rejects_invalid_trees() {
+        use common::packet::PacketBuilder;
+
+        // Both pass `Packet::try_new`, but a bitstream of 4000 zero bytes is
+        // longer than the symbol codes and an empty one has no bits.
+        let packet = Packet::new(&TEST_BYTES);
+        let zeros = PacketBuilder::from_packet(&packet)
+            .message(&[0; 4000])
+            .build();
+        let empty = PacketBuilder::from_packet(&packet).message(&[]).build();
+        assert!(matches!(
+            decode(&zeros),
+            Err(DecodeError::Tree(TreeError::BitstreamMismatch { .. }))
+        ));
+        assert_eq!(
+            decode(&empty),
+            Err(DecodeError::Tree(TreeError::EmptyBitstream))
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn decodes_deep_trees_with_fsm() {
+        let message = fibonacci_message();
+        let content = common::synthetic::encode_packet(&message);
+        assert_eq!(decode_packet(&content).unwrap().as_bytes(), message);
+    }
+}
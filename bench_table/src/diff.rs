@@ -0,0 +1,75 @@
+// Compares the mean times of two divan runs.
+
+use crate::divan::{Bench, Time};
+
+pub struct Change {
+    pub name: String,
+    pub old: Time,
+    pub new: Time,
+    /// Positive when `new` is slower.
+    pub percent: f64,
+}
+
+/// The benches in both runs whose mean changed by more than `threshold`
+/// percent, slowest regression first.
+pub fn compare(old: &[Bench], new: &[Bench], threshold: f64) -> Vec<Change> {
+    let mut changes: Vec<Change> = new
+        .iter()
+        .filter_map(|new| {
+            let old = old
+                .iter()
+                .find(|old| old.crate_name == new.crate_name && old.path == new.path)?;
+            let percent = (new.mean.nanos / old.mean.nanos - 1.0) * 100.0;
+            (percent.abs() > threshold).then(|| Change {
+                name: new.to_string(),
+                old: old.mean.clone(),
+                new: new.mean.clone(),
+                percent,
+            })
+        })
+        .collect();
+    changes.sort_by(|a, b| b.percent.total_cmp(&a.percent));
+    changes
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bench(case: &str, mean: &str) -> Bench {
+        let mean = Time::parse(mean).unwrap();
+        Bench {
+            crate_name: "fsm".to_owned(),
+            path: vec!["decode_packet".to_owned(), case.to_owned()],
+            fastest: mean.clone(),
+            median: mean.clone(),
+            mean,
+        }
+    }
+
+    #[test]
+    fn flags_changes_above_threshold() {
+        let old = [
+            bench("large", "100 µs"),
+            bench("medium", "50 µs"),
+            bench("small", "1 µs"),
+        ];
+        let new = [
+            bench("large", "104 µs"),
+            bench("medium", "40 µs"),
+            bench("small", "1.2 µs"),
+            bench("small_min", "10 ns"),
+        ];
+        let changes = compare(&old, &new, 5.0);
+        let names: Vec<_> = changes.iter().map(|change| change.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["fsm/decode_packet/small", "fsm/decode_packet/medium"]
+        );
+        assert!((changes[0].percent - 20.0).abs() < 1e-9);
+        assert!((changes[1].percent + 20.0).abs() < 1e-9);
+    }
+}
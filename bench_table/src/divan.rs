@@ -0,0 +1,179 @@
+// Parses the tree divan prints for `cargo bench`, e.g.
+//
+// table_unsafe_ptr                          fastest       │ slowest       │ median        │ mean          │ samples │ iters
+// ╰─ decoder                                              │               │               │               │         │
+//    ╰─ bench                                             │               │               │               │         │
+//       ╰─ decode_packet                                  │               │               │               │         │
+//          ╰─ large (msg_len=70.5k)         51.29 µs      │ 189.4 µs      │ 51.79 µs      │ 51.95 µs      │ 2000000 │ 2000000
+
+use std::fmt;
+
+/// A time as printed by divan, kept as text so tables match its formatting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Time {
+    pub nanos: f64,
+    pub text: String,
+}
+
+impl Time {
+    pub fn parse(text: &str) -> Option<Self> {
+        let (value, unit) = text.trim().split_once(' ')?;
+        let scale = match unit {
+            "ps" => 0.001,
+            "ns" => 1.0,
+            "µs" => 1_000.0,
+            "ms" => 1_000_000.0,
+            "s" => 1_000_000_000.0,
+            _ => return None,
+        };
+        let value: f64 = value.parse().ok()?;
+        Some(Time {
+            nanos: value * scale,
+            text: text.trim().to_owned(),
+        })
+    }
+
+    /// The value and unit, e.g. `("51.95", "µs")`.
+    pub fn parts(&self) -> (&str, &str) {
+        self.text.split_once(' ').unwrap()
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bench {
+    pub crate_name: String,
+    /// The tree path below the crate, e.g. `decoder/bench/decode_packet/large (msg_len=70.5k)`.
+    pub path: Vec<String>,
+    pub fastest: Time,
+    pub median: Time,
+    pub mean: Time,
+}
+
+impl Bench {
+    /// The group the bench is in, e.g. `decode_packet`.
+    pub fn group(&self) -> Option<&str> {
+        let len = self.path.len();
+        (len >= 2).then(|| self.path[len - 2].as_str())
+    }
+
+    /// The bench argument or name, e.g. `large (msg_len=70.5k)`.
+    pub fn case(&self) -> &str {
+        self.path.last().unwrap()
+    }
+}
+
+impl fmt::Display for Bench {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.crate_name, self.path.join("/"))
+    }
+}
+
+/// Collects every timed bench, ignoring anything else printed in between.
+pub fn parse(output: &str) -> Vec<Bench> {
+    let mut benches = Vec::new();
+    let mut crate_name = None;
+    let mut path: Vec<String> = Vec::new();
+
+    for line in output.lines() {
+        let Some(marker) = line.chars().position(|c| c == '├' || c == '╰') else {
+            if !line.starts_with(char::is_whitespace) && line.contains("fastest") {
+                crate_name = line.split_whitespace().next().map(str::to_owned);
+                path.clear();
+            }
+            continue;
+        };
+        let Some(crate_name) = &crate_name else {
+            continue;
+        };
+
+        // The marker is `├─ ` or `╰─ ` and each level indents by 3.
+        let rest: String = line.chars().skip(marker + 3).collect();
+        let mut columns = rest.split('│');
+        let first = columns.next().unwrap().trim();
+        let (name, fastest) = match first.split_once("  ") {
+            Some((name, fastest)) => (name, Some(fastest)),
+            None => (first, None),
+        };
+        path.truncate(marker / 3);
+        path.push(name.to_owned());
+
+        let Some(fastest) = fastest.and_then(Time::parse) else {
+            continue;
+        };
+        let mut columns = columns.map(Time::parse);
+        let (Some(Some(_slowest)), Some(Some(median)), Some(Some(mean))) =
+            (columns.next(), columns.next(), columns.next())
+        else {
+            continue;
+        };
+        benches.push(Bench {
+            crate_name: crate_name.clone(),
+            path: path.clone(),
+            fastest,
+            median,
+            mean,
+        });
+    }
+    benches
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "\
+fsm_unsafe_4channel                       fastest       │ slowest       │ median        │ mean          │ samples │ iters
+╰─ decoder                                              │               │               │               │         │
+   ╰─ bench                                             │               │               │               │         │
+      ├─ decode_message                                 │               │               │               │         │
+      │  ╰─ small_min (msg_len=40b)       10.02 µs      │ 40.11 µs      │ 10.11 µs      │ 10.14 µs      │ 2000000 │ 2000000
+      │                                   3.992 MB/s    │ 997.2 KB/s    │ 3.956 MB/s    │ 3.943 MB/s    │         │
+      ╰─ decode_packet                                  │               │               │               │         │
+         ├─ large (msg_len=70.5k)         33.49 µs      │ 150.5 µs      │ 33.69 µs      │ 33.81 µs      │ 2000000 │ 2000000
+         ╰─ small_min (msg_len=40b)       10.04 µs      │ 31.39 µs      │ 10.14 µs      │ 10.18 µs      │ 2000000 │ 2000000
+
+total decoded_len: 236668
+benches                                   fastest       │ slowest       │ median        │ mean          │ samples │ iters
+├─ all_samples_fsm                        423.4 µs      │ 718.4 µs      │ 425.8 µs      │ 427.7 µs      │ 10000   │ 10000
+╰─ all_samples_mtable                     614.2 µs      │ 683.3 µs      │ 619 µs        │ 620.4 µs      │ 10000   │ 10000
+";
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(Time::parse("51.95 µs").unwrap().nanos, 51_950.0);
+        assert_eq!(Time::parse("932.8 ns").unwrap().nanos, 932.8);
+        assert_eq!(Time::parse("1.5 ms").unwrap().parts(), ("1.5", "ms"));
+        assert_eq!(Time::parse("3.992 MB/s"), None);
+        assert_eq!(Time::parse("2000000"), None);
+    }
+
+    #[test]
+    fn parses_bench_tree() {
+        let benches = parse(OUTPUT);
+        let names: Vec<_> = benches.iter().map(Bench::to_string).collect();
+        assert_eq!(
+            names,
+            [
+                "fsm_unsafe_4channel/decoder/bench/decode_message/small_min (msg_len=40b)",
+                "fsm_unsafe_4channel/decoder/bench/decode_packet/large (msg_len=70.5k)",
+                "fsm_unsafe_4channel/decoder/bench/decode_packet/small_min (msg_len=40b)",
+                "benches/all_samples_fsm",
+                "benches/all_samples_mtable",
+            ]
+        );
+        assert_eq!(benches[1].group(), Some("decode_packet"));
+        assert_eq!(benches[1].fastest.text, "33.49 µs");
+        assert_eq!(benches[1].median.text, "33.69 µs");
+        assert_eq!(benches[1].mean.text, "33.81 µs");
+        assert_eq!(benches[3].group(), None);
+    }
+}
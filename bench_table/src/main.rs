@@ -0,0 +1,119 @@
+// Regenerates the README benchmark table from divan output and compares runs.
+//
+//     cargo run -p bench_table -- table [DIVAN_OUTPUT] [--group NAME] [--readme README.md]
+//     cargo run -p bench_table -- diff OLD NEW [--threshold PERCENT]
+//
+// Without DIVAN_OUTPUT `table` runs `cargo bench --workspace` itself.
+
+mod diff;
+mod divan;
+mod table;
+
+use std::process::{Command, ExitCode, Stdio};
+
+const USAGE: &str = "\
+usage: bench_table table [DIVAN_OUTPUT] [--group NAME] [--readme README.md]
+       bench_table diff OLD NEW [--threshold PERCENT]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<ExitCode, String> {
+    let (command, args) = args.split_first().ok_or("missing command")?;
+    let mut positional = Vec::new();
+    let mut options = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => {
+                let value = args.next().ok_or(format!("missing value for --{name}"))?;
+                options.push((name, value.as_str()));
+            }
+            None => positional.push(arg.as_str()),
+        }
+    }
+    let option = |name: &str| {
+        options
+            .iter()
+            .find(|(option, _)| *option == name)
+            .map(|(_, value)| *value)
+    };
+
+    match (command.as_str(), positional.as_slice()) {
+        ("table", [] | [_]) => {
+            let output = match positional.first() {
+                Some(path) => read(path)?,
+                None => run_benches()?,
+            };
+            let table = table::render(
+                &divan::parse(&output),
+                option("group").unwrap_or("decode_packet"),
+            );
+            match option("readme") {
+                Some(path) => {
+                    let readme = read(path)?;
+                    let readme = table::replace_table(&readme, &table)
+                        .ok_or(format!("{path} has no `| Approach` table"))?;
+                    std::fs::write(path, readme).map_err(|err| format!("writing {path}: {err}"))?;
+                }
+                None => print!("{table}"),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        ("diff", [old, new]) => {
+            let threshold = match option("threshold") {
+                Some(threshold) => threshold
+                    .parse()
+                    .map_err(|_| format!("invalid threshold {threshold}"))?,
+                None => 5.0,
+            };
+            let changes = diff::compare(
+                &divan::parse(&read(old)?),
+                &divan::parse(&read(new)?),
+                threshold,
+            );
+            for change in &changes {
+                let kind = if change.percent > 0.0 {
+                    "regression"
+                } else {
+                    "improvement"
+                };
+                println!(
+                    "{kind:<11} {:+7.1}%  {} -> {}  {}",
+                    change.percent, change.old, change.new, change.name
+                );
+            }
+            // Fail on regressions so the diff can gate CI.
+            if changes.iter().any(|change| change.percent > 0.0) {
+                Ok(ExitCode::FAILURE)
+            } else {
+                Ok(ExitCode::SUCCESS)
+            }
+        }
+        _ => Err(format!("invalid arguments for {command}")),
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("reading {path}: {err}"))
+}
+
+fn run_benches() -> Result<String, String> {
+    let output = Command::new("cargo")
+        .args(["bench", "--workspace"])
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| format!("running cargo bench: {err}"))?;
+    if !output.status.success() {
+        return Err("cargo bench failed".to_owned());
+    }
+    String::from_utf8(output.stdout).map_err(|err| err.to_string())
+}
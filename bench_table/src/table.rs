@@ -0,0 +1,261 @@
+// Renders the "Full Packet Processing Results" table of the README.
+
+use crate::divan::{Bench, Time};
+use std::fmt::Write;
+
+pub struct Row {
+    pub label: &'static str,
+    pub crate_name: &'static str,
+    pub child: &'static str,
+}
+
+pub struct Section {
+    /// Whether the fastest entry per column is bolded.
+    pub highlight: bool,
+    pub rows: &'static [Row],
+}
+
+const fn row(label: &'static str, crate_name: &'static str, child: &'static str) -> Row {
+    Row {
+        label,
+        crate_name,
+        child,
+    }
+}
+
+pub const SECTIONS: &[Section] = &[
+    Section {
+        highlight: false,
+        rows: &[row("BaseLine", "baseline", "Box")],
+    },
+    Section {
+        highlight: true,
+        rows: &[
+            row("Nested", "nested_box", "Box"),
+            row("Flat", "flat_index", "Index"),
+            row("S-Table", "table_single_index", "Index"),
+            row("M-Table", "table_index", "Index"),
+            row("FSM", "fsm", "Index"),
+            row("2 Channel FSM", "fsm_2channel", "Index"),
+            row("3 Channel FSM", "fsm_3channel", "Index"),
+            row("4 Channel FSM", "fsm_4channel", "Index"),
+        ],
+    },
+    Section {
+        highlight: true,
+        rows: &[
+            row("Nested", "nested_unsafe_box", "Box"),
+            row("Flat", "flat_unsafe_ptr", "Const"),
+            row("S-Table", "table_single_unsafe_ptr", "Const"),
+            row("M-Table", "table_unsafe_ptr", "Const"),
+            row("FSM", "fsm_unsafe", "Index"),
+            row("2 Channel FSM", "fsm_unsafe_2channel", "Index"),
+            row("3 Channel FSM", "fsm_unsafe_3channel", "Index"),
+            row("4 Channel FSM", "fsm_unsafe_4channel", "Index"),
+            row("5 Channel FSM", "fsm_unsafe_5channel", "Index"),
+        ],
+    },
+];
+
+const LABEL_WIDTH: usize = 17;
+const TIME_WIDTH: usize = 14;
+
+/// The markdown table of the mean times of the `group` benches, one column
+/// per case in the order divan ran them. Rows of crates without results are
+/// left out.
+pub fn render(benches: &[Bench], group: &str) -> String {
+    let benches: Vec<&Bench> = benches
+        .iter()
+        .filter(|bench| bench.group() == Some(group))
+        .collect();
+    let mut cases: Vec<&str> = Vec::new();
+    for bench in &benches {
+        if !cases.contains(&bench.case()) {
+            cases.push(bench.case());
+        }
+    }
+    let mean = |crate_name: &str, case: &str| {
+        benches
+            .iter()
+            .find(|bench| bench.crate_name == crate_name && bench.case() == case)
+            .map(|bench| &bench.mean)
+    };
+
+    let mut table = String::new();
+    write!(table, "| {:<LABEL_WIDTH$} | Safety | Child |", "Approach").unwrap();
+    for case in &cases {
+        write!(table, "{:^TIME_WIDTH$}|", column_name(case)).unwrap();
+    }
+    write!(
+        table,
+        "\n|{:-<w$}|:------:|:-----:|",
+        "",
+        w = LABEL_WIDTH + 2
+    )
+    .unwrap();
+    for _ in &cases {
+        write!(table, ":{:-<w$}:|", "", w = TIME_WIDTH - 2).unwrap();
+    }
+    table.push('\n');
+
+    let mut first = true;
+    for section in SECTIONS {
+        let rows: Vec<(&Row, Vec<Option<&Time>>)> = section
+            .rows
+            .iter()
+            .map(|row| {
+                let times = cases.iter().map(|case| mean(row.crate_name, case));
+                (row, times.collect::<Vec<_>>())
+            })
+            .filter(|(_, times)| times.iter().any(Option::is_some))
+            .collect();
+        if rows.is_empty() {
+            continue;
+        }
+        if !first {
+            write!(table, "| {:LABEL_WIDTH$} |        |       |", "").unwrap();
+            for _ in &cases {
+                write!(table, "{:TIME_WIDTH$}|", "").unwrap();
+            }
+            table.push('\n');
+        }
+        first = false;
+
+        let fastest: Vec<Option<f64>> = (0..cases.len())
+            .map(|i| {
+                let times = rows.iter().filter_map(|(_, times)| times[i]);
+                times.map(|time| time.nanos).min_by(f64::total_cmp)
+            })
+            .collect();
+        for (row, times) in &rows {
+            let bold: Vec<bool> = times
+                .iter()
+                .zip(&fastest)
+                .map(|(time, fastest)| section.highlight && time.map(|time| time.nanos) == *fastest)
+                .collect();
+            let label = if bold.contains(&true) {
+                format!("**{}**", row.label)
+            } else {
+                row.label.to_owned()
+            };
+            let safety = if row.crate_name.contains("unsafe") {
+                "❌"
+            } else {
+                "✅"
+            };
+            write!(
+                table,
+                "| {label:<LABEL_WIDTH$} | {safety:^6} | {:^5} |",
+                row.child
+            )
+            .unwrap();
+            for (time, bold) in times.iter().zip(bold) {
+                let cell = match time {
+                    Some(time) => {
+                        let (value, unit) = time.parts();
+                        let value = four_digits(value);
+                        if bold {
+                            format!("**{value}** {unit}")
+                        } else {
+                            format!("{value} {unit}")
+                        }
+                    }
+                    None => "-".to_owned(),
+                };
+                write!(table, "{cell:^TIME_WIDTH$}|").unwrap();
+            }
+            table.push('\n');
+        }
+    }
+    table
+}
+
+// `large (msg_len=70.5k)` is shown as `70.5k`.
+fn column_name(case: &str) -> &str {
+    case.split_once("msg_len=")
+        .and_then(|(_, rest)| rest.strip_suffix(')'))
+        .unwrap_or(case)
+}
+
+// divan drops trailing zeros, the README keeps 4 significant digits, `21.4` is
+// shown as `21.40`.
+fn four_digits(value: &str) -> String {
+    let digits = value.chars().filter(char::is_ascii_digit).count();
+    let mut value = value.to_owned();
+    if digits < 4 && !value.contains('.') {
+        value.push('.');
+    }
+    value.extend(std::iter::repeat_n('0', 4usize.saturating_sub(digits)));
+    value
+}
+
+/// Replaces the table starting with the `| Approach` line in `readme`.
+pub fn replace_table(readme: &str, table: &str) -> Option<String> {
+    let start = readme.find("\n| Approach")? + 1;
+    let end = readme[start..]
+        .split_inclusive('\n')
+        .take_while(|line| line.starts_with('|'))
+        .map(str::len)
+        .sum::<usize>()
+        + start;
+    Some(format!("{}{table}{}", &readme[..start], &readme[end..]))
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::divan::parse;
+
+    const BENCHMARKS: &str = include_str!("../../benchmarks.txt");
+
+    #[test]
+    fn renders_readme_rows() {
+        let table = render(&parse(BENCHMARKS), "decode_packet");
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines[0],
+            "| Approach          | Safety | Child |    70.5k     |    33.3k     |    22.5k     |    11.1k     |     5.5k     |     40b      |"
+        );
+        assert_eq!(
+            lines[1],
+            "|-------------------|:------:|:-----:|:------------:|:------------:|:------------:|:------------:|:------------:|:------------:|"
+        );
+        assert_eq!(
+            lines[2],
+            "| BaseLine          |   ✅    |  Box  |   326.8 µs   |   154.2 µs   |   99.76 µs   |   43.17 µs   |   22.98 µs   |   497.7 ns   |"
+        );
+        assert_eq!(
+            lines[3],
+            "|                   |        |       |              |              |              |              |              |              |"
+        );
+        assert!(table.contains(
+            "| **Flat**          |   ❌    | Const |   175.7 µs   |   61.08 µs   |   26.04 µs   |   12.85 µs   |   7.669 µs   | **146.4** ns |"
+        ));
+        assert!(table.contains(
+            "| 2 Channel FSM     |   ✅    | Index |   47.00 µs   |   27.86 µs   |   22.02 µs   |   16.86 µs   |   14.19 µs   |   11.24 µs   |"
+        ));
+        assert_eq!(lines.len(), 2 + 1 + 1 + 8 + 1 + 9);
+    }
+
+    #[test]
+    fn pads_four_digits() {
+        assert_eq!(four_digits("21.4"), "21.40");
+        assert_eq!(four_digits("47"), "47.00");
+        assert_eq!(four_digits("931"), "931.0");
+        assert_eq!(four_digits("9.961"), "9.961");
+        assert_eq!(four_digits("141.9"), "141.9");
+    }
+
+    #[test]
+    fn replaces_readme_table() {
+        let readme = "intro\n\n| Approach | a |\n|---|---|\n| x | 1 |\n\nafter\n";
+        assert_eq!(
+            replace_table(readme, "| Approach | b |\n").unwrap(),
+            "intro\n\n| Approach | b |\n\nafter\n"
+        );
+        assert_eq!(replace_table("no table", ""), None);
+    }
+}
@@ -2,33 +2,36 @@
 
 use std::hint::black_box;
 
-use common;
+extern crate common;
 
-use baseline;
+extern crate baseline;
 
-use nested_box;
-use nested_unsafe_box;
+extern crate nested_box;
+extern crate nested_unsafe_box;
 
-use flat_index;
-use flat_ptr;
-use flat_unsafe_ptr;
+extern crate flat_index;
+extern crate flat_ptr;
+extern crate flat_unsafe_ptr;
 
-use table_index;
-use table_ptr;
-use table_unsafe_ptr;
+extern crate table_index;
+extern crate table_ptr;
+extern crate table_unsafe_ptr;
 
-use table_single_index;
-use table_single_unsafe_ptr;
+extern crate table_single_index;
+extern crate table_single_unsafe_ptr;
 
-use fsm;
-use fsm_2channel;
-use fsm_3channel;
-use fsm_4channel;
-use fsm_unsafe;
-use fsm_unsafe_2channel;
-use fsm_unsafe_3channel;
-use fsm_unsafe_4channel;
-use fsm_unsafe_5channel;
+extern crate fsm;
+extern crate fsm_2channel;
+extern crate fsm_3channel;
+extern crate fsm_4channel;
+extern crate fsm_static;
+
+extern crate adaptive;
+extern crate fsm_unsafe;
+extern crate fsm_unsafe_2channel;
+extern crate fsm_unsafe_3channel;
+extern crate fsm_unsafe_4channel;
+extern crate fsm_unsafe_5channel;
 
 fn main() {
     divan::main();
@@ -37,13 +40,13 @@ fn main() {
 #[divan::bench(sample_count = 10_000)]
 fn all_samples_mtable(bencher: divan::Bencher) {
     let mut samples = Vec::new();
-    for sample in common::test_cases::SAMPLE_CASES {
+    for sample in common::corpus::samples() {
         samples.push(sample.request());
     }
     let mut encoded_len = 0;
     let mut decoded_len = 0;
     for content in samples.iter() {
-        let packet = common::packet::Packet::new(&content);
+        let packet = common::packet::Packet::new(content);
         encoded_len += packet.encoded_bytes_len;
         decoded_len += packet.decoded_bytes_len;
     }
@@ -51,11 +54,11 @@ fn all_samples_mtable(bencher: divan::Bencher) {
     println!("total decoded_len: {}", decoded_len);
     bencher.bench_local(move || {
         for content in samples.iter() {
-            let packet = common::packet::Packet::new(&content);
+            let packet = common::packet::Packet::new(content);
             if packet.decoded_bytes_len <= 300 {
-                black_box(flat_unsafe_ptr::decode_packet(&content));
+                black_box(flat_unsafe_ptr::decode_packet(content));
             } else {
-                black_box(table_unsafe_ptr::decode_packet(&content));
+                black_box(table_unsafe_ptr::decode_packet(content));
             }
         }
     });
@@ -64,26 +67,106 @@ fn all_samples_mtable(bencher: divan::Bencher) {
 #[divan::bench(sample_count = 10_000)]
 fn all_samples_fsm(bencher: divan::Bencher) {
     let mut samples = Vec::new();
-    for sample in common::test_cases::SAMPLE_CASES {
+    for sample in common::corpus::samples() {
         samples.push(sample.request());
     }
     let mut encoded_len = 0;
     let mut decoded_len = 0;
     for content in samples.iter() {
-        let packet = common::packet::Packet::new(&content);
+        let packet = common::packet::Packet::new(content);
         encoded_len += packet.encoded_bytes_len;
         decoded_len += packet.decoded_bytes_len;
     }
     bencher.bench_local(move || {
         for content in samples.iter() {
-            let packet = common::packet::Packet::new(&content);
+            let packet = common::packet::Packet::new(content);
             if packet.decoded_bytes_len <= 300 {
-                black_box(flat_unsafe_ptr::decode_packet(&content));
+                black_box(flat_unsafe_ptr::decode_packet(content));
             } else if packet.decoded_bytes_len >= 10_000 {
-                black_box(fsm_unsafe_4channel::decode_packet(&content));
+                black_box(fsm_unsafe_4channel::decode_packet(content));
             } else {
-                black_box(table_unsafe_ptr::decode_packet(&content));
+                black_box(table_unsafe_ptr::decode_packet(content));
             }
         }
     });
 }
+
+#[divan::bench(sample_count = 10_000, args = [1, 4])]
+fn all_samples_batch(bencher: divan::Bencher, threads: usize) {
+    let mut samples = Vec::new();
+    for sample in common::corpus::samples() {
+        samples.push(sample.request());
+    }
+    let contents: Vec<&[u8]> = samples.iter().map(Vec::as_slice).collect();
+    let options = adaptive::BatchOptions {
+        threads,
+        ..Default::default()
+    };
+    bencher.bench_local(move || {
+        black_box(adaptive::decode_batch_with(&contents, options));
+    });
+}
+
+// The `small_min` captures, decoded below 1000 bytes.
+fn small_samples() -> Vec<Vec<u8>> {
+    common::corpus::samples_in(common::corpus::SizeGroup::SmallMin)
+        .map(|sample| sample.request())
+        .collect()
+}
+
+#[divan::bench(sample_count = 10_000)]
+fn small_samples_flat(bencher: divan::Bencher) {
+    let samples = small_samples();
+    bencher.bench_local(move || {
+        for content in samples.iter() {
+            black_box(flat_unsafe_ptr::decode_packet(content));
+        }
+    });
+}
+
+#[divan::bench(sample_count = 10_000, consts = [1, 2, 4, 8])]
+fn small_samples_many<const LANES: usize>(bencher: divan::Bencher) {
+    let samples = small_samples();
+    let contents: Vec<&[u8]> = samples.iter().map(Vec::as_slice).collect();
+    bencher.bench_local(move || {
+        black_box(flat_unsafe_ptr::decode_many_small_with::<
+            common::min_heap::Classic,
+            LANES,
+        >(&contents));
+    });
+}
+
+#[divan::bench(sample_count = 10_000, args = common::corpus::SizeGroup::ALL)]
+fn group_samples_adaptive(bencher: divan::Bencher, group: common::corpus::SizeGroup) {
+    let samples: Vec<_> = common::corpus::samples_in(group)
+        .map(|sample| sample.request())
+        .collect();
+    bencher.bench_local(move || {
+        for content in samples.iter() {
+            black_box(adaptive::decode_packet(content));
+        }
+    });
+}
+
+// Synthetic packets from 64 B to 1 MB to locate the flat, table and FSM crossovers.
+macro_rules! synthetic_sweep {
+    ($bench_name:ident, $decode_packet:path) => {
+        #[divan::bench(sample_count = 1_000, args = common::synthetic::SWEEP_DECODED_LENS)]
+        fn $bench_name(bencher: divan::Bencher, decoded_len: usize) {
+            let content = common::synthetic::market_packet(decoded_len as u64, decoded_len);
+            let packet = common::packet::Packet::new(&content);
+            bencher
+                .counter(divan::counter::BytesCount::from(packet.decoded_bytes_len))
+                .bench_local(|| black_box($decode_packet(black_box(&content))));
+        }
+    };
+}
+
+synthetic_sweep!(synthetic_sweep_flat, flat_unsafe_ptr::decode_packet);
+synthetic_sweep!(synthetic_sweep_table, table_unsafe_ptr::decode_packet);
+synthetic_sweep!(synthetic_sweep_fsm, fsm_unsafe_4channel::decode_packet);
+
+// The safe table and single channel FSM, with the FSM's lazily built tables.
+synthetic_sweep!(synthetic_sweep_safe_table, table_index::decode_packet);
+synthetic_sweep!(synthetic_sweep_safe_fsm, fsm::decode_packet);
+synthetic_sweep!(synthetic_sweep_safe_fsm_lazy, fsm::decode_packet_lazy);
@@ -0,0 +1,197 @@
+// Discovers the `GetWorldMarketList_<main>_<sub>.bin` captures in
+// `_sample_data`, or the directory in `BDO_SAMPLES`, for tests and benches.
+
+use crate::packet::Packet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const SAMPLES_ENV: &str = "BDO_SAMPLES";
+
+const FILE_PREFIX: &str = "GetWorldMarketList_";
+
+/// The message size groups from the README, by decoded length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SizeGroup {
+    SmallMin,
+    Small,
+    MediumSmall,
+    Medium,
+    LargeMedium,
+    Large,
+}
+
+impl SizeGroup {
+    pub const ALL: [SizeGroup; 6] = [
+        SizeGroup::SmallMin,
+        SizeGroup::Small,
+        SizeGroup::MediumSmall,
+        SizeGroup::Medium,
+        SizeGroup::LargeMedium,
+        SizeGroup::Large,
+    ];
+
+    pub fn from_decoded_len(decoded_len: u32) -> Self {
+        match decoded_len {
+            0..1_000 => SizeGroup::SmallMin,
+            1_000..10_000 => SizeGroup::Small,
+            10_000..20_000 => SizeGroup::MediumSmall,
+            20_000..30_000 => SizeGroup::Medium,
+            30_000..50_000 => SizeGroup::LargeMedium,
+            _ => SizeGroup::Large,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SizeGroup::SmallMin => "small_min",
+            SizeGroup::Small => "small",
+            SizeGroup::MediumSmall => "medium_small",
+            SizeGroup::Medium => "medium",
+            SizeGroup::LargeMedium => "large_medium",
+            SizeGroup::Large => "large",
+        }
+    }
+}
+
+impl core::fmt::Display for SizeGroup {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    /// `<main>_<sub>`, e.g. `55_4`.
+    pub name: String,
+    pub main_category: u32,
+    pub sub_category: u32,
+    pub path: PathBuf,
+    pub decoded_len: u32,
+    pub group: SizeGroup,
+}
+
+impl core::fmt::Display for Sample {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl Sample {
+    pub fn request(&self) -> Vec<u8> {
+        std::fs::read(&self.path).unwrap()
+    }
+}
+
+/// `BDO_SAMPLES` if set, otherwise the repository's `_sample_data`.
+pub fn sample_dir() -> PathBuf {
+    match std::env::var_os(SAMPLES_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("../_sample_data"),
+    }
+}
+
+/// All captures in `sample_dir()` ordered by category, discovered once.
+///
+/// Panics if the directory can't be read or holds an invalid capture.
+pub fn samples() -> &'static [Sample] {
+    static SAMPLES: OnceLock<Vec<Sample>> = OnceLock::new();
+    SAMPLES.get_or_init(|| {
+        let dir = sample_dir();
+        discover(&dir).unwrap_or_else(|err| panic!("reading {}: {err}", dir.display()))
+    })
+}
+
+/// The captures of `samples()` in `group`.
+pub fn samples_in(group: SizeGroup) -> impl Iterator<Item = &'static Sample> {
+    samples().iter().filter(move |sample| sample.group == group)
+}
+
+/// Scans `dir` for captures, ignoring files not named like one.
+pub fn discover(dir: &Path) -> std::io::Result<Vec<Sample>> {
+    let mut samples = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some((main_category, sub_category)) = parse_file_name(&path) else {
+            continue;
+        };
+        let content = std::fs::read(&path)?;
+        let packet = Packet::try_new(&content).map_err(|err| {
+            let message = format!("{}: {err}", path.display());
+            std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+        })?;
+        let decoded_len = packet.decoded_bytes_len;
+        samples.push(Sample {
+            name: format!("{main_category}_{sub_category}"),
+            main_category,
+            sub_category,
+            path,
+            decoded_len,
+            group: SizeGroup::from_decoded_len(decoded_len),
+        });
+    }
+    samples.sort_unstable_by_key(|sample| (sample.main_category, sample.sub_category));
+    Ok(samples)
+}
+
+fn parse_file_name(path: &Path) -> Option<(u32, u32)> {
+    let name = path.file_name()?.to_str()?;
+    let categories = name.strip_prefix(FILE_PREFIX)?.strip_suffix(".bin")?;
+    let (main, sub) = categories.split_once('_')?;
+    Some((main.parse().ok()?, sub.parse().ok()?))
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_cases::*;
+
+    #[test]
+    fn parses_file_names() {
+        let parse = |name: &str| parse_file_name(Path::new(name));
+        assert_eq!(parse("dir/GetWorldMarketList_55_4.bin"), Some((55, 4)));
+        assert_eq!(parse("GetWorldMarketList_55_4.bin.bak"), None);
+        assert_eq!(parse("GetWorldMarketList_55.bin"), None);
+        assert_eq!(parse("GetWorldMarketList_a_4.bin"), None);
+        assert_eq!(parse("README.md"), None);
+    }
+
+    #[test]
+    fn discovers_sample_data() {
+        let samples = samples();
+        assert!(!samples.is_empty());
+        assert!(samples
+            .windows(2)
+            .all(|pair| (pair[0].main_category, pair[0].sub_category)
+                < (pair[1].main_category, pair[1].sub_category)));
+        for case in ALL_CASES {
+            let sample = samples
+                .iter()
+                .find(|sample| {
+                    sample.name == format!("{}_{}", case.main_category, case.sub_category)
+                })
+                .unwrap();
+            assert_eq!(sample.request(), case.request());
+        }
+    }
+
+    #[test]
+    fn classifies_readme_groups() {
+        let expected = [
+            (SizeGroup::Large, 55, 4),
+            (SizeGroup::LargeMedium, 55, 3),
+            (SizeGroup::Medium, 55, 2),
+            (SizeGroup::MediumSmall, 55, 1),
+            (SizeGroup::Small, 25, 2),
+            (SizeGroup::SmallMin, 75, 6),
+        ];
+        for (group, main_category, sub_category) in expected {
+            assert!(
+                samples_in(group).any(|sample| sample.main_category == main_category
+                    && sample.sub_category == sub_category)
+            );
+        }
+    }
+}
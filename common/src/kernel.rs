@@ -0,0 +1,55 @@
+// Runtime selection of the decoding kernels specialised for CPU features.
+//
+// The unsafe table and FSM decoders compile their decoding loop once per
+// kernel and pick one per `Decoder`/`decode_packet` call with `Kernel::detect`.
+// Without `std` the features can't be detected at runtime, so only those
+// enabled at compile time count as supported.
+
+/// A decoding kernel, `Generic` is the portable fallback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    Generic,
+    /// x86_64 with the Haswell features AVX2, BMI1, BMI2 and LZCNT. The loops
+    /// use BMI2's flagless shifts but not `pext`.
+    #[cfg(target_arch = "x86_64")]
+    Haswell,
+}
+
+impl Kernel {
+    /// Every kernel of the target, from slowest to fastest.
+    pub const ALL: &'static [Kernel] = &[
+        Kernel::Generic,
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Haswell,
+    ];
+
+    /// The fastest kernel the running CPU supports.
+    pub fn detect() -> Self {
+        *Self::ALL
+            .iter()
+            .rev()
+            .find(|kernel| kernel.is_supported())
+            .unwrap()
+    }
+
+    /// Whether the running CPU has every feature the kernel is compiled with.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Kernel::Generic => true,
+            #[cfg(all(target_arch = "x86_64", feature = "std"))]
+            Kernel::Haswell => {
+                std::is_x86_feature_detected!("avx2")
+                    && std::is_x86_feature_detected!("bmi1")
+                    && std::is_x86_feature_detected!("bmi2")
+                    && std::is_x86_feature_detected!("lzcnt")
+            }
+            #[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+            Kernel::Haswell => cfg!(all(
+                target_feature = "avx2",
+                target_feature = "bmi1",
+                target_feature = "bmi2",
+                target_feature = "lzcnt"
+            )),
+        }
+    }
+}
@@ -1,8 +1,20 @@
-use mimalloc::MiMalloc;
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 
+extern crate alloc;
+
+// Only installed with the `mimalloc` feature, the benches and profile_bin enable it.
+#[cfg(feature = "mimalloc")]
 #[global_allocator]
-static GLOBAL: MiMalloc = MiMalloc;
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
+#[cfg(feature = "std")]
+pub mod corpus;
+pub mod kernel;
+pub mod metrics;
 pub mod min_heap;
 pub mod packet;
+pub mod profile;
+pub mod records;
+pub mod synthetic;
 pub mod test_cases;
+pub mod tree;
@@ -0,0 +1,44 @@
+// Per call timings of the decoding phases, enabled with the `metrics` feature.
+//
+// The decoders wrap `Packet::new`, building the tree and tables and decoding
+// the message in `time`, which only calls `f` when the feature is disabled.
+
+pub use crate::profile::Phase;
+
+/// Called with the phase and its duration after every timed call, the
+/// `Packet` phase isn't recorded as it is the sum of the others.
+#[cfg(feature = "metrics")]
+pub type Recorder = fn(Phase, std::time::Duration);
+
+#[cfg(feature = "metrics")]
+static RECORDER: core::sync::atomic::AtomicPtr<()> =
+    core::sync::atomic::AtomicPtr::new(core::ptr::null_mut());
+
+/// Sets the process wide recorder, `None` stops recording.
+#[cfg(feature = "metrics")]
+pub fn set_recorder(recorder: Option<Recorder>) {
+    let recorder = recorder.map_or(core::ptr::null_mut(), |recorder| recorder as *mut ());
+    RECORDER.store(recorder, core::sync::atomic::Ordering::Release);
+}
+
+#[cfg(feature = "metrics")]
+fn recorder() -> Option<Recorder> {
+    let recorder = RECORDER.load(core::sync::atomic::Ordering::Acquire);
+    // SAFETY: only `set_recorder` stores non-null pointers, which are `Recorder`s.
+    (!recorder.is_null()).then(|| unsafe { core::mem::transmute::<*mut (), Recorder>(recorder) })
+}
+
+/// Calls `f`, reporting its duration to the recorder if one is set.
+#[inline(always)]
+pub fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "metrics")]
+    if let Some(recorder) = recorder() {
+        let start = std::time::Instant::now();
+        let result = f();
+        recorder(phase, start.elapsed());
+        return result;
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = phase;
+    f()
+}
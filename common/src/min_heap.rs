@@ -1,42 +1,200 @@
 use crate::packet::MAX_SYMBOLS;
-// use heapless::Vec;
-
-// pub struct MinHeap<T: MinHeapNode + std::cmp::PartialOrd>(Vec<T, MAX_SYMBOLS>);
-pub struct MinHeap<T: MinHeapNode + std::cmp::PartialOrd>(Vec<T>);
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::marker::PhantomData;
 
 pub trait MinHeapNode {
     fn frequency(&self) -> u32;
 }
 
-impl<T: MinHeapNode + std::cmp::PartialOrd> Default for MinHeap<T> {
+// MARK: Tie Breaking
+
+/// How a heap orders nodes that compare equal, which decides the shape of the
+/// Huffman tree and so has to match the encoder.
+pub trait TieBreak {
+    /// The push order kept with each node, `()` when the policy ignores it.
+    type Seq: Copy + Default;
+
+    /// The push order of the next node, advancing `seq`.
+    fn next(seq: &mut Self::Seq) -> Self::Seq;
+
+    /// Whether `a` pops before `b`.
+    fn less<T: PartialOrd>(a: &(T, Self::Seq), b: &(T, Self::Seq)) -> bool;
+
+    /// The fastest queue popping in this order, for building trees. Only the
+    /// insertion order policies have a faster queue than their heap.
+    type Queue<T: MinHeapNode + PartialOrd + Copy>: MinQueue<T>;
+}
+
+/// A `TieBreak` ordering every pushed node against the queued ones, so a
+/// `SortedQueue` pops like the heaps.
+pub trait SortedTieBreak: TieBreak {
+    /// Whether `queued` pops before `pushed`, which is pushed after it.
+    fn pops_before<T: PartialOrd>(queued: &T, pushed: &T) -> bool;
+}
+
+/// Plain sift-up and sift-down on the node order, ties pop in whatever order
+/// the sifts leave them in. This matches the BDO encoder.
+pub struct Classic;
+
+impl TieBreak for Classic {
+    type Seq = ();
+
+    #[inline(always)]
+    fn next(_: &mut ()) {}
+
+    #[inline(always)]
+    fn less<T: PartialOrd>(a: &(T, ()), b: &(T, ())) -> bool {
+        a.0 < b.0
+    }
+
+    type Queue<T: MinHeapNode + PartialOrd + Copy> = MinHeapless<T, Classic>;
+}
+
+/// Ties pop in push order, like a stable priority queue.
+pub struct InsertionOrder;
+
+impl TieBreak for InsertionOrder {
+    type Seq = u32;
+
+    #[inline(always)]
+    fn next(seq: &mut u32) -> u32 {
+        *seq += 1;
+        *seq
+    }
+
+    #[inline(always)]
+    fn less<T: PartialOrd>(a: &(T, u32), b: &(T, u32)) -> bool {
+        match a.0.partial_cmp(&b.0) {
+            Some(Ordering::Equal) => a.1 < b.1,
+            order => order == Some(Ordering::Less),
+        }
+    }
+
+    type Queue<T: MinHeapNode + PartialOrd + Copy> = SortedQueue<T, InsertionOrder>;
+}
+
+impl SortedTieBreak for InsertionOrder {
+    #[inline(always)]
+    fn pops_before<T: PartialOrd>(queued: &T, pushed: &T) -> bool {
+        queued <= pushed
+    }
+}
+
+/// Ties pop in reverse push order, the most recently pushed first.
+pub struct ReverseInsertionOrder;
+
+impl TieBreak for ReverseInsertionOrder {
+    type Seq = u32;
+
+    #[inline(always)]
+    fn next(seq: &mut u32) -> u32 {
+        *seq += 1;
+        *seq
+    }
+
+    #[inline(always)]
+    fn less<T: PartialOrd>(a: &(T, u32), b: &(T, u32)) -> bool {
+        match a.0.partial_cmp(&b.0) {
+            Some(Ordering::Equal) => a.1 > b.1,
+            order => order == Some(Ordering::Less),
+        }
+    }
+
+    type Queue<T: MinHeapNode + PartialOrd + Copy> = SortedQueue<T, ReverseInsertionOrder>;
+}
+
+impl SortedTieBreak for ReverseInsertionOrder {
+    #[inline(always)]
+    fn pops_before<T: PartialOrd>(queued: &T, pushed: &T) -> bool {
+        queued < pushed
+    }
+}
+
+/// A `TieBreak` chosen at runtime, for decoder options.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TieBreakPolicy {
+    #[default]
+    Classic,
+    InsertionOrder,
+    ReverseInsertionOrder,
+}
+
+impl TieBreakPolicy {
+    pub const ALL: [TieBreakPolicy; 3] = [
+        TieBreakPolicy::Classic,
+        TieBreakPolicy::InsertionOrder,
+        TieBreakPolicy::ReverseInsertionOrder,
+    ];
+}
+
+/// Calls the generic function `$f::<P>($args)` with the `TieBreak` of a
+/// `TieBreakPolicy`, e.g. `with_tie_break!(policy, fsm::decode_packet_with(content))`.
+#[macro_export]
+macro_rules! with_tie_break {
+    ($policy:expr, $($f:ident)::+ ($($args:expr),* $(,)?)) => {
+        match $policy {
+            $crate::min_heap::TieBreakPolicy::Classic => {
+                $($f)::+::<$crate::min_heap::Classic>($($args),*)
+            }
+            $crate::min_heap::TieBreakPolicy::InsertionOrder => {
+                $($f)::+::<$crate::min_heap::InsertionOrder>($($args),*)
+            }
+            $crate::min_heap::TieBreakPolicy::ReverseInsertionOrder => {
+                $($f)::+::<$crate::min_heap::ReverseInsertionOrder>($($args),*)
+            }
+        }
+    };
+}
+
+// MARK: Heaps
+
+/// The unsafe variant of the heap on a `Vec`, comparing nodes through
+/// `get_unchecked`. Pops in the same order as `MinHeapless` and
+/// `MinHeapChecked`, which the decoders use; this one is kept to bench them.
+pub struct MinHeap<T: MinHeapNode + core::cmp::PartialOrd, P: TieBreak = Classic> {
+    nodes: Vec<(T, P::Seq)>,
+    seq: P::Seq,
+    policy: PhantomData<P>,
+}
+
+impl<T: MinHeapNode + core::cmp::PartialOrd, P: TieBreak> Default for MinHeap<T, P> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T: MinHeapNode + std::cmp::PartialOrd> MinHeap<T> {
+impl<T: MinHeapNode + core::cmp::PartialOrd, P: TieBreak> MinHeap<T, P> {
     pub fn new() -> Self {
-        // MinHeap(Vec::<T, MAX_SYMBOLS>::new())
-        MinHeap(Vec::<T>::with_capacity(MAX_SYMBOLS))
+        MinHeap {
+            nodes: Vec::with_capacity(MAX_SYMBOLS),
+            seq: P::Seq::default(),
+            policy: PhantomData,
+        }
     }
 
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.nodes.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.len() == 0
+        self.nodes.len() == 0
     }
 
     pub fn push(&mut self, node: T) {
-        self.0.push(node);
-        let mut child = self.0.len() - 1;
+        self.nodes.push((node, P::next(&mut self.seq)));
+        let mut child = self.nodes.len() - 1;
 
         while child > 0 {
             let parent = (child - 1) / 2;
             // SAFETY: child is greater than 0 and parent can at minimum be zero.
-            if unsafe { self.0.get_unchecked(child) < self.0.get_unchecked(parent) } {
-                self.0.swap(child, parent);
+            if unsafe {
+                P::less(
+                    self.nodes.get_unchecked(child),
+                    self.nodes.get_unchecked(parent),
+                )
+            } {
+                self.nodes.swap(child, parent);
                 child = parent;
             } else {
                 break;
@@ -45,61 +203,87 @@ impl<T: MinHeapNode + std::cmp::PartialOrd> MinHeap<T> {
     }
 
     pub fn pop(&mut self) -> T {
-        let root = self.0.swap_remove(0);
+        let root = self.nodes.swap_remove(0);
         let mut parent = 0;
         let mut child = 1;
-        let end = self.0.len();
+        let end = self.nodes.len();
 
         while child < end {
             let right = child + 1;
-            if right < end && unsafe { self.0.get_unchecked(child) > self.0.get_unchecked(right) } {
+            // SAFETY: `right` and `child` are below `end` and `parent` below
+            // `child`, the length of the heap.
+            if right < end
+                && unsafe {
+                    P::less(
+                        self.nodes.get_unchecked(right),
+                        self.nodes.get_unchecked(child),
+                    )
+                }
+            {
                 child += 1;
             };
 
-            if unsafe { self.0.get_unchecked(child) < self.0.get_unchecked(parent) } {
-                self.0.swap(parent, child);
+            if unsafe {
+                P::less(
+                    self.nodes.get_unchecked(child),
+                    self.nodes.get_unchecked(parent),
+                )
+            } {
+                self.nodes.swap(parent, child);
                 parent = child;
                 child = 2 * parent + 1;
             } else {
                 break;
             }
         }
-        root
+        root.0
     }
 }
 
 use heapless::Vec as HeaplessVec;
-pub struct MinHeapless<T: MinHeapNode + std::cmp::PartialOrd>(HeaplessVec<T, MAX_SYMBOLS>);
+pub struct MinHeapless<T: MinHeapNode + core::cmp::PartialOrd, P: TieBreak = Classic> {
+    nodes: HeaplessVec<(T, P::Seq), MAX_SYMBOLS>,
+    seq: P::Seq,
+    policy: PhantomData<P>,
+}
 
-impl<T: MinHeapNode + std::cmp::PartialOrd> Default for MinHeapless<T> {
+impl<T: MinHeapNode + core::cmp::PartialOrd, P: TieBreak> Default for MinHeapless<T, P> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T: MinHeapNode + std::cmp::PartialOrd> MinHeapless<T> {
+impl<T: MinHeapNode + core::cmp::PartialOrd, P: TieBreak> MinHeapless<T, P> {
     pub fn new() -> Self {
-        MinHeapless(HeaplessVec::<T, MAX_SYMBOLS>::new())
-        // MinHeap(Vec::<T>::with_capacity(MAX_SYMBOLS))
+        MinHeapless {
+            nodes: HeaplessVec::new(),
+            seq: P::Seq::default(),
+            policy: PhantomData,
+        }
     }
 
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.nodes.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.len() == 0
+        self.nodes.len() == 0
     }
 
     pub fn push(&mut self, node: T) {
-        unsafe { self.0.push_unchecked(node) };
-        let mut child = self.0.len() - 1;
+        unsafe { self.nodes.push_unchecked((node, P::next(&mut self.seq))) };
+        let mut child = self.nodes.len() - 1;
 
         while child > 0 {
             let parent = (child - 1) / 2;
             // SAFETY: child is greater than 0 and parent can at minimum be zero.
-            if unsafe { self.0.get_unchecked(child) < self.0.get_unchecked(parent) } {
-                self.0.swap(child, parent);
+            if unsafe {
+                P::less(
+                    self.nodes.get_unchecked(child),
+                    self.nodes.get_unchecked(parent),
+                )
+            } {
+                self.nodes.swap(child, parent);
                 child = parent;
             } else {
                 break;
@@ -108,29 +292,221 @@ impl<T: MinHeapNode + std::cmp::PartialOrd> MinHeapless<T> {
     }
 
     pub fn pop(&mut self) -> T {
-        let root = self.0.swap_remove(0);
+        let root = self.nodes.swap_remove(0);
         let mut parent = 0;
         let mut child = 1;
-        let end = self.0.len();
+        let end = self.nodes.len();
 
         while child < end {
             let right = child + 1;
-            if right < end && unsafe { self.0.get_unchecked(child) > self.0.get_unchecked(right) } {
+            if right < end
+                && unsafe {
+                    P::less(
+                        self.nodes.get_unchecked(right),
+                        self.nodes.get_unchecked(child),
+                    )
+                }
+            {
                 child += 1;
             };
 
-            if unsafe { self.0.get_unchecked(child) < self.0.get_unchecked(parent) } {
-                self.0.swap(parent, child);
+            if unsafe {
+                P::less(
+                    self.nodes.get_unchecked(child),
+                    self.nodes.get_unchecked(parent),
+                )
+            } {
+                self.nodes.swap(parent, child);
                 parent = child;
                 child = 2 * parent + 1;
             } else {
                 break;
             }
         }
-        root
+        root.0
+    }
+}
+
+/// `MinHeapless` with checked pushes and accesses, for the safe decoders.
+///
+/// Pops in the same order, so trees built with it are identical.
+pub struct MinHeapChecked<T: MinHeapNode + core::cmp::PartialOrd, P: TieBreak = Classic> {
+    nodes: HeaplessVec<(T, P::Seq), MAX_SYMBOLS>,
+    seq: P::Seq,
+    policy: PhantomData<P>,
+}
+
+impl<T: MinHeapNode + core::cmp::PartialOrd, P: TieBreak> Default for MinHeapChecked<T, P> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+#[forbid(unsafe_code)]
+impl<T: MinHeapNode + core::cmp::PartialOrd, P: TieBreak> MinHeapChecked<T, P> {
+    pub fn new() -> Self {
+        MinHeapChecked {
+            nodes: HeaplessVec::new(),
+            seq: P::Seq::default(),
+            policy: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 0
+    }
+
+    /// Panics when pushing more than `MAX_SYMBOLS` nodes.
+    pub fn push(&mut self, node: T) {
+        let node = (node, P::next(&mut self.seq));
+        assert!(
+            self.nodes.push(node).is_ok(),
+            "more than {MAX_SYMBOLS} heap nodes"
+        );
+        let mut child = self.nodes.len() - 1;
+
+        while child > 0 {
+            let parent = (child - 1) / 2;
+            if P::less(&self.nodes[child], &self.nodes[parent]) {
+                self.nodes.swap(child, parent);
+                child = parent;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Panics when empty.
+    pub fn pop(&mut self) -> T {
+        let root = self.nodes.swap_remove(0);
+        let mut parent = 0;
+        let mut child = 1;
+        let end = self.nodes.len();
+
+        while child < end {
+            let right = child + 1;
+            if right < end && P::less(&self.nodes[right], &self.nodes[child]) {
+                child += 1;
+            };
+
+            if P::less(&self.nodes[child], &self.nodes[parent]) {
+                self.nodes.swap(parent, child);
+                parent = child;
+                child = 2 * parent + 1;
+            } else {
+                break;
+            }
+        }
+        root.0
+    }
+}
+
+/// A sorted insertion array popping in the same order as `MinHeapless` with the
+/// insertion order policies, for building their trees without sifting a heap.
+///
+/// Nodes are kept in reverse pop order, so a pop takes the last node and a push
+/// moves the nodes popping before it up one slot. This needs every pushed node
+/// ordered against the queued ones, which `Classic` ties aren't, so there is no
+/// `SortedQueue` for the BDO packets.
+pub struct SortedQueue<
+    T: MinHeapNode + core::cmp::PartialOrd + Copy,
+    P: SortedTieBreak = InsertionOrder,
+> {
+    nodes: HeaplessVec<T, MAX_SYMBOLS>,
+    policy: PhantomData<P>,
+}
+
+impl<T: MinHeapNode + core::cmp::PartialOrd + Copy, P: SortedTieBreak> Default
+    for SortedQueue<T, P>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: MinHeapNode + core::cmp::PartialOrd + Copy, P: SortedTieBreak> SortedQueue<T, P> {
+    pub fn new() -> Self {
+        SortedQueue {
+            nodes: HeaplessVec::new(),
+            policy: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 0
+    }
+
+    pub fn push(&mut self, node: T) {
+        let len = self.nodes.len();
+        assert!(len < MAX_SYMBOLS, "a SortedQueue holds {MAX_SYMBOLS} nodes");
+        // SAFETY: the slot at `len` is in bounds as checked above. The nodes
+        // are `Copy`, so a panicking comparison can't drop one twice.
+        unsafe {
+            let nodes = self.nodes.as_mut_ptr();
+            let mut index = len;
+            while index > 0 && P::pops_before(&*nodes.add(index - 1), &node) {
+                nodes.add(index).write(nodes.add(index - 1).read());
+                index -= 1;
+            }
+            nodes.add(index).write(node);
+            self.nodes.set_len(len + 1);
+        }
+    }
+
+    pub fn pop(&mut self) -> T {
+        self.nodes.pop().unwrap()
+    }
+}
+
+// MARK: Queues
+
+/// The push and pop of the heaps and `SortedQueue`, for tree builders generic
+/// over the queue.
+pub trait MinQueue<T>: Default {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool;
+    fn push(&mut self, node: T);
+    fn pop(&mut self) -> T;
+}
+
+macro_rules! impl_min_queue {
+    ($queue:ident, $bound:ident $(+ $extra:ident)?) => {
+        impl<T: MinHeapNode + core::cmp::PartialOrd $(+ $extra)?, P: $bound> MinQueue<T>
+            for $queue<T, P>
+        {
+            #[inline(always)]
+            fn len(&self) -> usize {
+                $queue::len(self)
+            }
+            #[inline(always)]
+            fn is_empty(&self) -> bool {
+                $queue::is_empty(self)
+            }
+            #[inline(always)]
+            fn push(&mut self, node: T) {
+                $queue::push(self, node)
+            }
+            #[inline(always)]
+            fn pop(&mut self) -> T {
+                $queue::pop(self)
+            }
+        }
+    };
+}
+
+impl_min_queue!(MinHeap, TieBreak);
+impl_min_queue!(MinHeapless, TieBreak);
+impl_min_queue!(MinHeapChecked, TieBreak);
+impl_min_queue!(SortedQueue, SortedTieBreak + Copy);
+
 // MARK: Unit Tests
 
 #[cfg(test)]
@@ -139,7 +515,7 @@ mod tests {
     use crate::test_cases::*;
 
     #[repr(C)]
-    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
     pub struct HeapNode {
         pub left_index: u8,
         pub right_index: u8,
@@ -208,6 +584,181 @@ mod tests {
         assert_eq!(pop_order, EXPECTED_POP_ORDER);
     }
 
+    fn pop_order_with<P: TieBreak>() -> std::vec::Vec<Option<u8>> {
+        let mut heap = MinHeapless::<HeapNode, P>::new();
+        for (symbol, frequency) in EXPECTED_SYMBOL_FREQUENCIES {
+            heap.push(HeapNode::new(Some(symbol), frequency));
+        }
+        let mut pop_order = std::vec::Vec::new();
+        while !heap.is_empty() {
+            pop_order.push(heap.pop().symbol);
+        }
+        pop_order
+    }
+
+    #[test]
+    fn pop_order_tie_breaks() {
+        assert_eq!(pop_order_with::<Classic>(), EXPECTED_POP_ORDER);
+
+        // `EXPECTED_POP_ORDER` with the ties of each frequency in push order,
+        // then in reverse push order.
+        let stable = [50, 52, 57, 51, 54, 55, 124, 49, 56, 45, 53, 48].map(Some);
+        let reverse = [57, 52, 50, 124, 55, 54, 51, 56, 49, 53, 45, 48].map(Some);
+        assert_eq!(pop_order_with::<InsertionOrder>(), stable);
+        assert_eq!(pop_order_with::<ReverseInsertionOrder>(), reverse);
+
+        let frequency = |symbol: Option<u8>| {
+            let symbol = symbol.unwrap();
+            EXPECTED_SYMBOL_FREQUENCIES
+                .iter()
+                .find(|(s, _)| *s == symbol)
+                .unwrap()
+                .1
+        };
+        for order in [stable, reverse] {
+            assert_eq!(order.map(frequency), EXPECTED_POP_ORDER.map(frequency));
+        }
+    }
+
+    #[test]
+    fn tie_breaks_match_across_heaps() {
+        fn pop_orders<P: TieBreak>() -> [std::vec::Vec<Option<u8>>; 3] {
+            let mut heap = MinHeap::<HeapNode, P>::new();
+            let mut checked = MinHeapChecked::<HeapNode, P>::new();
+            for (symbol, frequency) in EXPECTED_SYMBOL_FREQUENCIES {
+                heap.push(HeapNode::new(Some(symbol), frequency));
+                checked.push(HeapNode::new(Some(symbol), frequency));
+            }
+            let heap = (0..heap.len()).map(|_| heap.pop().symbol).collect();
+            let checked = (0..checked.len()).map(|_| checked.pop().symbol).collect();
+            [heap, checked, pop_order_with::<P>()]
+        }
+        for orders in [
+            pop_orders::<Classic>(),
+            pop_orders::<InsertionOrder>(),
+            pop_orders::<ReverseInsertionOrder>(),
+        ] {
+            assert_eq!(orders[0], orders[2]);
+            assert_eq!(orders[1], orders[2]);
+        }
+    }
+
+    // Merges like building a tree from every ordering of up to 6 leaf
+    // frequencies with ties, so the parents also tie with leaves and parents,
+    // and from every pair of frequencies for more leaves.
+    fn assert_same_merges<P: TieBreak, Q: MinQueue<HeapNode>>() {
+        for n in 1..=MAX_SYMBOLS {
+            let max_frequency = if n <= 6 { n as u32 } else { 2 };
+            let mut frequencies = std::vec![1; n];
+            loop {
+                let mut heap = MinHeapless::<HeapNode, P>::new();
+                let mut queue = Q::default();
+                for (symbol, &frequency) in frequencies.iter().enumerate() {
+                    heap.push(HeapNode::new(Some(symbol as u8), frequency));
+                    queue.push(HeapNode::new(Some(symbol as u8), frequency));
+                }
+                for parent in 0.. {
+                    let left = heap.pop();
+                    assert_eq!(queue.pop(), left, "{frequencies:?}");
+                    if heap.is_empty() {
+                        break;
+                    }
+                    let right = heap.pop();
+                    assert_eq!(queue.pop(), right, "{frequencies:?}");
+                    let parent = HeapNode {
+                        left_index: parent,
+                        frequency: left.frequency + right.frequency,
+                        ..Default::default()
+                    };
+                    heap.push(parent);
+                    queue.push(parent);
+                }
+                assert!(queue.is_empty());
+
+                // Next frequencies, counting in base max_frequency.
+                let Some(i) = frequencies.iter().position(|&f| f < max_frequency) else {
+                    break;
+                };
+                frequencies[i] += 1;
+                frequencies[..i].fill(1);
+            }
+        }
+    }
+
+    #[test]
+    fn sorted_queue_matches_heapless() {
+        assert_same_merges::<InsertionOrder, SortedQueue<HeapNode, InsertionOrder>>();
+        assert_same_merges::<ReverseInsertionOrder, SortedQueue<HeapNode, ReverseInsertionOrder>>();
+        assert_same_merges::<Classic, <Classic as TieBreak>::Queue<HeapNode>>();
+    }
+
+    #[test]
+    fn classic_ties_arent_sorted() {
+        // Equal leaves pop out of push order, so no sorted queue pops like the
+        // `Classic` heap and its `Queue` stays `MinHeapless`.
+        let mut heap = MinHeapless::<HeapNode>::new();
+        for symbol in 0..3 {
+            heap.push(HeapNode::new(Some(symbol), 1));
+        }
+        let pop_order: std::vec::Vec<_> = (0..3).map(|_| heap.pop().symbol).collect();
+        assert_eq!(pop_order, [Some(0), Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn pop_order_checked() {
+        let mut heap = MinHeapChecked::<HeapNode>::new();
+        for (symbol, frequency) in EXPECTED_SYMBOL_FREQUENCIES {
+            heap.push(HeapNode::new(Some(symbol), frequency));
+        }
+        let mut pop_order = std::vec::Vec::<Option<u8>>::new();
+        while !heap.is_empty() {
+            pop_order.push(heap.pop().symbol);
+        }
+        assert_eq!(pop_order, EXPECTED_POP_ORDER);
+    }
+
+    #[test]
+    fn checked_matches_heapless() {
+        // Equal frequencies, so the tie order depends on the sift steps.
+        let mut checked = MinHeapChecked::<HeapNode>::new();
+        let mut heapless = MinHeapless::<HeapNode>::new();
+        for symbol in 0..MAX_SYMBOLS as u8 {
+            let frequency = u32::from(symbol % 5);
+            checked.push(HeapNode::new(Some(symbol), frequency));
+            heapless.push(HeapNode::new(Some(symbol), frequency));
+        }
+        // Merges like building a tree, the parents tie with the leaves.
+        while heapless.len() > 1 {
+            let left = heapless.pop();
+            let right = heapless.pop();
+            assert_eq!(checked.pop(), left);
+            assert_eq!(checked.pop(), right);
+            let frequency = left.frequency + right.frequency;
+            checked.push(HeapNode::new(None, frequency));
+            heapless.push(HeapNode::new(None, frequency));
+        }
+        assert_eq!(checked.pop(), heapless.pop());
+        assert!(checked.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "heap nodes")]
+    fn checked_push_overflow() {
+        let mut heap = MinHeapChecked::<HeapNode>::new();
+        for symbol in 0..=MAX_SYMBOLS as u8 {
+            heap.push(HeapNode::new(Some(symbol), 1));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "a SortedQueue holds 12 nodes")]
+    fn sorted_queue_push_overflow() {
+        let mut queue = SortedQueue::<HeapNode>::new();
+        for symbol in 0..=MAX_SYMBOLS as u8 {
+            queue.push(HeapNode::new(Some(symbol), 1));
+        }
+    }
+
     #[test]
     fn min() {
         let mut heap = MinHeap::<HeapNode>::new();
@@ -234,13 +785,12 @@ mod tests {
 }
 
 // MARK: Benches
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = 10_000)]
 mod benches {
     use super::*;
     use crate::test_cases::*;
 
-    // use divan::{black_box, Bencher};
-
     #[repr(C)]
     #[derive(Clone, Debug, Default, PartialEq, Eq)]
     pub struct HeapNode {
@@ -290,4 +840,30 @@ mod benches {
         }
         assert_eq!(pop_order, EXPECTED_POP_ORDER);
     }
+
+    #[divan::bench]
+    fn pop_order_heapless() {
+        let mut heap = MinHeapless::<HeapNode>::new();
+        for (symbol, frequency) in EXPECTED_SYMBOL_FREQUENCIES {
+            heap.push(HeapNode::new(Some(symbol), frequency));
+        }
+        let mut pop_order = std::vec::Vec::<Option<u8>>::new();
+        while !heap.is_empty() {
+            pop_order.push(heap.pop().symbol);
+        }
+        assert_eq!(pop_order, EXPECTED_POP_ORDER);
+    }
+
+    #[divan::bench]
+    fn pop_order_checked() {
+        let mut heap = MinHeapChecked::<HeapNode>::new();
+        for (symbol, frequency) in EXPECTED_SYMBOL_FREQUENCIES {
+            heap.push(HeapNode::new(Some(symbol), frequency));
+        }
+        let mut pop_order = std::vec::Vec::<Option<u8>>::new();
+        while !heap.is_empty() {
+            pop_order.push(heap.pop().symbol);
+        }
+        assert_eq!(pop_order, EXPECTED_POP_ORDER);
+    }
 }
@@ -1,12 +1,118 @@
+use alloc::vec::Vec;
+use bytes::{BufMut, Bytes, BytesMut};
+
 pub const MAX_SYMBOLS: usize = 12; // digits 0-9, '|' and '-'
 
+/// The symbols allowed in a packet's symbol table.
+pub const ALLOWED_SYMBOLS: &[u8; MAX_SYMBOLS] = b"-0123456789|";
+
+/// Why `Packet::try_new` rejected a packet, with the byte offset of the problem.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PacketError {
+    /// The content ends before `field` at `offset` could be read.
+    Truncated {
+        field: &'static str,
+        offset: usize,
+        needed: usize,
+        available: usize,
+    },
+    /// `len_content` doesn't match the size of the content.
+    LengthMismatch { len: u64, content_len: usize },
+    /// `len_symbol_table` is outside of `2..=MAX_SYMBOLS`.
+    SymbolCount { symbol_count: u32 },
+    /// The symbol of entry `entry` at `offset` isn't one of `ALLOWED_SYMBOLS`.
+    InvalidSymbol {
+        entry: usize,
+        offset: usize,
+        symbol: u8,
+    },
+    /// The symbol of entry `entry` appeared in an earlier entry.
+    DuplicateSymbol {
+        entry: usize,
+        offset: usize,
+        symbol: u8,
+    },
+    /// The strz padding of entry `entry` has a non-zero byte at `offset`.
+    NonZeroPadding {
+        entry: usize,
+        offset: usize,
+        byte: u8,
+    },
+    /// `len_bitstream` needs more bits than `len_encoded_data` holds.
+    BitstreamLength {
+        bitstream_len: u32,
+        encoded_bytes_len: u32,
+    },
+}
+
+impl core::fmt::Display for PacketError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            PacketError::Truncated {
+                field,
+                offset,
+                needed,
+                available,
+            } => write!(
+                f,
+                "truncated {field} at offset {offset}: needed {needed} bytes, {available} available"
+            ),
+            PacketError::LengthMismatch { len, content_len } => write!(
+                f,
+                "len_content is {len} but the content is {content_len} bytes"
+            ),
+            PacketError::SymbolCount { symbol_count } => write!(
+                f,
+                "len_symbol_table is {symbol_count}, expected 2 to {MAX_SYMBOLS}"
+            ),
+            PacketError::InvalidSymbol {
+                entry,
+                offset,
+                symbol,
+            } => write!(
+                f,
+                "symbol entry {entry} has invalid symbol {symbol:#04x} at offset {offset}"
+            ),
+            PacketError::DuplicateSymbol {
+                entry,
+                offset,
+                symbol,
+            } => write!(
+                f,
+                "symbol entry {entry} repeats symbol {:?} at offset {offset}",
+                symbol as char
+            ),
+            PacketError::NonZeroPadding {
+                entry,
+                offset,
+                byte,
+            } => write!(
+                f,
+                "symbol entry {entry} has non-zero strz padding {byte:#04x} at offset {offset}"
+            ),
+            PacketError::BitstreamLength {
+                bitstream_len,
+                encoded_bytes_len,
+            } => write!(
+                f,
+                "len_bitstream is {bitstream_len} bits but len_encoded_data is {encoded_bytes_len} bytes"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for PacketError {}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Packet<'a> {
     pub len: u64,
     pub symbol_count: u32,
+    #[cfg_attr(feature = "serde", serde(with = "serde_bytes", borrow))]
     pub symbol_frequency_bytes: &'a [u8],
     pub bitstream_len: u32,
     pub encoded_bytes_len: u32,
     pub decoded_bytes_len: u32,
+    #[cfg_attr(feature = "serde", serde(with = "serde_bytes", borrow))]
     pub encoded_message: &'a [u8],
 }
 
@@ -15,6 +121,11 @@ impl<'a> Packet<'a> {
     // parsing to avoid allocating new storage and redundant copying.
     // This reduces the runtime of large packet parsing from ~440ns to 3.2ns.
     pub fn new(content: &'a [u8]) -> Self {
+        crate::metrics::time(crate::metrics::Phase::Parse, || Self::parse(content))
+    }
+
+    #[inline(always)]
+    fn parse(content: &'a [u8]) -> Self {
         let mut pos = 0;
 
         let u64_bytes: [u8; 8] = content[pos..pos + 8].try_into().unwrap();
@@ -52,6 +163,528 @@ impl<'a> Packet<'a> {
             encoded_message,
         }
     }
+
+    /// Creates a `Packet` after checking `content` against the packet layout.
+    ///
+    /// Unlike `new` this never panics. Every field must be in bounds, `len`
+    /// must match the content size, each symbol entry must hold a distinct
+    /// symbol from `ALLOWED_SYMBOLS` followed by zeroed strz padding and the
+    /// bitstream must fit in the encoded message.
+    pub fn try_new(content: &'a [u8]) -> Result<Self, PacketError> {
+        let header = PacketHeader::try_new(content)?;
+        let pos = header.header_len();
+        let encoded_message =
+            read_field(content, "message", pos, header.encoded_bytes_len as usize)?;
+        if header.len != content.len() as u64 || pos + encoded_message.len() != content.len() {
+            return Err(PacketError::LengthMismatch {
+                len: header.len,
+                content_len: content.len(),
+            });
+        }
+        header.check_bitstream_len()?;
+
+        Ok(Packet {
+            len: header.len,
+            symbol_count: header.symbol_count,
+            symbol_frequency_bytes: header.symbol_frequency_bytes,
+            bitstream_len: header.bitstream_len,
+            encoded_bytes_len: header.encoded_bytes_len,
+            decoded_bytes_len: header.decoded_bytes_len,
+            encoded_message,
+        })
+    }
+
+    /// The `(symbol, frequency)` entries of the symbol table in packet order.
+    pub fn symbol_frequencies(&self) -> impl Iterator<Item = (u8, u32)> + 'a {
+        symbol_frequencies(self.symbol_frequency_bytes)
+    }
+
+    /// The fields before the encoded message.
+    pub fn header(&self) -> PacketHeader<'a> {
+        PacketHeader {
+            len: self.len,
+            symbol_count: self.symbol_count,
+            symbol_frequency_bytes: self.symbol_frequency_bytes,
+            bitstream_len: self.bitstream_len,
+            encoded_bytes_len: self.encoded_bytes_len,
+            decoded_bytes_len: self.decoded_bytes_len,
+        }
+    }
+
+    /// Copies the packet contents into an `OwnedPacket` that doesn't borrow the
+    /// input, checking them like `OwnedPacket::try_new`.
+    pub fn to_owned(&self) -> Result<OwnedPacket, PacketError> {
+        let mut content = BytesMut::with_capacity(
+            24 + self.symbol_frequency_bytes.len() + self.encoded_message.len(),
+        );
+        content.put_u64_le(self.len);
+        content.put_u32_le(self.symbol_count);
+        content.put_slice(self.symbol_frequency_bytes);
+        content.put_u32_le(self.bitstream_len);
+        content.put_u32_le(self.encoded_bytes_len);
+        content.put_u32_le(self.decoded_bytes_len);
+        content.put_slice(self.encoded_message);
+        OwnedPacket::try_new(content.freeze())
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Packet<'a> {
+    type Error = PacketError;
+
+    fn try_from(content: &'a [u8]) -> Result<Self, Self::Error> {
+        Packet::try_new(content)
+    }
+}
+
+impl core::fmt::Debug for Packet<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        struct Symbols<'a, 'b>(&'b Packet<'a>);
+        impl core::fmt::Debug for Symbols<'_, '_> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.debug_map()
+                    .entries(
+                        self.0
+                            .symbol_frequencies()
+                            .map(|(s, freq)| (s as char, freq)),
+                    )
+                    .finish()
+            }
+        }
+
+        f.debug_struct("Packet")
+            .field("len", &self.len)
+            .field("symbol_count", &self.symbol_count)
+            .field("symbols", &Symbols(self))
+            .field("bitstream_len", &self.bitstream_len)
+            .field("encoded_bytes_len", &self.encoded_bytes_len)
+            .field("decoded_bytes_len", &self.decoded_bytes_len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl core::fmt::Display for Packet<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "packet of {} bytes: {} symbols, {} bits in {} encoded bytes, {} decoded bytes",
+            self.len,
+            self.symbol_count,
+            self.bitstream_len,
+            self.encoded_bytes_len,
+            self.decoded_bytes_len
+        )
+    }
+}
+
+// MARK: PacketHeader
+
+/// The fields before the encoded message, for readers that receive a packet in
+/// pieces and want to check it before the message arrives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PacketHeader<'a> {
+    pub len: u64,
+    pub symbol_count: u32,
+    pub symbol_frequency_bytes: &'a [u8],
+    pub bitstream_len: u32,
+    pub encoded_bytes_len: u32,
+    pub decoded_bytes_len: u32,
+}
+
+impl<'a> PacketHeader<'a> {
+    /// The `len_content` and `len_symbol_table` fields.
+    pub const PREFIX_LEN: usize = 12;
+
+    /// The header length of a packet with `symbol_count` symbols.
+    pub const fn len_for(symbol_count: u32) -> usize {
+        24 + 8 * symbol_count as usize
+    }
+
+    /// Checks the header fields the same way as `Packet::try_new`, `content`
+    /// only needs to hold the header.
+    pub fn try_new(content: &'a [u8]) -> Result<Self, PacketError> {
+        let read_u32 = |field: &'static str, offset: usize| {
+            read_field(content, field, offset, 4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+
+        let len = read_field(content, "len_content", 0, 8)?;
+        let len = u64::from_le_bytes(len.try_into().unwrap());
+        let symbol_count = read_u32("len_symbol_table", 8)?;
+        if !(2..=MAX_SYMBOLS as u32).contains(&symbol_count) {
+            return Err(PacketError::SymbolCount { symbol_count });
+        }
+
+        let mut pos = Self::PREFIX_LEN;
+        let symbol_frequency_bytes =
+            read_field(content, "symbol_table", pos, 8 * symbol_count as usize)?;
+        for (entry, chunk) in symbol_frequency_bytes.chunks_exact(8).enumerate() {
+            let offset = pos + 8 * entry + 4;
+            let symbol = chunk[4];
+            if !ALLOWED_SYMBOLS.contains(&symbol) {
+                return Err(PacketError::InvalidSymbol {
+                    entry,
+                    offset,
+                    symbol,
+                });
+            }
+            if symbol_frequency_bytes[..8 * entry]
+                .chunks_exact(8)
+                .any(|earlier| earlier[4] == symbol)
+            {
+                return Err(PacketError::DuplicateSymbol {
+                    entry,
+                    offset,
+                    symbol,
+                });
+            }
+            if let Some(i) = chunk[5..].iter().position(|&byte| byte != 0) {
+                return Err(PacketError::NonZeroPadding {
+                    entry,
+                    offset: offset + 1 + i,
+                    byte: chunk[5 + i],
+                });
+            }
+        }
+        pos += symbol_frequency_bytes.len();
+
+        Ok(PacketHeader {
+            len,
+            symbol_count,
+            symbol_frequency_bytes,
+            bitstream_len: read_u32("len_bitstream", pos)?,
+            encoded_bytes_len: read_u32("len_encoded_data", pos + 4)?,
+            decoded_bytes_len: read_u32("len_decoded_data", pos + 8)?,
+        })
+    }
+
+    pub fn header_len(&self) -> usize {
+        Self::len_for(self.symbol_count)
+    }
+
+    /// Checks that `len_bitstream` fits in `len_encoded_data`.
+    pub fn check_bitstream_len(&self) -> Result<(), PacketError> {
+        if self.bitstream_len as u64 > 8 * self.encoded_bytes_len as u64 {
+            return Err(PacketError::BitstreamLength {
+                bitstream_len: self.bitstream_len,
+                encoded_bytes_len: self.encoded_bytes_len,
+            });
+        }
+        Ok(())
+    }
+
+    /// The `(symbol, frequency)` entries of the symbol table in packet order.
+    pub fn symbol_frequencies(&self) -> impl Iterator<Item = (u8, u32)> + 'a {
+        symbol_frequencies(self.symbol_frequency_bytes)
+    }
+}
+
+fn read_field<'a>(
+    content: &'a [u8],
+    field: &'static str,
+    offset: usize,
+    needed: usize,
+) -> Result<&'a [u8], PacketError> {
+    content
+        .get(offset..)
+        .and_then(|rest| rest.get(..needed))
+        .ok_or(PacketError::Truncated {
+            field,
+            offset,
+            needed,
+            available: content.len().saturating_sub(offset),
+        })
+}
+
+fn symbol_frequencies(bytes: &[u8]) -> impl Iterator<Item = (u8, u32)> + '_ {
+    bytes.chunks_exact(8).map(|chunk| {
+        let frequency = u32::from_le_bytes(chunk[..4].try_into().unwrap());
+        (chunk[4], frequency)
+    })
+}
+
+// MARK: OwnedPacket
+
+/// A packet that owns its contents, for queueing packets for deferred decoding.
+///
+/// The contents are held in a reference counted `Bytes` so clones are cheap and
+/// the packet can be sent to other threads after the input buffer is dropped.
+/// They always pass `Packet::try_new`.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "PacketFields", into = "PacketFields")
+)]
+pub struct OwnedPacket {
+    content: Bytes,
+}
+
+impl OwnedPacket {
+    /// Takes ownership of `content` without copying it, after checking it with
+    /// `Packet::try_new`.
+    pub fn try_new(content: impl Into<Bytes>) -> Result<Self, PacketError> {
+        let content = content.into();
+        Packet::try_new(&content)?;
+        Ok(OwnedPacket { content })
+    }
+
+    pub fn packet(&self) -> Packet<'_> {
+        // Checked by `try_new`, so parsing can't panic.
+        Packet::new(&self.content)
+    }
+
+    /// The raw packet contents, as passed to the `decode_packet` functions.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.content
+    }
+}
+
+impl TryFrom<&Packet<'_>> for OwnedPacket {
+    type Error = PacketError;
+
+    fn try_from(packet: &Packet<'_>) -> Result<Self, Self::Error> {
+        packet.to_owned()
+    }
+}
+
+impl TryFrom<Bytes> for OwnedPacket {
+    type Error = PacketError;
+
+    fn try_from(content: Bytes) -> Result<Self, Self::Error> {
+        OwnedPacket::try_new(content)
+    }
+}
+
+impl TryFrom<Vec<u8>> for OwnedPacket {
+    type Error = PacketError;
+
+    fn try_from(content: Vec<u8>) -> Result<Self, Self::Error> {
+        OwnedPacket::try_new(content)
+    }
+}
+
+impl core::fmt::Debug for OwnedPacket {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&self.packet(), f)
+    }
+}
+
+impl core::fmt::Display for OwnedPacket {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.packet(), f)
+    }
+}
+
+// `OwnedPacket` is (de)serialized with the same fields as `Packet` so a
+// serialized `Packet` can be deserialized as an `OwnedPacket`. Fields that
+// don't make a valid packet fail to deserialize.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PacketFields {
+    len: u64,
+    symbol_count: u32,
+    symbol_frequency_bytes: Bytes,
+    bitstream_len: u32,
+    encoded_bytes_len: u32,
+    decoded_bytes_len: u32,
+    encoded_message: Bytes,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<PacketFields> for OwnedPacket {
+    type Error = PacketError;
+
+    fn try_from(fields: PacketFields) -> Result<Self, Self::Error> {
+        Packet {
+            len: fields.len,
+            symbol_count: fields.symbol_count,
+            symbol_frequency_bytes: &fields.symbol_frequency_bytes,
+            bitstream_len: fields.bitstream_len,
+            encoded_bytes_len: fields.encoded_bytes_len,
+            decoded_bytes_len: fields.decoded_bytes_len,
+            encoded_message: &fields.encoded_message,
+        }
+        .to_owned()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<OwnedPacket> for PacketFields {
+    fn from(owned: OwnedPacket) -> Self {
+        let packet = owned.packet();
+        let slice = |bytes: &[u8]| owned.content.slice_ref(bytes);
+        PacketFields {
+            len: packet.len,
+            symbol_count: packet.symbol_count,
+            symbol_frequency_bytes: slice(packet.symbol_frequency_bytes),
+            bitstream_len: packet.bitstream_len,
+            encoded_bytes_len: packet.encoded_bytes_len,
+            decoded_bytes_len: packet.decoded_bytes_len,
+            encoded_message: slice(packet.encoded_message),
+        }
+    }
+}
+
+// MARK: PacketBuilder
+
+/// Builds packet contents field by field in the layout `Packet::new` parses.
+///
+/// Fields that aren't set explicitly are derived from the others:
+/// - `len` is the size of the written packet.
+/// - `symbol_count` is the number of symbol entries.
+/// - `bitstream_len` is 8 bits per message byte.
+/// - `encoded_bytes_len` is the message length.
+/// - `decoded_bytes_len` is the sum of the symbol frequencies.
+///
+/// Setting a field explicitly writes it as is, even when it doesn't agree with
+/// the rest of the packet.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PacketBuilder {
+    len: Option<u64>,
+    symbol_count: Option<u32>,
+    symbols: Vec<(u8, u32)>,
+    bitstream_len: Option<u32>,
+    encoded_bytes_len: Option<u32>,
+    decoded_bytes_len: Option<u32>,
+    message: Vec<u8>,
+}
+
+impl PacketBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from the fields of an existing packet, `len` is recomputed.
+    pub fn from_packet(packet: &Packet) -> Self {
+        PacketBuilder {
+            len: None,
+            symbol_count: Some(packet.symbol_count),
+            symbols: packet.symbol_frequencies().collect(),
+            bitstream_len: Some(packet.bitstream_len),
+            encoded_bytes_len: Some(packet.encoded_bytes_len),
+            decoded_bytes_len: Some(packet.decoded_bytes_len),
+            message: packet.encoded_message.to_vec(),
+        }
+    }
+
+    pub fn len(&mut self, len: u64) -> &mut Self {
+        self.len = Some(len);
+        self
+    }
+
+    pub fn symbol_count(&mut self, symbol_count: u32) -> &mut Self {
+        self.symbol_count = Some(symbol_count);
+        self
+    }
+
+    /// Appends a symbol entry, written as a `u32` frequency and a 4 byte strz.
+    pub fn symbol(&mut self, symbol: u8, frequency: u32) -> &mut Self {
+        self.symbols.push((symbol, frequency));
+        self.symbol_count = None;
+        self
+    }
+
+    pub fn symbols(&mut self, symbols: impl IntoIterator<Item = (u8, u32)>) -> &mut Self {
+        self.symbols.extend(symbols);
+        self.symbol_count = None;
+        self
+    }
+
+    /// Removes every entry for `symbol`, keeping the order of the others.
+    pub fn remove_symbol(&mut self, symbol: u8) -> &mut Self {
+        self.symbols.retain(|&(s, _)| s != symbol);
+        self.symbol_count = None;
+        self
+    }
+
+    pub fn clear_symbols(&mut self) -> &mut Self {
+        self.symbols.clear();
+        self.symbol_count = None;
+        self
+    }
+
+    pub fn bitstream_len(&mut self, bitstream_len: u32) -> &mut Self {
+        self.bitstream_len = Some(bitstream_len);
+        self
+    }
+
+    pub fn encoded_bytes_len(&mut self, encoded_bytes_len: u32) -> &mut Self {
+        self.encoded_bytes_len = Some(encoded_bytes_len);
+        self
+    }
+
+    pub fn decoded_bytes_len(&mut self, decoded_bytes_len: u32) -> &mut Self {
+        self.decoded_bytes_len = Some(decoded_bytes_len);
+        self
+    }
+
+    /// Sets the encoded message and resets `bitstream_len` and `encoded_bytes_len`.
+    pub fn message(&mut self, message: &[u8]) -> &mut Self {
+        self.message = message.to_vec();
+        self.bitstream_len = None;
+        self.encoded_bytes_len = None;
+        self
+    }
+
+    /// Shortens the encoded message, clamping `bitstream_len` and `encoded_bytes_len`.
+    pub fn truncate_message(&mut self, len: usize) -> &mut Self {
+        self.message.truncate(len);
+        let max_len = self.message.len() as u32;
+        self.encoded_bytes_len = self.encoded_bytes_len.map(|n| n.min(max_len));
+        self.bitstream_len = self.bitstream_len.map(|n| n.min(8 * max_len));
+        self
+    }
+
+    /// The number of bytes `build` will write.
+    pub fn written_len(&self) -> usize {
+        24 + 8 * self.symbols.len() + self.message.len()
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut content = Vec::with_capacity(self.written_len());
+        self.put_header(&mut content);
+        content.extend_from_slice(&self.message);
+        content
+    }
+
+    /// `build` into an `OwnedPacket`, failing if the fields set explicitly
+    /// don't make a valid packet.
+    pub fn build_owned(&self) -> Result<OwnedPacket, PacketError> {
+        OwnedPacket::try_new(self.build())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn write_to<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let mut header = Vec::with_capacity(self.written_len() - self.message.len());
+        self.put_header(&mut header);
+        writer.write_all(&header)?;
+        writer.write_all(&self.message)
+    }
+
+    // Everything up to the encoded message.
+    fn put_header(&self, buf: &mut impl BufMut) {
+        let len = self.len.unwrap_or(self.written_len() as u64);
+        let symbol_count = self.symbol_count.unwrap_or(self.symbols.len() as u32);
+        let encoded_bytes_len = self.encoded_bytes_len.unwrap_or(self.message.len() as u32);
+        let bitstream_len = self.bitstream_len.unwrap_or(8 * self.message.len() as u32);
+        let decoded_bytes_len = self
+            .decoded_bytes_len
+            .unwrap_or_else(|| self.symbols.iter().map(|&(_, freq)| freq).sum());
+
+        buf.put_u64_le(len);
+        buf.put_u32_le(symbol_count);
+        for &(symbol, frequency) in &self.symbols {
+            buf.put_u32_le(frequency);
+            buf.put_slice(&[symbol, 0, 0, 0]);
+        }
+        buf.put_u32_le(bitstream_len);
+        buf.put_u32_le(encoded_bytes_len);
+        buf.put_u32_le(decoded_bytes_len);
+    }
 }
 
 // =========================================================
@@ -79,10 +712,237 @@ mod tests {
         }
 
         assert!(frequencies == EXPECTED_SYMBOL_FREQUENCIES);
+        assert!(packet.symbol_frequencies().eq(EXPECTED_SYMBOL_FREQUENCIES));
+    }
+
+    #[test]
+    fn to_owned_round_trips() {
+        let content = TEST_BYTES.to_vec();
+        let owned = Packet::new(&content).to_owned().unwrap();
+        drop(content);
+        assert_eq!(owned.as_bytes(), TEST_BYTES);
+        assert_eq!(owned, OwnedPacket::try_new(TEST_BYTES.to_vec()).unwrap());
+
+        let packet = owned.packet();
+        assert_eq!(packet.decoded_bytes_len, 40);
+        assert_eq!(packet.encoded_message, &TEST_BYTES[120..]);
+
+        let handle = std::thread::spawn(move || owned.packet().symbol_count);
+        assert_eq!(handle.join().unwrap(), 12);
+    }
+
+    #[test]
+    fn owned_packets_are_checked() {
+        assert!(matches!(
+            OwnedPacket::try_new(TEST_BYTES[..100].to_vec()),
+            Err(PacketError::Truncated { .. })
+        ));
+        let mut packet = Packet::new(&TEST_BYTES);
+        packet.len += 1;
+        assert!(matches!(
+            packet.to_owned(),
+            Err(PacketError::LengthMismatch { .. })
+        ));
+        assert!(PacketBuilder::from_packet(&Packet::new(&TEST_BYTES))
+            .symbol_count(13)
+            .build_owned()
+            .is_err());
+    }
+
+    #[test]
+    fn formats_header_summary() {
+        let packet = Packet::new(&TEST_BYTES);
+        assert_eq!(
+            format!("{packet}"),
+            "packet of 136 bytes: 12 symbols, 128 bits in 16 encoded bytes, 40 decoded bytes"
+        );
+        assert_eq!(
+            format!("{packet:?}"),
+            "Packet { len: 136, symbol_count: 12, symbols: {'-': 6, '0': 11, '1': 3, '2': 1, \
+             '3': 2, '4': 1, '5': 6, '6': 2, '7': 2, '8': 3, '9': 1, '|': 2}, bitstream_len: 128, \
+             encoded_bytes_len: 16, decoded_bytes_len: 40, .. }"
+        );
+        let owned = packet.to_owned().unwrap();
+        assert_eq!(format!("{owned}"), format!("{packet}"));
+        assert_eq!(format!("{owned:?}"), format!("{packet:?}"));
+    }
+
+    #[test]
+    fn builder_round_trips() {
+        let packet = Packet::new(&TEST_BYTES);
+        assert_eq!(PacketBuilder::from_packet(&packet).build(), TEST_BYTES);
+
+        let content = PacketBuilder::new()
+            .symbols(EXPECTED_SYMBOL_FREQUENCIES)
+            .bitstream_len(128)
+            .message(&TEST_BYTES[120..])
+            .build();
+        assert_eq!(content, TEST_BYTES);
+
+        for case in ALL_CASES {
+            let content = case.request();
+            let packet = Packet::new(&content);
+            assert_eq!(PacketBuilder::from_packet(&packet).build(), content);
+        }
+    }
+
+    #[test]
+    fn builder_recomputes_len() {
+        let packet = Packet::new(&TEST_BYTES);
+        let mut builder = PacketBuilder::from_packet(&packet);
+        builder
+            .remove_symbol(b'9')
+            .decoded_bytes_len(39)
+            .truncate_message(10);
+        let content = builder.build();
+        assert_eq!(content.len(), builder.written_len());
+
+        let modified = Packet::new(&content);
+        assert_eq!(modified.len, 136 - 8 - 6);
+        assert_eq!(modified.symbol_count, 11);
+        assert!(!modified.symbol_frequencies().any(|(s, _)| s == b'9'));
+        assert_eq!(modified.bitstream_len, 80);
+        assert_eq!(modified.encoded_bytes_len, 10);
+        assert_eq!(modified.decoded_bytes_len, 39);
+        assert_eq!(modified.encoded_message, &TEST_BYTES[120..130]);
+
+        // Explicit fields are written even when inconsistent.
+        let content = builder.len(1).symbol_count(2).build();
+        let modified = Packet::new(&content);
+        assert_eq!((modified.len, modified.symbol_count), (1, 2));
+    }
+
+    #[test]
+    fn try_new_matches_new() {
+        let packet = Packet::try_new(&TEST_BYTES).unwrap();
+        let expected = Packet::new(&TEST_BYTES);
+        assert_eq!(format!("{packet:?}"), format!("{expected:?}"));
+        assert_eq!(packet.encoded_message, expected.encoded_message);
+    }
+
+    #[test]
+    fn try_new_reports_errors() {
+        let packet = Packet::new(&TEST_BYTES);
+        let check = |content: &[u8], expected: PacketError| {
+            assert_eq!(Packet::try_new(content).unwrap_err(), expected);
+        };
+
+        check(
+            &TEST_BYTES[..6],
+            PacketError::Truncated {
+                field: "len_content",
+                offset: 0,
+                needed: 8,
+                available: 6,
+            },
+        );
+        check(
+            &TEST_BYTES[..130],
+            PacketError::Truncated {
+                field: "message",
+                offset: 120,
+                needed: 16,
+                available: 10,
+            },
+        );
+
+        let mut content = TEST_BYTES;
+        content[12 + 3 * 8 + 4] = b'a';
+        check(
+            &content,
+            PacketError::InvalidSymbol {
+                entry: 3,
+                offset: 40,
+                symbol: b'a',
+            },
+        );
+
+        let mut content = TEST_BYTES;
+        content[12 + 5 * 8 + 4] = b'-';
+        check(
+            &content,
+            PacketError::DuplicateSymbol {
+                entry: 5,
+                offset: 56,
+                symbol: b'-',
+            },
+        );
+
+        let mut content = TEST_BYTES;
+        content[12 + 11 * 8 + 7] = 1;
+        check(
+            &content,
+            PacketError::NonZeroPadding {
+                entry: 11,
+                offset: 107,
+                byte: 1,
+            },
+        );
+
+        let content = PacketBuilder::from_packet(&packet).len(135).build();
+        check(
+            &content,
+            PacketError::LengthMismatch {
+                len: 135,
+                content_len: 136,
+            },
+        );
+
+        let content = PacketBuilder::from_packet(&packet)
+            .bitstream_len(129)
+            .build();
+        check(
+            &content,
+            PacketError::BitstreamLength {
+                bitstream_len: 129,
+                encoded_bytes_len: 16,
+            },
+        );
+
+        let mut builder = PacketBuilder::new();
+        builder.symbol(b'0', 1);
+        check(
+            &builder.build(),
+            PacketError::SymbolCount { symbol_count: 1 },
+        );
+    }
+
+    #[test]
+    fn header_only_needs_header_bytes() {
+        let header_len = PacketHeader::len_for(12);
+        let header = PacketHeader::try_new(&TEST_BYTES[..header_len]).unwrap();
+        assert_eq!(header.header_len(), header_len);
+        assert_eq!(header.encoded_bytes_len, 16);
+        assert_eq!(header.decoded_bytes_len, 40);
+        assert!(header.check_bitstream_len().is_ok());
+        assert!(matches!(
+            PacketHeader::try_new(&TEST_BYTES[..header_len - 1]),
+            Err(PacketError::Truncated {
+                field: "len_decoded_data",
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips() {
+        let packet = Packet::new(&TEST_BYTES);
+        let json = serde_json::to_string(&packet).unwrap();
+        let owned: OwnedPacket = serde_json::from_str(&json).unwrap();
+        assert_eq!(owned.as_bytes(), TEST_BYTES);
+        assert_eq!(serde_json::to_string(&owned).unwrap(), json);
+
+        let mut mismatched = Packet::new(&TEST_BYTES);
+        mismatched.symbol_count = 11;
+        let json = serde_json::to_string(&mismatched).unwrap();
+        let err = serde_json::from_str::<OwnedPacket>(&json).unwrap_err();
+        assert!(err.to_string().starts_with("truncated"), "{err}");
     }
 }
 
 // MARK: Benches
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = 10_000)]
 mod benches {
     use super::*;
@@ -0,0 +1,151 @@
+// The phases of decoding a packet, run in a loop by `profile_bin`.
+//
+// Each decoder crate exposes a `profile` function that passes its private tree
+// and table builders to `run`, so no decoder code needs copying to profile it.
+
+use crate::packet::Packet;
+use core::hint::black_box;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// `Packet::new` only.
+    Parse,
+    /// Building the Huffman tree from a parsed packet.
+    Tree,
+    /// Building the lookup tables from a built tree, a no-op for tree decoders.
+    Table,
+    /// Decoding the message with a built tree and tables.
+    Decode,
+    /// All of the above, the same work as `decode_packet`.
+    Packet,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::Parse,
+        Phase::Tree,
+        Phase::Table,
+        Phase::Decode,
+        Phase::Packet,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Parse => "parse",
+            Phase::Tree => "tree",
+            Phase::Table => "table",
+            Phase::Decode => "decode",
+            Phase::Packet => "packet",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Phase::ALL.into_iter().find(|phase| phase.name() == name)
+    }
+}
+
+impl core::fmt::Display for Phase {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Runs `phase` `iterations` times, building whatever it needs once up front.
+///
+/// Trees are built in place by `build_tree` into a `new_tree`, as the pointer
+/// based trees point into themselves and can't be moved once built.
+pub fn run<Tree, Table, Output>(
+    content: &[u8],
+    phase: Phase,
+    iterations: u64,
+    new_tree: impl Fn() -> Tree,
+    build_tree: impl Fn(&Packet, &mut Tree),
+    table: impl Fn(&Tree) -> Table,
+    decode: impl Fn(&Packet, &Tree, &Table) -> Output,
+) {
+    let packet = &Packet::new(content);
+    let mut tree = new_tree();
+    build_tree(packet, &mut tree);
+    match phase {
+        Phase::Parse => {
+            for _ in 0..iterations {
+                black_box(Packet::new(black_box(content)));
+            }
+        }
+        Phase::Tree => {
+            for _ in 0..iterations {
+                let mut tree = new_tree();
+                build_tree(black_box(packet), &mut tree);
+                black_box(&tree);
+            }
+        }
+        Phase::Table => {
+            for _ in 0..iterations {
+                black_box(table(black_box(&tree)));
+            }
+        }
+        Phase::Decode => {
+            let table = &table(&tree);
+            for _ in 0..iterations {
+                black_box(decode(black_box(packet), &tree, table));
+            }
+        }
+        Phase::Packet => {
+            for _ in 0..iterations {
+                let packet = &Packet::new(black_box(content));
+                let mut tree = new_tree();
+                build_tree(packet, &mut tree);
+                let table = &table(&tree);
+                black_box(decode(packet, &tree, table));
+            }
+        }
+    }
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_cases::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn parses_phase_names() {
+        for phase in Phase::ALL {
+            assert_eq!(Phase::from_name(phase.name()), Some(phase));
+        }
+        assert_eq!(Phase::from_name("tables"), None);
+    }
+
+    #[test]
+    fn runs_only_the_phase() {
+        let counts = [Cell::new(0), Cell::new(0), Cell::new(0)];
+        for (phase, expected) in [
+            (Phase::Parse, [1, 0, 0]),
+            (Phase::Tree, [4, 0, 0]),
+            (Phase::Table, [1, 3, 0]),
+            (Phase::Decode, [1, 1, 3]),
+            (Phase::Packet, [4, 3, 3]),
+        ] {
+            counts.iter().for_each(|count| count.set(0));
+            run(
+                &TEST_BYTES,
+                phase,
+                3,
+                || 0,
+                |packet, tree| {
+                    *tree = packet.symbol_count;
+                    counts[0].set(counts[0].get() + 1)
+                },
+                |tree| {
+                    assert_eq!(*tree, 12);
+                    counts[1].set(counts[1].get() + 1)
+                },
+                |_, _, _| counts[2].set(counts[2].get() + 1),
+            );
+            assert_eq!(counts.each_ref().map(Cell::get), expected, "{phase}");
+        }
+    }
+}
@@ -0,0 +1,213 @@
+// Records of a decoded GetWorldMarketList message.
+//
+// Records are delimited by '|' and their fields by '-', e.g.
+// "53801-0-55556-41900|53802-0-16807-70000|".
+
+/// One record of a decoded message.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MarketRecord {
+    pub item: u32,
+    pub count: u64,
+    pub price: u64,
+    pub cumulative_count: u64,
+}
+
+pub const FIELD_NAMES: [&str; 4] = ["item", "count", "price", "cumulative_count"];
+
+/// Why a record couldn't be parsed, `offset` is the byte offset of the record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordError {
+    /// The record doesn't have exactly four fields.
+    FieldCount {
+        record: usize,
+        offset: usize,
+        fields: usize,
+    },
+    /// A field is empty, not a number or too large.
+    InvalidField {
+        record: usize,
+        offset: usize,
+        field: &'static str,
+    },
+}
+
+impl core::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            RecordError::FieldCount {
+                record,
+                offset,
+                fields,
+            } => write!(
+                f,
+                "record {record} at offset {offset} has {fields} fields, expected 4"
+            ),
+            RecordError::InvalidField {
+                record,
+                offset,
+                field,
+            } => write!(
+                f,
+                "record {record} at offset {offset} has an invalid {field}"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for RecordError {}
+
+impl MarketRecord {
+    /// Parses a single record without its trailing '|'.
+    pub fn parse(record: &str) -> Result<Self, RecordError> {
+        parse_record(record, 0, 0)
+    }
+}
+
+/// Parses a record without its trailing '|', with errors reporting it as
+/// record `index` at byte `offset` of its message.
+pub fn parse_record(
+    record: &str,
+    index: usize,
+    offset: usize,
+) -> Result<MarketRecord, RecordError> {
+    let mut fields = [0u64; 4];
+    let mut count = 0;
+    for field in record.split('-') {
+        if count < 4 {
+            let invalid = RecordError::InvalidField {
+                record: index,
+                offset,
+                field: FIELD_NAMES[count],
+            };
+            // `u64::from_str` accepts a leading '+' which is never sent.
+            if field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid);
+            }
+            fields[count] = field.parse().map_err(|_| invalid)?;
+        }
+        count += 1;
+    }
+    if count != 4 {
+        return Err(RecordError::FieldCount {
+            record: index,
+            offset,
+            fields: count,
+        });
+    }
+    Ok(MarketRecord {
+        item: u32::try_from(fields[0]).map_err(|_| RecordError::InvalidField {
+            record: index,
+            offset,
+            field: FIELD_NAMES[0],
+        })?,
+        count: fields[1],
+        price: fields[2],
+        cumulative_count: fields[3],
+    })
+}
+
+/// Iterates the records of a decoded message.
+pub fn parse_records(message: &str) -> Records<'_> {
+    Records {
+        message,
+        offset: 0,
+        index: 0,
+    }
+}
+
+pub struct Records<'a> {
+    message: &'a str,
+    offset: usize,
+    index: usize,
+}
+
+impl Iterator for Records<'_> {
+    type Item = Result<MarketRecord, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.message[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+        let (record, len) = match rest.find('|') {
+            Some(end) => (&rest[..end], end + 1),
+            None => (rest, rest.len()),
+        };
+        let result = parse_record(record, self.index, self.offset);
+        self.offset += len;
+        self.index += 1;
+        Some(result)
+    }
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_cases::*;
+
+    #[test]
+    fn parses_records() {
+        let records: Vec<_> = parse_records(EXPECTED_MESSAGE).collect();
+        assert_eq!(
+            records,
+            [
+                Ok(MarketRecord {
+                    item: 53801,
+                    count: 0,
+                    price: 55556,
+                    cumulative_count: 41900,
+                }),
+                Ok(MarketRecord {
+                    item: 53802,
+                    count: 0,
+                    price: 16807,
+                    cumulative_count: 70000,
+                }),
+            ]
+        );
+        assert_eq!(parse_records("").count(), 0);
+    }
+
+    #[test]
+    fn reports_record_errors() {
+        let mut records = parse_records("1-2-3-4|1-2-3|1-x-3-4|1-2-3-4-5|5000000000-0-0-0");
+        assert!(records.next().unwrap().is_ok());
+        assert_eq!(
+            records.next().unwrap(),
+            Err(RecordError::FieldCount {
+                record: 1,
+                offset: 8,
+                fields: 3
+            })
+        );
+        assert_eq!(
+            records.next().unwrap(),
+            Err(RecordError::InvalidField {
+                record: 2,
+                offset: 14,
+                field: "count"
+            })
+        );
+        assert_eq!(
+            records.next().unwrap(),
+            Err(RecordError::FieldCount {
+                record: 3,
+                offset: 22,
+                fields: 5
+            })
+        );
+        assert_eq!(
+            records.next().unwrap(),
+            Err(RecordError::InvalidField {
+                record: 4,
+                offset: 32,
+                field: "item"
+            })
+        );
+        assert_eq!(records.next(), None);
+    }
+}
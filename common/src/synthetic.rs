@@ -0,0 +1,236 @@
+// Synthetic GetWorldMarketList messages of arbitrary size, encoded into packets
+// the decoders accept, for benches beyond the captured sizes.
+
+use crate::min_heap::{Classic, MinHeapNode, MinHeapless, TieBreak};
+use crate::packet::{ALLOWED_SYMBOLS, MAX_SYMBOLS};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// Decoded lengths from 64 B to 1 MB, doubling.
+pub const SWEEP_DECODED_LENS: [usize; 15] = {
+    let mut lens = [0; 15];
+    let mut i = 0;
+    while i < lens.len() {
+        lens[i] = 64 << i;
+        i += 1;
+    }
+    lens
+};
+
+/// The splitmix64 generator, small and good enough for test data.
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A value in `0..bound`, `bound` must not be zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// A value spread evenly over the decimal magnitudes of `1..10^max_digits`.
+    pub fn log_uniform(&mut self, max_digits: u32) -> u64 {
+        let digits = 1 + self.below(max_digits as u64) as u32;
+        let low = 10u64.pow(digits - 1);
+        low + self.below(9 * low)
+    }
+}
+
+/// A `|` terminated `item-count-price-cumulative_count` message of at most
+/// `decoded_len` bytes, and at least one record.
+///
+/// Like the captures, item ids ascend in small steps, most counts are zero and
+/// the last field has three significant digits.
+pub fn market_message(seed: u64, decoded_len: usize) -> String {
+    let mut rng = SplitMix64::new(seed);
+    let mut message = String::with_capacity(decoded_len);
+    let mut record = String::new();
+    let mut item = 1_000 + rng.below(60_000);
+    loop {
+        record.clear();
+        let count = match rng.below(10) {
+            0..6 => 0,
+            _ => rng.log_uniform(6),
+        };
+        let price = rng.log_uniform(10);
+        let cumulative_count = (100 + rng.below(900)) * 10u64.pow(rng.below(8) as u32);
+        let _ = write!(record, "{item}-{count}-{price}-{cumulative_count}|");
+        if !message.is_empty() && message.len() + record.len() > decoded_len {
+            return message;
+        }
+        message.push_str(&record);
+        item += 1 + rng.below(4);
+    }
+}
+
+/// `market_message` encoded with `encode_packet`.
+pub fn market_packet(seed: u64, decoded_len: usize) -> Vec<u8> {
+    encode_packet(market_message(seed, decoded_len).as_bytes())
+}
+
+/// Huffman encodes `message` into a packet, building the tree the same way as
+/// the decoders so ties resolve identically.
+///
+/// Panics if `message` holds fewer than two distinct symbols or symbols
+/// outside `ALLOWED_SYMBOLS`.
+pub fn encode_packet(message: &[u8]) -> Vec<u8> {
+    encode_packet_with::<Classic>(message)
+}
+
+/// `encode_packet` breaking frequency ties with `P`, to test decoders for
+/// encoders that build their trees differently.
+pub fn encode_packet_with<P: TieBreak>(message: &[u8]) -> Vec<u8> {
+    let mut frequencies = [0u32; MAX_SYMBOLS];
+    for symbol in message {
+        let i = ALLOWED_SYMBOLS
+            .iter()
+            .position(|allowed| allowed == symbol)
+            .expect("symbol outside ALLOWED_SYMBOLS");
+        frequencies[i] += 1;
+    }
+    let symbols: Vec<(u8, u32)> = ALLOWED_SYMBOLS
+        .iter()
+        .zip(frequencies)
+        .filter(|(_, frequency)| *frequency > 0)
+        .map(|(&symbol, frequency)| (symbol, frequency))
+        .collect();
+    assert!(symbols.len() >= 2, "a tree needs at least two symbols");
+
+    let codes = prefix_codes::<P>(&symbols);
+    let mut encoded = Vec::with_capacity(message.len() / 2);
+    let mut bitstream_len = 0usize;
+    for symbol in message {
+        let (code, code_len) = codes[*symbol as usize];
+        for i in (0..code_len).rev() {
+            if bitstream_len.is_multiple_of(8) {
+                encoded.push(0);
+            }
+            let bit = (code >> i) as u8 & 1;
+            *encoded.last_mut().unwrap() |= bit << (7 - bitstream_len % 8);
+            bitstream_len += 1;
+        }
+    }
+
+    let len = 24 + 8 * symbols.len() + encoded.len();
+    let mut content = Vec::with_capacity(len);
+    content.extend_from_slice(&(len as u64).to_le_bytes());
+    content.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+    for (symbol, frequency) in &symbols {
+        content.extend_from_slice(&frequency.to_le_bytes());
+        content.extend_from_slice(&[*symbol, 0, 0, 0]);
+    }
+    content.extend_from_slice(&(bitstream_len as u32).to_le_bytes());
+    content.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+    content.extend_from_slice(&(message.len() as u32).to_le_bytes());
+    content.extend_from_slice(&encoded);
+    content
+}
+
+// `(code, code_len)` by symbol byte, the first popped node is the left child.
+fn prefix_codes<P: TieBreak>(symbols: &[(u8, u32)]) -> Vec<(u32, u32)> {
+    let mut children = Vec::new();
+    let mut heap = MinHeapless::<TreeNode, P>::new();
+    for (i, (_, frequency)) in symbols.iter().enumerate() {
+        heap.push(TreeNode {
+            frequency: *frequency,
+            node: Node::Leaf(i),
+        });
+    }
+    while heap.len() > 1 {
+        let left = heap.pop();
+        let right = heap.pop();
+        children.push((left.node, right.node));
+        heap.push(TreeNode {
+            frequency: left.frequency + right.frequency,
+            node: Node::Parent(children.len() - 1),
+        });
+    }
+
+    let mut codes = vec![(0, 0); 256];
+    let mut stack = vec![(heap.pop().node, 0u32, 0u32)];
+    while let Some((node, code, code_len)) = stack.pop() {
+        match node {
+            Node::Leaf(i) => codes[symbols[i].0 as usize] = (code, code_len),
+            Node::Parent(i) => {
+                let (left, right) = children[i];
+                stack.push((left, code << 1, code_len + 1));
+                stack.push((right, code << 1 | 1, code_len + 1));
+            }
+        }
+    }
+    codes
+}
+
+#[derive(Clone, Copy)]
+enum Node {
+    Leaf(usize),
+    Parent(usize),
+}
+
+struct TreeNode {
+    frequency: u32,
+    node: Node,
+}
+
+impl MinHeapNode for TreeNode {
+    fn frequency(&self) -> u32 {
+        self.frequency
+    }
+}
+
+impl PartialEq for TreeNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.frequency == other.frequency
+    }
+}
+
+impl PartialOrd for TreeNode {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        self.frequency.partial_cmp(&other.frequency)
+    }
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::Packet;
+    use crate::records::parse_records;
+    use crate::test_cases::*;
+
+    #[test]
+    fn encodes_test_bytes() {
+        assert_eq!(encode_packet(EXPECTED_MESSAGE.as_bytes()), TEST_BYTES);
+    }
+
+    #[test]
+    fn generates_valid_messages() {
+        for (seed, decoded_len) in SWEEP_DECODED_LENS.into_iter().enumerate() {
+            let message = market_message(seed as u64, decoded_len);
+            assert!(message.len() <= decoded_len);
+            assert!(message.len() + 40 > decoded_len);
+            assert!(parse_records(&message).all(|record| record.is_ok()));
+
+            let content = market_packet(seed as u64, decoded_len);
+            let packet = Packet::try_new(&content).unwrap();
+            assert_eq!(packet.decoded_bytes_len as usize, message.len());
+        }
+        assert_eq!(market_message(7, 1_000), market_message(7, 1_000));
+        assert_ne!(market_message(7, 1_000), market_message(8, 1_000));
+    }
+}
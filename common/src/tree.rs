@@ -0,0 +1,452 @@
+// Checks that a symbol table builds a Huffman tree the decoders can handle.
+//
+// The decoders build their trees with `parent_frequency`, panicking on an
+// overflowing `u32` sum, and assume the codes are short enough for their lookup
+// tables. `validate_tree` builds the same tree shape and reports both instead. `validate_packet` adds the checks of
+// the header against the tree that every validating decoder runs, and
+// `packet_tree` returns the tree it checked for decoders walking it bit by bit.
+
+use crate::min_heap::{Classic, MinHeapChecked, MinHeapNode, TieBreak};
+use crate::packet::{PacketHeader, MAX_SYMBOLS};
+
+/// The longest code of a 12 symbol tree, decoders walking the tree handle any.
+pub const MAX_DEPTH: u32 = MAX_SYMBOLS as u32 - 1;
+
+/// The longest code the 8 bit multi-symbol lookup tables of the `table_index`,
+/// `table_ptr` and `table_unsafe_ptr` decoders can decode.
+pub const MAX_TABLE_DEPTH: u32 = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TreeError {
+    /// The table has fewer than 2 or more than `MAX_SYMBOLS` symbols.
+    SymbolCount { symbol_count: usize },
+    /// Merging nodes with the `left` and `right` frequencies overflows a `u32`.
+    FrequencyOverflow { left: u32, right: u32 },
+    /// The code of `symbol` is `depth` bits, longer than `max_depth`.
+    Depth {
+        symbol: u8,
+        depth: u32,
+        max_depth: u32,
+    },
+    /// The symbol frequencies don't add up to `len_decoded_data`.
+    FrequencySum {
+        frequency_sum: u32,
+        decoded_len: u32,
+    },
+    /// `len_encoded_data` is 0, there's no bitstream to decode.
+    EmptyBitstream,
+    /// `len_bitstream` isn't the `code_bits` the codes of the symbol
+    /// frequencies take.
+    BitstreamMismatch { code_bits: u64, bitstream_len: u32 },
+}
+
+impl core::fmt::Display for TreeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            TreeError::SymbolCount { symbol_count } => write!(
+                f,
+                "the symbol table has {symbol_count} symbols, 2 to {MAX_SYMBOLS} are supported"
+            ),
+            TreeError::FrequencyOverflow { left, right } => {
+                write!(f, "merging frequencies {left} and {right} overflows a u32")
+            }
+            TreeError::Depth {
+                symbol,
+                depth,
+                max_depth,
+            } => write!(
+                f,
+                "symbol {:?} has a {depth} bit code, the decoder handles up to {max_depth}",
+                symbol as char
+            ),
+            TreeError::FrequencySum {
+                frequency_sum,
+                decoded_len,
+            } => write!(
+                f,
+                "symbol frequencies sum to {frequency_sum} but len_decoded_data is {decoded_len}"
+            ),
+            TreeError::EmptyBitstream => write!(f, "len_encoded_data is 0"),
+            TreeError::BitstreamMismatch {
+                code_bits,
+                bitstream_len,
+            } => write!(
+                f,
+                "the symbol codes take {code_bits} bits but len_bitstream is {bitstream_len}"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for TreeError {}
+
+/// The frequency of the parent of nodes with the `left` and `right`
+/// frequencies, the one sum the decoders' tree builders make.
+///
+/// Panics if it overflows a `u32`, which `validate_tree` reports as
+/// `FrequencyOverflow` instead, rather than wrapping into a different tree.
+#[inline(always)]
+pub fn parent_frequency(left: u32, right: u32) -> u32 {
+    match left.checked_add(right) {
+        Some(frequency) => frequency,
+        None => panic!("merging frequencies {left} and {right} overflows a u32"),
+    }
+}
+
+/// Builds the Huffman tree of `symbol_frequencies` like the decoders do and
+/// returns its depth, the length of the longest code.
+///
+/// Fails if there are fewer than 2 or more than `MAX_SYMBOLS` symbols, a
+/// parent frequency overflows, the frequencies don't sum to `decoded_len` or a
+/// code is longer than `max_depth`.
+pub fn validate_tree(
+    symbol_frequencies: impl IntoIterator<Item = (u8, u32)>,
+    decoded_len: u32,
+    max_depth: u32,
+) -> Result<u32, TreeError> {
+    validate_tree_with::<Classic>(symbol_frequencies, decoded_len, max_depth)
+}
+
+/// `validate_tree` for decoders breaking frequency ties with `P`.
+pub fn validate_tree_with<P: TieBreak>(
+    symbol_frequencies: impl IntoIterator<Item = (u8, u32)>,
+    decoded_len: u32,
+    max_depth: u32,
+) -> Result<u32, TreeError> {
+    build_tree::<P>(symbol_frequencies, decoded_len, max_depth).map(|tree| tree.depth())
+}
+
+/// Checks the tree of `header` like `validate_tree`, then that the bitstream
+/// matches it: the message isn't empty and `len_bitstream` is the length of
+/// the codes of `len_decoded_data` symbols with the table's frequencies.
+///
+/// Every decoder taking untrusted packets runs this after `Packet::try_new`.
+/// It doesn't decode the bitstream, the unsafe kernels bound their writes in
+/// case it decodes to a different number of symbols.
+pub fn validate_packet(header: &PacketHeader, max_depth: u32) -> Result<u32, TreeError> {
+    validate_packet_with::<Classic>(header, max_depth)
+}
+
+/// `validate_packet` for decoders breaking frequency ties with `P`.
+pub fn validate_packet_with<P: TieBreak>(
+    header: &PacketHeader,
+    max_depth: u32,
+) -> Result<u32, TreeError> {
+    packet_tree_with::<P>(header, max_depth).map(|tree| tree.depth())
+}
+
+/// Validates `header` like `validate_packet` and returns its tree.
+pub fn packet_tree(header: &PacketHeader, max_depth: u32) -> Result<HuffmanTree, TreeError> {
+    packet_tree_with::<Classic>(header, max_depth)
+}
+
+/// `packet_tree` for decoders breaking frequency ties with `P`.
+pub fn packet_tree_with<P: TieBreak>(
+    header: &PacketHeader,
+    max_depth: u32,
+) -> Result<HuffmanTree, TreeError> {
+    let tree = build_tree::<P>(
+        header.symbol_frequencies(),
+        header.decoded_bytes_len,
+        max_depth,
+    )?;
+    if header.encoded_bytes_len == 0 {
+        return Err(TreeError::EmptyBitstream);
+    }
+    if tree.code_bits != header.bitstream_len as u64 {
+        return Err(TreeError::BitstreamMismatch {
+            code_bits: tree.code_bits,
+            bitstream_len: header.bitstream_len,
+        });
+    }
+    Ok(tree)
+}
+
+/// A Huffman tree built like the decoders build theirs, walked from `root`
+/// with `child`.
+#[derive(Clone, Debug)]
+pub struct HuffmanTree {
+    // The children of inner nodes and the symbols of leaves.
+    children: [(u8, u8); 2 * MAX_SYMBOLS - 1],
+    symbols: [Option<u8>; 2 * MAX_SYMBOLS - 1],
+    len: usize,
+    depth: u32,
+    // The sum of the frequency times the code length of every symbol.
+    code_bits: u64,
+}
+
+impl HuffmanTree {
+    /// The length of the longest code.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn root(&self) -> u8 {
+        self.len as u8 - 1
+    }
+
+    /// The node reached from the inner node `node` by `bit`, 0 going left, and
+    /// its symbol when it's a leaf.
+    pub fn child(&self, node: u8, bit: u8) -> (u8, Option<u8>) {
+        let (left, right) = self.children[node as usize];
+        let child = if bit == 0 { left } else { right };
+        (child, self.symbols[child as usize])
+    }
+}
+
+fn build_tree<P: TieBreak>(
+    symbol_frequencies: impl IntoIterator<Item = (u8, u32)>,
+    decoded_len: u32,
+    max_depth: u32,
+) -> Result<HuffmanTree, TreeError> {
+    // Nodes in creation order, so parents come after their children.
+    let mut nodes = [Node::default(); 2 * MAX_SYMBOLS - 1];
+    let mut len = 0;
+    let mut heap = MinHeapChecked::<HeapNode, P>::new();
+    let mut symbol_frequencies = symbol_frequencies.into_iter();
+    for (symbol, frequency) in symbol_frequencies.by_ref() {
+        if len == MAX_SYMBOLS {
+            let symbol_count = len + 1 + symbol_frequencies.count();
+            return Err(TreeError::SymbolCount { symbol_count });
+        }
+        nodes[len].symbol = Some(symbol);
+        nodes[len].frequency = frequency;
+        heap.push(HeapNode::new(frequency, len));
+        len += 1;
+    }
+    if len < 2 {
+        return Err(TreeError::SymbolCount { symbol_count: len });
+    }
+
+    while heap.len() > 1 {
+        let (left, right) = (heap.pop(), heap.pop());
+        let frequency =
+            left.frequency
+                .checked_add(right.frequency)
+                .ok_or(TreeError::FrequencyOverflow {
+                    left: left.frequency,
+                    right: right.frequency,
+                })?;
+        nodes[len].children = Some((left.index, right.index));
+        heap.push(HeapNode::new(frequency, len));
+        len += 1;
+    }
+
+    let root = heap.pop();
+    if root.frequency != decoded_len {
+        return Err(TreeError::FrequencySum {
+            frequency_sum: root.frequency,
+            decoded_len,
+        });
+    }
+
+    // Merging always gives a full tree, every symbol gets a code.
+    let mut depth = 0;
+    let mut code_bits = 0;
+    for i in (0..len).rev() {
+        let node = nodes[i];
+        match node.children {
+            Some((left, right)) => {
+                nodes[left as usize].depth = node.depth + 1;
+                nodes[right as usize].depth = node.depth + 1;
+            }
+            None if node.depth > max_depth => {
+                return Err(TreeError::Depth {
+                    symbol: node.symbol.unwrap_or_default(),
+                    depth: node.depth,
+                    max_depth,
+                })
+            }
+            None => {
+                depth = depth.max(node.depth);
+                code_bits += node.frequency as u64 * node.depth as u64;
+            }
+        }
+    }
+    Ok(HuffmanTree {
+        children: nodes.map(|node| node.children.unwrap_or_default()),
+        symbols: nodes.map(|node| node.symbol),
+        len,
+        depth,
+        code_bits,
+    })
+}
+
+#[derive(Clone, Copy, Default)]
+struct Node {
+    symbol: Option<u8>,
+    frequency: u32,
+    children: Option<(u8, u8)>,
+    depth: u32,
+}
+
+// Compared by frequency only, so ties pop in the same order as the decoders.
+struct HeapNode {
+    frequency: u32,
+    index: u8,
+}
+
+impl HeapNode {
+    fn new(frequency: u32, index: usize) -> Self {
+        HeapNode {
+            frequency,
+            index: index as u8,
+        }
+    }
+}
+
+impl MinHeapNode for HeapNode {
+    fn frequency(&self) -> u32 {
+        self.frequency
+    }
+}
+
+impl PartialEq for HeapNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.frequency == other.frequency
+    }
+}
+
+impl PartialOrd for HeapNode {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        self.frequency.partial_cmp(&other.frequency)
+    }
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{Packet, ALLOWED_SYMBOLS};
+    use crate::test_cases::*;
+
+    fn validate(frequencies: &[(u8, u32)], max_depth: u32) -> Result<u32, TreeError> {
+        let decoded_len = frequencies.iter().map(|&(_, frequency)| frequency).sum();
+        validate_tree(frequencies.iter().copied(), decoded_len, max_depth)
+    }
+
+    #[test]
+    fn validates_test_packet() {
+        let packet = Packet::new(&TEST_BYTES);
+        let depth = validate_tree(
+            packet.symbol_frequencies(),
+            packet.decoded_bytes_len,
+            MAX_TABLE_DEPTH,
+        );
+        assert!(depth.is_ok_and(|depth| depth <= MAX_TABLE_DEPTH));
+    }
+
+    #[test]
+    fn walks_packet_tree() {
+        let tree = packet_tree(&Packet::new(&TEST_BYTES).header(), MAX_DEPTH).unwrap();
+        for (symbol, code) in EXPECTED_PREFIXES {
+            let mut node = tree.root();
+            let mut leaf = None;
+            for bit in code.bytes() {
+                assert_eq!(leaf, None, "{symbol} {code}");
+                (node, leaf) = tree.child(node, bit - b'0');
+            }
+            assert_eq!(leaf, symbol.bytes().next(), "{code}");
+        }
+        assert_eq!(tree.depth(), 6);
+    }
+
+    #[test]
+    fn rejects_mismatched_bitstreams() {
+        let header = Packet::new(&TEST_BYTES).header();
+        let code_bits = header.bitstream_len as u64;
+        let mismatched = PacketHeader {
+            bitstream_len: header.bitstream_len - 1,
+            ..header
+        };
+        assert_eq!(
+            validate_packet(&mismatched, MAX_DEPTH),
+            Err(TreeError::BitstreamMismatch {
+                code_bits,
+                bitstream_len: header.bitstream_len - 1
+            })
+        );
+        let empty = PacketHeader {
+            encoded_bytes_len: 0,
+            ..header
+        };
+        assert_eq!(
+            validate_packet(&empty, MAX_DEPTH),
+            Err(TreeError::EmptyBitstream)
+        );
+    }
+
+    #[test]
+    fn rejects_overflowing_frequencies() {
+        let frequencies = [(b'0', u32::MAX), (b'1', 1), (b'2', 1)];
+        assert_eq!(
+            validate_tree(frequencies, 0, MAX_DEPTH),
+            Err(TreeError::FrequencyOverflow {
+                left: 2,
+                right: u32::MAX
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "merging frequencies 2 and 4294967295 overflows a u32")]
+    fn parent_frequency_panics_on_overflow() {
+        parent_frequency(2, u32::MAX);
+    }
+
+    #[test]
+    fn rejects_symbol_counts() {
+        let symbols = ALLOWED_SYMBOLS.map(|symbol| (symbol, 1));
+        assert_eq!(
+            validate_tree([], 0, MAX_DEPTH),
+            Err(TreeError::SymbolCount { symbol_count: 0 })
+        );
+        assert_eq!(
+            validate_tree(symbols[..1].iter().copied(), 1, MAX_DEPTH),
+            Err(TreeError::SymbolCount { symbol_count: 1 })
+        );
+        let repeated = symbols.iter().chain(&symbols[..3]).copied();
+        assert_eq!(
+            validate_tree(repeated, 15, MAX_DEPTH),
+            Err(TreeError::SymbolCount { symbol_count: 15 })
+        );
+        assert!(validate_tree(symbols, 12, MAX_DEPTH).is_ok());
+    }
+
+    #[test]
+    fn rejects_frequency_sum_mismatch() {
+        let frequencies = [(b'0', 3), (b'1', 4)];
+        assert_eq!(
+            validate_tree(frequencies, 8, MAX_DEPTH),
+            Err(TreeError::FrequencySum {
+                frequency_sum: 7,
+                decoded_len: 8
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_deep_codes() {
+        // Fibonacci frequencies give a 12 symbol tree with an 11 bit code, the
+        // first code found that is too long is the 9 bit one.
+        let mut fibonacci = [(0, 1); MAX_SYMBOLS];
+        for i in 0..MAX_SYMBOLS {
+            fibonacci[i].0 = ALLOWED_SYMBOLS[i];
+            if i >= 2 {
+                fibonacci[i].1 = fibonacci[i - 1].1 + fibonacci[i - 2].1;
+            }
+        }
+        assert_eq!(validate(&fibonacci, MAX_DEPTH), Ok(MAX_DEPTH));
+        assert_eq!(
+            validate(&fibonacci, MAX_TABLE_DEPTH),
+            Err(TreeError::Depth {
+                symbol: b'2',
+                depth: 9,
+                max_depth: MAX_TABLE_DEPTH
+            })
+        );
+        assert_eq!(validate(&fibonacci[..2], MAX_TABLE_DEPTH), Ok(1));
+    }
+}
@@ -1,13 +1,33 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::Packet;
+use common::profile::Phase;
+use common::tree::parent_frequency;
 
 const MAX_TREE_LEN: usize = 23;
 
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    decode_message(packet, &tree)
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    time(Phase::Decode, || decode_message(packet, &tree))
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree,
+        |_| (),
+        |packet, tree, _| decode_message(packet, tree),
+    );
 }
 
 fn decode_message(packet: &Packet, tree: &[TreeNode; MAX_TREE_LEN]) -> String {
@@ -53,7 +73,7 @@ fn decode_message(packet: &Packet, tree: &[TreeNode; MAX_TREE_LEN]) -> String {
     }
 
     let slice = &decoded[..];
-    std::str::from_utf8(slice).unwrap().to_owned()
+    core::str::from_utf8(slice).unwrap().to_owned()
 }
 
 #[inline(always)]
@@ -86,7 +106,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
         process_heap_node(&left, tree, tree_index);
 
         // Add a parent node to the heap for ordering
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
         let parent = HeapNode::new_parent(parent_frequency, tree_index as u8);
         heap.push(parent);
     }
@@ -98,8 +118,8 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
     process_heap_node(&left, tree, tree_index);
 }
 
-fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
+fn symbols_heap(packet: &Packet) -> MinHeapChecked<HeapNode> {
+    let mut heap = MinHeapChecked::<HeapNode>::new();
     let bytes = &packet.symbol_frequency_bytes;
     for i in 0..packet.symbol_count {
         let pos = (i as usize) * 8;
@@ -124,12 +144,12 @@ impl MinHeapNode for HeapNode {
 }
 
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -174,6 +194,7 @@ mod tests {
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = common::test_cases::BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
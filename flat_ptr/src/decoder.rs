@@ -1,13 +1,32 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::Packet;
+use common::profile::Phase;
 
 const MAX_TREE_LEN: usize = 23;
 
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    decode_message(packet, &tree)
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    time(Phase::Decode, || decode_message(packet, &tree))
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree,
+        |_| (),
+        |packet, tree, _| decode_message(packet, tree),
+    );
 }
 
 fn decode_message(packet: &Packet, tree: &[TreeNode; MAX_TREE_LEN]) -> String {
@@ -47,7 +66,7 @@ fn decode_message(packet: &Packet, tree: &[TreeNode; MAX_TREE_LEN]) -> String {
     }
 
     let slice = &decoded[..];
-    std::str::from_utf8(slice).unwrap().to_owned()
+    core::str::from_utf8(slice).unwrap().to_owned()
 }
 
 #[inline(always)]
@@ -92,7 +111,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
         process_heap_node(&left, tree, tree_index);
 
         // Add a parent node to the heap for ordering
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
         let parent = HeapNode::new_parent(parent_frequency, tree_index as u8);
         heap.push(parent);
     }
@@ -131,12 +150,12 @@ impl MinHeapNode for HeapNode {
 }
 
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -168,8 +187,8 @@ struct TreeNode {
 impl Default for TreeNode {
     fn default() -> Self {
         Self {
-            left_ptr: std::ptr::null(),
-            right_ptr: std::ptr::null(),
+            left_ptr: core::ptr::null(),
+            right_ptr: core::ptr::null(),
             symbol: None,
         }
     }
@@ -191,7 +210,10 @@ mod tests {
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+use common::tree::parent_frequency;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -1,2 +1,6 @@
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+extern crate alloc;
+
 pub(crate) mod decoder;
-pub use decoder::decode_packet;
+pub use decoder::{decode_packet, profile};
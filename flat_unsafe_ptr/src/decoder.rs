@@ -1,23 +1,253 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use common::metrics::time;
 use common::min_heap::*;
-use common::packet::Packet;
+use common::packet::{Packet, PacketError};
+use common::profile::Phase;
+use common::tree::{parent_frequency, validate_packet_with, TreeError, MAX_DEPTH};
 
 const MAX_TREE_LEN: usize = 23;
 
 pub fn decode_packet(content: &[u8]) -> String {
+    decode_packet_with::<Classic>(content)
+}
+
+/// `decode_packet` for packets whose encoder breaks frequency ties with `P`.
+pub fn decode_packet_with<P: TieBreak>(content: &[u8]) -> String {
     let packet = &Packet::new(content);
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    unsafe { decode_message(packet, &tree) }
+    time(Phase::Tree, || huffman_tree::<P>(packet, &mut tree));
+    time(Phase::Decode, || unsafe { decode_message(packet, &tree) })
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree::<Classic>,
+        |_| (),
+        |packet, tree, _| unsafe { decode_message(packet, tree) },
+    );
+}
+
+/// Why `decode_many_small` couldn't decode a packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    Packet(PacketError),
+    Tree(TreeError),
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::Packet(err) => write!(f, "invalid packet: {err}"),
+            DecodeError::Tree(err) => write!(f, "invalid symbol table: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+impl From<PacketError> for DecodeError {
+    fn from(err: PacketError) -> Self {
+        DecodeError::Packet(err)
+    }
+}
+
+impl From<TreeError> for DecodeError {
+    fn from(err: TreeError) -> Self {
+        DecodeError::Tree(err)
+    }
+}
+
+/// Decodes small packets `LANES` at a time, returning the messages in order.
+///
+/// A lone packet decode is latency bound on stepping through its tree, so the
+/// packets of a group step through their trees in lock-step to overlap them.
+/// Packets are checked with `Packet::try_new` and `validate_packet`, the writes
+/// of a bitstream with more symbols than `len_decoded_data` are cut short at it.
+pub fn decode_many_small(contents: &[&[u8]]) -> Vec<Result<String, DecodeError>> {
+    decode_many_small_with::<Classic, 4>(contents)
+}
+
+/// `decode_many_small` for packets whose encoder breaks frequency ties with `P`,
+/// decoding `LANES` packets at a time.
+pub fn decode_many_small_with<P: TieBreak, const LANES: usize>(
+    contents: &[&[u8]],
+) -> Vec<Result<String, DecodeError>> {
+    let mut decoded = Vec::with_capacity(contents.len());
+    let mut packets = Vec::new();
+    for (i, content) in contents.iter().enumerate() {
+        // Validation also bounds `len_decoded_data` by `len_bitstream`, each
+        // symbol taking at least a bit, before any output is allocated.
+        let packet = Packet::try_new(content).map_err(DecodeError::from);
+        let packet = packet.and_then(|packet| {
+            validate_packet_with::<P>(&packet.header(), MAX_DEPTH)?;
+            Ok(packet)
+        });
+        decoded.push(packet.map(|packet| {
+            packets.push((i, packet));
+            String::new()
+        }));
+    }
+
+    // Lanes step together until the shortest message of their group ends, so
+    // group packets of similar lengths.
+    let mut order: Vec<usize> = (0..packets.len()).collect();
+    order.sort_unstable_by_key(|&i| packets[i].1.encoded_bytes_len);
+
+    let mut trees = [[TreeNode::default(); MAX_TREE_LEN]; LANES];
+    let mut lane_nodes = [[[0u8; 4]; MAX_TREE_LEN]; LANES];
+    for group in order.chunks(LANES) {
+        for (lane, (tree, &i)) in trees.iter_mut().zip(group).enumerate() {
+            // `huffman_tree` expects a default tree, internal nodes keep their symbol.
+            *tree = [TreeNode::default(); MAX_TREE_LEN];
+            time(Phase::Tree, || {
+                huffman_tree::<P>(&packets[i].1, tree);
+                flatten_tree(tree, lane, &mut lane_nodes[lane]);
+            });
+        }
+        if let Ok(group) = <&[usize; LANES]>::try_from(group) {
+            let lanes = group.map(|i| &packets[i].1);
+            let messages = time(Phase::Decode, || unsafe {
+                decode_lanes(lanes, &trees, &lane_nodes)
+            });
+            for (message, &i) in messages.into_iter().zip(group) {
+                decoded[packets[i].0] = Ok(message);
+            }
+        } else {
+            for (tree, &i) in trees.iter().zip(group) {
+                let (index, packet) = &packets[i];
+                decoded[*index] = Ok(time(Phase::Decode, || unsafe {
+                    decode_message(packet, tree)
+                }));
+            }
+        }
+    }
+    decoded
+}
+
+/// Writes `tree` as the `[left, right, symbol, 0]` nodes of `lane`, indexed
+/// across the lanes' nodes so one base pointer serves every lane. The children of the
+/// leaves are the root's, stepping past a leaf continues from the root.
+fn flatten_tree(
+    tree: &[TreeNode; MAX_TREE_LEN],
+    lane: usize,
+    lane_nodes: &mut [[u8; 4]; MAX_TREE_LEN],
+) {
+    let offset = lane * MAX_TREE_LEN;
+    let index =
+        |ptr: *const TreeNode| (offset + unsafe { ptr.offset_from(tree.as_ptr()) } as usize) as u8;
+    let root_children = [index(tree[0].left_ptr), index(tree[0].right_ptr)];
+    for (node, lane_node) in tree.iter().zip(lane_nodes) {
+        *lane_node = match node.symbol {
+            Some(symbol) => [root_children[0], root_children[1], symbol, 0],
+            None if !node.left_ptr.is_null() => [index(node.left_ptr), index(node.right_ptr), 0, 0],
+            None => [0; 4],
+        };
+    }
+}
+
+/// `packets` must pass `Packet::try_new`, so their symbols aren't 0, and
+/// `validate_packet`, and `trees` and `lane_nodes` be built from them.
+unsafe fn decode_lanes<const LANES: usize>(
+    packets: [&Packet; LANES],
+    trees: &[[TreeNode; MAX_TREE_LEN]; LANES],
+    lane_nodes: &[[[u8; 4]; MAX_TREE_LEN]; LANES],
+) -> [String; LANES] {
+    // The lanes' bytes are packed into a `u64` and node indices are `u8`s.
+    const { assert!(LANES > 0 && LANES <= 8) };
+
+    // Every lane has at least `shared_len` bytes before its last one.
+    let shared_len = packets
+        .iter()
+        .map(|packet| packet.encoded_message.len().saturating_sub(1))
+        .min()
+        .unwrap();
+
+    // Each step writes a symbol and moves past it only at a leaf, so one byte
+    // of slop covers the branchless writes of a message decoding as the header
+    // says and 8 per byte covers those of a bitstream with more symbols.
+    let mut decoded: [Vec<u8>; LANES] = packets.map(|packet| {
+        Vec::with_capacity((packet.decoded_bytes_len as usize + 1).max(8 * shared_len))
+    });
+    let mut write_ptrs = decoded.each_mut().map(|decoded| decoded.as_mut_ptr());
+    let mut nodes: [u8; LANES] = core::array::from_fn(|lane| (lane * MAX_TREE_LEN) as u8);
+    let lane_nodes = lane_nodes.as_ptr().cast::<u8>();
+    for i in 0..shared_len {
+        let mut bits = 0u64;
+        for (lane, packet) in packets.iter().enumerate() {
+            bits |= (*packet.encoded_message.get_unchecked(i) as u64) << (8 * lane);
+        }
+        for _ in 0..8 {
+            for lane in 0..LANES {
+                let direction = (bits >> (8 * lane + 7)) as usize & 1;
+                let node = lane_nodes.add(4 * nodes[lane] as usize);
+                nodes[lane] = *node.add(direction);
+                let symbol = *lane_nodes.add(4 * nodes[lane] as usize + 2);
+                *write_ptrs[lane] = symbol;
+                write_ptrs[lane] = write_ptrs[lane].add((symbol != 0) as usize);
+            }
+            // A lane's bits shifted into the next lane are never read.
+            bits <<= 1;
+        }
+    }
+
+    let mut lane = 0;
+    decoded.map(|decoded| {
+        let tree = &trees[lane];
+        let node = &tree[nodes[lane] as usize - lane * MAX_TREE_LEN];
+        // `decode_from` steps past leaves from the root.
+        let node = if node.symbol.is_some() {
+            &tree[0]
+        } else {
+            node
+        };
+        let write_index = write_ptrs[lane].offset_from(decoded.as_ptr()) as usize;
+        let message = decode_from(
+            packets[lane],
+            &tree[0],
+            node,
+            shared_len,
+            decoded,
+            write_index,
+        );
+        lane += 1;
+        message
+    })
 }
 
 unsafe fn decode_message(packet: &Packet, tree: &[TreeNode; MAX_TREE_LEN]) -> String {
-    let mut decoded: Vec<u8> = Vec::with_capacity(packet.decoded_bytes_len as usize);
-    let mut write_index = 0;
+    let decoded = Vec::with_capacity(packet.decoded_bytes_len as usize);
     let root = unsafe { tree.get_unchecked(0) };
-    let mut node = root;
+    decode_from(packet, root, root, 0, decoded, 0)
+}
+
+/// Decodes `packet` from byte `start`, with `write_index` symbols decoded and
+/// `node` being where the previous byte ended.
+///
+/// `decoded` must have room for `decoded_bytes_len` symbols and `write_index`
+/// must not be past its capacity.
+#[inline(always)]
+unsafe fn decode_from<'a>(
+    packet: &Packet,
+    root: &'a TreeNode,
+    mut node: &'a TreeNode,
+    start: usize,
+    mut decoded: Vec<u8>,
+    mut write_index: usize,
+) -> String {
+    let decoded_len = packet.decoded_bytes_len as usize;
+    let read_limit = packet.encoded_message.len().saturating_sub(1);
 
-    let read_limit = packet.encoded_bytes_len as usize - 1;
-    for i in 0..read_limit {
+    // A byte decodes at most 8 symbols, so only the bytes after the last one
+    // with room for 8 more need checking for the end of the message.
+    let mut i = start;
+    while i < read_limit && write_index + 8 <= decoded_len {
         let mut bits = *packet.encoded_message.get_unchecked(i);
         for _ in 0..8 {
             let direction = (bits >> 7) as usize;
@@ -29,26 +259,31 @@ unsafe fn decode_message(packet: &Packet, tree: &[TreeNode; MAX_TREE_LEN]) -> St
                 node = root;
             }
         }
+        i += 1;
     }
 
-    let mut bits = *packet.encoded_message.get_unchecked(read_limit);
-    for _ in 0..8 {
-        let direction = (bits >> 7) as usize;
-        bits <<= 1;
-        node = step(direction, node);
-        if let Some(symbol) = node.symbol {
-            *decoded.as_mut_ptr().add(write_index) = symbol;
-            write_index += 1;
-            if write_index == packet.decoded_bytes_len as usize {
-                break;
+    // Stop at `decoded_bytes_len` whether the last byte's padding or a
+    // bitstream with more symbols than the header reaches it.
+    'bytes: for &byte in &packet.encoded_message[i..] {
+        let mut bits = byte;
+        for _ in 0..8 {
+            if write_index >= decoded_len {
+                break 'bytes;
+            }
+            let direction = (bits >> 7) as usize;
+            bits <<= 1;
+            node = step(direction, node);
+            if let Some(symbol) = node.symbol {
+                *decoded.as_mut_ptr().add(write_index) = symbol;
+                write_index += 1;
+                node = root;
             }
-            node = root;
         }
     }
 
-    decoded.set_len(write_index);
-    let slice = std::slice::from_raw_parts(decoded.as_ptr(), decoded.len());
-    std::str::from_utf8_unchecked(slice).to_owned()
+    decoded.set_len(write_index.min(decoded_len));
+    let slice = core::slice::from_raw_parts(decoded.as_ptr(), decoded.len());
+    core::str::from_utf8_unchecked(slice).to_owned()
 }
 
 #[allow(clippy::unnecessary_cast)]
@@ -76,13 +311,13 @@ fn process_heap_node(node: &HeapNode, tree: &mut [TreeNode; MAX_TREE_LEN], index
     }
 }
 
-fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
+fn huffman_tree<P: TieBreak>(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
     // Set the root node.
     tree[0].symbol = None;
     tree[0].left_ptr = &tree[1] as *const TreeNode;
     tree[0].right_ptr = &tree[2] as *const TreeNode;
 
-    let mut heap = unsafe { symbols_heap(packet) };
+    let mut heap = unsafe { symbols_heap::<P>(packet) };
     let mut tree_index = 2 * packet.symbol_count as usize - 1;
 
     // Successively move two smallest nodes from heap to tree
@@ -96,7 +331,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
         process_heap_node(&left, tree, tree_index);
 
         // Add a parent node to the heap for ordering
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
         let parent = HeapNode::new_parent(parent_frequency, tree_index as u8);
         heap.push(parent);
     }
@@ -110,8 +345,8 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
 }
 
 #[inline(never)]
-unsafe fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
+unsafe fn symbols_heap<P: TieBreak>(packet: &Packet) -> P::Queue<HeapNode> {
+    let mut heap = P::Queue::<HeapNode>::default();
     let ptr = packet.symbol_frequency_bytes.as_ptr();
     for i in 0..packet.symbol_count as usize {
         let freq_ptr = ptr.add(i * 8) as *const (u32, u8);
@@ -121,7 +356,7 @@ unsafe fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
     heap
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 struct HeapNode {
     tree_index: u8,
     symbol: Option<u8>,
@@ -135,12 +370,12 @@ impl MinHeapNode for HeapNode {
 }
 
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -172,8 +407,8 @@ struct TreeNode {
 impl Default for TreeNode {
     fn default() -> Self {
         Self {
-            left_ptr: std::ptr::null(),
-            right_ptr: std::ptr::null(),
+            left_ptr: core::ptr::null(),
+            right_ptr: core::ptr::null(),
             symbol: None,
         }
     }
@@ -184,6 +419,8 @@ impl Default for TreeNode {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use common::corpus::samples;
     use common::test_cases::*;
 
     #[test]
@@ -191,11 +428,80 @@ mod tests {
         let decoded_message = super::decode_packet(&TEST_BYTES);
         assert_eq!(decoded_message, EXPECTED_MESSAGE);
     }
+
+    #[test]
+    fn decodes_many_small_in_order() {
+        let requests: Vec<_> = samples()
+            .iter()
+            .filter(|sample| sample.decoded_len <= 1_000)
+            .map(|sample| sample.request())
+            .collect();
+        let contents: Vec<&[u8]> = requests.iter().map(Vec::as_slice).collect();
+        let expected: Vec<_> = contents
+            .iter()
+            .map(|content| Ok(decode_packet(content)))
+            .collect();
+        assert_eq!(decode_many_small_with::<Classic, 1>(&contents), expected);
+        assert_eq!(decode_many_small_with::<Classic, 4>(&contents), expected);
+        assert_eq!(decode_many_small_with::<Classic, 8>(&contents), expected);
+        // Groups of mixed lengths, and one shorter than `LANES`.
+        assert_eq!(decode_many_small(&contents[..7]), expected[..7]);
+        assert!(decode_many_small(&[]).is_empty());
+    }
+
+    #[test]
+    fn decodes_many_small_malformed_packets() {
+        use common::packet::PacketBuilder;
+
+        // Invalid packets are reported in place. A bitstream of zero bytes
+        // repeats the code of `1`, all zeros in the test packet, past
+        // `len_decoded_data` and an empty one has nothing to decode.
+        let packet = Packet::new(&TEST_BYTES);
+        let zeros = PacketBuilder::from_packet(&packet)
+            .message(&[0; 16])
+            .build();
+        let empty = PacketBuilder::from_packet(&packet).message(&[]).build();
+        // Passes `Packet::try_new` but overflows the parent frequencies.
+        let overflow = PacketBuilder::from_packet(&packet)
+            .clear_symbols()
+            .symbols([(b'0', u32::MAX), (b'3', u32::MAX), (b'1', 1), (b'2', 1)])
+            .build();
+        let huge = PacketBuilder::from_packet(&packet)
+            .decoded_bytes_len(3_000_000_000)
+            .build();
+        let contents: [&[u8]; 6] = [&zeros, &TEST_BYTES[..100], &empty, &zeros, &overflow, &huge];
+        for decoded in [
+            decode_many_small_with::<Classic, 1>(&contents),
+            decode_many_small_with::<Classic, 4>(&contents),
+        ] {
+            let zeros_message = "1".repeat(packet.decoded_bytes_len as usize);
+            assert_eq!(decoded[0].as_ref(), Ok(&zeros_message));
+            assert!(matches!(
+                decoded[1],
+                Err(DecodeError::Packet(PacketError::Truncated { .. }))
+            ));
+            assert_eq!(
+                decoded[2],
+                Err(DecodeError::Tree(TreeError::EmptyBitstream))
+            );
+            assert_eq!(decoded[3].as_ref(), Ok(&zeros_message));
+            assert!(matches!(
+                decoded[4],
+                Err(DecodeError::Tree(TreeError::FrequencyOverflow { .. }))
+            ));
+            assert!(matches!(
+                decoded[5],
+                Err(DecodeError::Tree(TreeError::FrequencySum { .. }))
+            ));
+        }
+    }
 }
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -204,13 +510,17 @@ mod bench {
     use divan::counter::BytesCount;
     use divan::{black_box, Bencher};
 
-    #[divan::bench(sample_count = 100_000, args = [ALL_CASES[0], ALL_CASES[5]])]
-    fn gen_tree(bencher: Bencher, case: &Case) {
+    #[divan::bench(
+        sample_count = 100_000,
+        types = [Classic, InsertionOrder, ReverseInsertionOrder],
+        args = [ALL_CASES[0], ALL_CASES[5]]
+    )]
+    fn gen_tree<P: TieBreak>(bencher: Bencher, case: &Case) {
         let content = case.request();
         let packet = &Packet::new(&content);
         bencher.bench_local(move || {
             let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-            huffman_tree(packet, &mut tree);
+            huffman_tree::<P>(packet, &mut tree);
             black_box(tree);
         });
     }
@@ -220,7 +530,7 @@ mod bench {
         let content = case.request();
         let packet = &Packet::new(&content);
         let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-        huffman_tree(packet, &mut tree);
+        huffman_tree::<Classic>(packet, &mut tree);
         bencher
             .counter(BytesCount::from(packet.decoded_bytes_len))
             .bench_local(move || {
@@ -1,2 +1,9 @@
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+extern crate alloc;
+
 pub(crate) mod decoder;
-pub use decoder::decode_packet;
+pub use decoder::{
+    decode_many_small, decode_many_small_with, decode_packet, decode_packet_with, profile,
+    DecodeError,
+};
@@ -1,18 +1,126 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 use bitter::{BigEndianReader, BitReader};
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::{Packet, MAX_SYMBOLS};
+use common::profile::Phase;
 
 const MAX_TREE_LEN: usize = 23;
 
 pub fn decode_packet(content: &[u8]) -> String {
+    decode_packet_with_layout::<Padded>(content)
+}
+
+/// `decode_packet` with the state tables in layout `L`.
+pub fn decode_packet_with_layout<L: Layout>(content: &[u8]) -> String {
     let packet = &Packet::new(content);
+    let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    let tables = time(Phase::Table, || L::state_tables(&tree));
+    time(Phase::Decode, || L::decode_message(packet, &tables))
+}
+
+/// The memory layout of the state tables.
+pub trait Layout: layout::Sealed {}
+
+/// Each state's 256 entries of the next state and up to 8 symbols, `[u8; 9]`
+/// each, in a table aligned to its own 4 KiB page.
+pub struct Padded;
+
+/// Each state's 256 `u16` entries of the next state and an index into a pool of
+/// the distinct 8 byte symbol blocks, which all states share.
+pub struct Compact;
+
+impl Layout for Padded {}
+impl Layout for Compact {}
+
+mod layout {
+    use super::*;
+
+    pub trait Sealed {
+        type Tables;
+        fn state_tables(tree: &[TreeNode; MAX_TREE_LEN]) -> Self::Tables;
+        fn decode_message(packet: &Packet, tables: &Self::Tables) -> String;
+    }
+
+    impl Sealed for Padded {
+        type Tables = StateTables;
+        fn state_tables(tree: &[TreeNode; MAX_TREE_LEN]) -> StateTables {
+            state_tables(tree)
+        }
+        fn decode_message(packet: &Packet, tables: &StateTables) -> String {
+            super::decode_message(packet, &mut &*tables)
+        }
+    }
+
+    impl Sealed for Compact {
+        type Tables = CompactTables;
+        fn state_tables(tree: &[TreeNode; MAX_TREE_LEN]) -> CompactTables {
+            compact_tables(tree)
+        }
+        fn decode_message(packet: &Packet, tables: &CompactTables) -> String {
+            super::decode_message(packet, &mut &*tables)
+        }
+    }
+}
+
+/// `decode_packet` building each state's table when decoding first enters the
+/// state, so the tables of states a message never visits are never built.
+pub fn decode_packet_lazy(content: &[u8]) -> String {
+    decode_packet_lazy_stats(content).0
+}
+
+/// How many of a packet's states `decode_packet_lazy` built a table for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LazyStats {
+    pub states: usize,
+    pub built: usize,
+}
+
+/// `decode_packet_lazy` also returning its `LazyStats`.
+pub fn decode_packet_lazy_stats(content: &[u8]) -> (String, LazyStats) {
+    let packet = &Packet::new(content);
+    let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    let mut tables = time(Phase::Table, || LazyStateTables::new(&tree));
+    let decoded = time(Phase::Decode, || decode_message(packet, &mut tables));
+    let stats = LazyStats {
+        states: tables.state_count,
+        built: tables.built_count,
+    };
+    (decoded, stats)
+}
+
+/// The `[next_state, symbols..]` entries of each state table of `packet`,
+/// for checking other table builders against.
+pub fn state_table_entries(packet: &Packet) -> Vec<[[u8; 9]; 256]> {
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
     huffman_tree(packet, &mut tree);
-    let table = &state_tables(&tree);
-    decode_message(packet, table)
+    let (_, state_count) = table_indices(&tree);
+    let tables = state_tables(&tree);
+    tables.tables[..state_count]
+        .iter()
+        .map(|table| table.symbols)
+        .collect()
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree,
+        state_tables,
+        |packet, _, table| decode_message(packet, &mut &*table),
+    );
 }
 
-fn decode_message(packet: &Packet, table: &StateTables) -> String {
+fn decode_message(packet: &Packet, table: &mut impl Tables) -> String {
     // Add slop space instead of checking write_index against decoded_len.
     let mut decoded: Vec<u8> = vec![0; packet.decoded_bytes_len as usize + 8];
     let mut index = 0usize;
@@ -37,29 +145,27 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
 
     // Truncate decoded slop.
     let slice = &decoded[..packet.decoded_bytes_len as usize];
-    std::str::from_utf8(slice).unwrap().to_owned()
+    core::str::from_utf8(slice).unwrap().to_owned()
 }
 
 #[inline(always)]
 fn step(
     bit_reader: &mut BigEndianReader,
-    table: &StateTables,
+    table: &mut impl Tables,
     write_index: &mut usize,
     decoded: &mut [u8],
     state: usize,
 ) -> usize {
     let index = bit_reader.peek(8) as usize;
-    let symbols: &[u8; 9] = &table.tables[state].symbols[index];
-    let state = symbols[0] as usize;
-    copy_symbols(symbols, write_index, decoded);
+    let (state, symbol_block) = table.entry(state, index);
+    copy_symbols(symbol_block, write_index, decoded);
     bit_reader.consume(8);
     state
 }
 
 #[inline(always)]
-fn copy_symbols(symbols: &[u8; 9], write_index: &mut usize, decoded: &mut [u8]) {
-    decoded[*write_index..*write_index + 8].copy_from_slice(&symbols[1..9]);
-    let symbol_block = u64::from_le_bytes(symbols[1..9].try_into().unwrap());
+fn copy_symbols(symbol_block: u64, write_index: &mut usize, decoded: &mut [u8]) {
+    decoded[*write_index..*write_index + 8].copy_from_slice(&symbol_block.to_le_bytes());
     let len = 8 - (symbol_block.leading_zeros() / 8) as usize;
     *write_index += len;
 }
@@ -98,7 +204,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
         process_heap_node(&left, tree, tree_index);
 
         // Add a parent node to the heap for ordering
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
         let parent = HeapNode::new_parent(parent_frequency, tree_index as u8);
         heap.push(parent);
     }
@@ -111,8 +217,8 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
     process_heap_node(&left, tree, tree_index);
 }
 
-fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
+fn symbols_heap(packet: &Packet) -> MinHeapChecked<HeapNode> {
+    let mut heap = MinHeapChecked::<HeapNode>::new();
     let bytes = &packet.symbol_frequency_bytes;
     for chunk in bytes.chunks_exact(8) {
         let frequency = u32::from_le_bytes(chunk[..4].try_into().unwrap());
@@ -135,192 +241,206 @@ impl Default for SymbolTable {
     }
 }
 
-struct StateTables {
+pub struct StateTables {
     tables: [SymbolTable; MAX_SYMBOLS],
 }
 
 fn state_tables(tree: &[TreeNode; MAX_TREE_LEN]) -> StateTables {
-    let (table_indices, child_states) = child_states(tree);
+    let (table_indices, state_count) = table_indices(tree);
+    let nibbles = nibble_tables(tree, &table_indices);
 
     let mut state_tables = StateTables {
         tables: [SymbolTable::default(); MAX_SYMBOLS],
     };
+    for (state, table) in state_tables.tables[..state_count].iter_mut().enumerate() {
+        compose_table(&nibbles, state, table);
+    }
+    state_tables
+}
 
-    // Find the last 'state 3' node and populate a symbol table to copy entries from later.
-    let reference_index = child_states.iter().rposition(|&x| x == 3).unwrap();
-    let reference = initialize_reference_table(&tree[reference_index], tree, &table_indices);
-    state_tables.tables[table_indices[reference_index] as usize] = reference;
+/// The next state and symbol block of decoding byte `index` from `state`.
+pub trait Tables {
+    fn entry(&mut self, state: usize, index: usize) -> (usize, u64);
+}
 
-    for i in 0..MAX_TREE_LEN {
-        let table_index = table_indices[i];
-        if table_index == MAX_TREE_LEN as u8 || i == reference_index {
-            continue;
+impl SymbolTable {
+    #[inline(always)]
+    fn entry(&self, index: usize) -> (usize, u64) {
+        let symbols = &self.symbols[index];
+        let symbol_block = u64::from_le_bytes(symbols[1..9].try_into().unwrap());
+        (symbols[0] as usize, symbol_block)
+    }
+}
+
+impl Tables for &StateTables {
+    #[inline(always)]
+    fn entry(&mut self, state: usize, index: usize) -> (usize, u64) {
+        self.tables[state].entry(index)
+    }
+}
+
+/// State tables built on first use from the nibble tables.
+struct LazyStateTables {
+    nibbles: [[NibbleEntry; 16]; MAX_SYMBOLS],
+    state_count: usize,
+    built: [bool; MAX_SYMBOLS],
+    built_count: usize,
+    tables: StateTables,
+}
+
+impl LazyStateTables {
+    fn new(tree: &[TreeNode; MAX_TREE_LEN]) -> Self {
+        let (table_indices, state_count) = table_indices(tree);
+        LazyStateTables {
+            nibbles: nibble_tables(tree, &table_indices),
+            state_count,
+            built: [false; MAX_SYMBOLS],
+            built_count: 0,
+            tables: StateTables {
+                tables: [SymbolTable::default(); MAX_SYMBOLS],
+            },
         }
+    }
 
-        // Copy and modify entries from the reference table when we can.
-        let start_node = &tree[i];
-        let table_fn = match child_states[i] {
-            1 => copy_lower_gen_upper,
-            2 => gen_lower_copy_upper,
-            3 => copy_full_range,
-            _ => gen_full_range,
-        };
-        state_tables.tables[table_index as usize] =
-            table_fn(start_node, tree, &table_indices, &reference);
+    #[cold]
+    fn build(&mut self, state: usize) {
+        compose_table(&self.nibbles, state, &mut self.tables.tables[state]);
+        self.built[state] = true;
+        self.built_count += 1;
     }
-    state_tables
 }
 
-fn child_states(tree: &[TreeNode; MAX_TREE_LEN]) -> ([u8; MAX_TREE_LEN], [u8; MAX_TREE_LEN]) {
-    let mut table_indices = [MAX_TREE_LEN as u8; MAX_TREE_LEN];
-    let mut child_states = [MAX_TREE_LEN as u8; MAX_TREE_LEN];
-    let mut internal_count = 0;
-    tree.iter().enumerate().for_each(|(i, node)| {
-        if node.symbol.is_none() && node.index.is_some() {
-            table_indices[i] = internal_count;
-            internal_count += 1;
-        };
-        let left_state = tree[node.left_index as usize].symbol.is_some() as u8;
-        let right_state = tree[node.right_index as usize].symbol.is_some() as u8;
-        let child_state = left_state + 2 * right_state;
-        child_states[i] = child_state;
-    });
-    (table_indices, child_states)
+impl Tables for LazyStateTables {
+    #[inline(always)]
+    fn entry(&mut self, state: usize, index: usize) -> (usize, u64) {
+        if !self.built[state] {
+            self.build(state);
+        }
+        self.tables.tables[state].entry(index)
+    }
 }
 
-fn initialize_reference_table(
-    start_node: &TreeNode,
-    tree: &[TreeNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    for byte in 0u8..=127 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
+/// The `Compact` layout.
+pub struct CompactTables {
+    // The next state in the upper 4 bits and the index into `symbol_blocks` in
+    // the lower 12, there are at most 11 states and 11 * 256 blocks.
+    entries: [[u16; 256]; MAX_SYMBOLS],
+    symbol_blocks: Vec<u64>,
+}
+
+impl Tables for &CompactTables {
+    #[inline(always)]
+    fn entry(&mut self, state: usize, index: usize) -> (usize, u64) {
+        let entry = self.entries[state][index];
+        let symbol_block = self.symbol_blocks[(entry & 0xfff) as usize];
+        ((entry >> 12) as usize, symbol_block)
     }
-    let (first_half, second_half) = table.symbols.split_at_mut(128);
-    second_half.copy_from_slice(first_half);
-    table.symbols[128..=255]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.right_index as usize].symbol.unwrap());
-    table
 }
 
-fn copy_lower_gen_upper(
-    start_node: &TreeNode,
-    tree: &[TreeNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-    reference_table: &SymbolTable,
-) -> SymbolTable {
+fn compact_tables(tree: &[TreeNode; MAX_TREE_LEN]) -> CompactTables {
+    let (table_indices, state_count) = table_indices(tree);
+    let nibbles = nibble_tables(tree, &table_indices);
+
+    let mut tables = CompactTables {
+        entries: [[0; 256]; MAX_SYMBOLS],
+        symbol_blocks: Vec::new(),
+    };
+    // Open addressing from a block's hash to its index + 1, 0 for empty slots.
+    let mut block_indices = [0u16; 4096];
     let mut table = SymbolTable::default();
-    table.symbols[0..=127].copy_from_slice(&reference_table.symbols[0..=127]);
-    table.symbols[0..=127]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.left_index as usize].symbol.unwrap());
-
-    for byte in 128u8..=255 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
+    for state in 0..state_count {
+        compose_table(&nibbles, state, &mut table);
+        for (index, entry) in tables.entries[state].iter_mut().enumerate() {
+            let (next_state, symbol_block) = table.entry(index);
+            let mut slot = (symbol_block.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 52) as usize;
+            let block_index = loop {
+                match block_indices[slot] {
+                    0 => {
+                        tables.symbol_blocks.push(symbol_block);
+                        block_indices[slot] = tables.symbol_blocks.len() as u16;
+                        break tables.symbol_blocks.len() - 1;
+                    }
+                    i if tables.symbol_blocks[i as usize - 1] == symbol_block => {
+                        break i as usize - 1;
+                    }
+                    _ => slot = (slot + 1) % block_indices.len(),
+                }
+            };
+            *entry = (next_state as u16) << 12 | block_index as u16;
+        }
     }
-    table
+    tables
 }
 
-fn gen_lower_copy_upper(
-    start_node: &TreeNode,
-    tree: &[TreeNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-    reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    table.symbols[128..=255].copy_from_slice(&reference_table.symbols[128..=255]);
-    table.symbols[128..=255]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.right_index as usize].symbol.unwrap());
-
-    for byte in 0u8..=127 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
+fn compose_table(
+    nibbles: &[[NibbleEntry; 16]; MAX_SYMBOLS],
+    state: usize,
+    table: &mut SymbolTable,
+) {
+    // A byte decodes its upper nibble from the state, then its lower nibble from
+    // the state the upper nibble ends in.
+    for (upper, high) in nibbles[state].iter().enumerate() {
+        for (lower, low) in nibbles[high.state as usize].iter().enumerate() {
+            let symbols = high.symbols as u64 | (low.symbols as u64) << (8 * high.len);
+            let entry = &mut table.symbols[upper << 4 | lower];
+            entry[0] = low.state;
+            entry[1..9].copy_from_slice(&symbols.to_le_bytes());
+        }
     }
-    table
 }
 
-fn copy_full_range(
-    start_node: &TreeNode,
-    tree: &[TreeNode; MAX_TREE_LEN],
-    _table_indices: &[u8; MAX_TREE_LEN],
-    reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable {
-        symbols: reference_table.symbols,
-    };
-    table.symbols[0..=127]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.left_index as usize].symbol.unwrap());
-    table.symbols[128..=255]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.right_index as usize].symbol.unwrap());
-    table
+/// The symbols decoded from 4 bits of a state and the state they end in.
+#[derive(Clone, Copy, Default)]
+struct NibbleEntry {
+    state: u8,
+    len: u8,
+    // The first symbol in the lowest byte.
+    symbols: u32,
 }
 
-fn gen_full_range(
-    start_node: &TreeNode,
+fn nibble_tables(
     tree: &[TreeNode; MAX_TREE_LEN],
     table_indices: &[u8; MAX_TREE_LEN],
-    _reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    for byte in 0u8..=255 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
+) -> [[NibbleEntry; 16]; MAX_SYMBOLS] {
+    let mut nibbles = [[NibbleEntry::default(); 16]; MAX_SYMBOLS];
+    for (start_node, &table_index) in tree.iter().zip(table_indices) {
+        if table_index == MAX_TREE_LEN as u8 {
+            continue;
+        }
+        for (bits, entry) in nibbles[table_index as usize].iter_mut().enumerate() {
+            let mut node = start_node;
+            for shift in (0..4).rev() {
+                node = match bits >> shift & 1 {
+                    0 => &tree[node.left_index as usize],
+                    _ => &tree[node.right_index as usize],
+                };
+                if let Some(symbol) = node.symbol {
+                    entry.symbols |= (symbol as u32) << (8 * entry.len);
+                    entry.len += 1;
+                }
+            }
+            entry.state = if node.symbol.is_some() {
+                0
+            } else {
+                table_indices[node.index.unwrap()]
+            };
+        }
     }
-    table
+    nibbles
 }
 
-#[inline(always)]
-fn decode_bits<'a>(
-    mut bits: u8,
-    mut node: &'a TreeNode,
-    symbols: &mut [u8; 9],
-    tree: &'a [TreeNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-) {
-    let mut write_index = 1;
-    for _ in 0..=7 {
-        node = match bits >> 7 {
-            0 => &tree[node.left_index as usize],
-            _ => &tree[node.right_index as usize],
+/// The state of each internal node, `MAX_TREE_LEN` for leaves, and the number
+/// of states.
+fn table_indices(tree: &[TreeNode; MAX_TREE_LEN]) -> ([u8; MAX_TREE_LEN], usize) {
+    let mut table_indices = [MAX_TREE_LEN as u8; MAX_TREE_LEN];
+    let mut internal_count = 0;
+    tree.iter().enumerate().for_each(|(i, node)| {
+        if node.symbol.is_none() && node.index.is_some() {
+            table_indices[i] = internal_count;
+            internal_count += 1;
         };
-        if let Some(symbol) = node.symbol {
-            symbols[write_index] = symbol;
-            write_index += 1;
-        }
-        bits <<= 1;
-    }
-    symbols[0] = if node.symbol.is_some() {
-        0
-    } else {
-        table_indices[node.index.unwrap()]
-    };
+    });
+    (table_indices, internal_count as usize)
 }
 
 #[derive(PartialEq, Eq)]
@@ -335,12 +455,12 @@ impl MinHeapNode for HeapNode {
     }
 }
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -362,7 +482,7 @@ impl HeapNode {
 }
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-struct TreeNode {
+pub struct TreeNode {
     left_index: u8,
     right_index: u8,
     symbol: Option<u8>,
@@ -375,6 +495,7 @@ struct TreeNode {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use common::test_cases::*;
 
     #[test]
@@ -382,11 +503,109 @@ mod tests {
         let decoded_message = super::decode_packet(&TEST_BYTES);
         assert_eq!(decoded_message, EXPECTED_MESSAGE);
     }
+
+    #[test]
+    fn lazy_tables_decode_like_eager_tables() {
+        assert_eq!(decode_packet_lazy(&TEST_BYTES), EXPECTED_MESSAGE);
+        // Every state of the 12 symbol trees is entered from 5.5k upwards, the
+        // 40b message skips 3.
+        let built = [11, 11, 11, 11, 11, 8];
+        for (case, built) in ALL_CASES.iter().zip(built) {
+            let content = case.request();
+            let (decoded, stats) = decode_packet_lazy_stats(&content);
+            assert_eq!(decoded, super::decode_packet(&content));
+            assert_eq!(stats, LazyStats { states: 11, built }, "{case}");
+        }
+    }
+
+    #[test]
+    fn compact_tables_match_padded_tables() {
+        assert_eq!(
+            decode_packet_with_layout::<Compact>(&TEST_BYTES),
+            EXPECTED_MESSAGE
+        );
+        for case in ALL_CASES {
+            let content = case.request();
+            let packet = &Packet::new(&content);
+            let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+            huffman_tree(packet, &mut tree);
+            let (padded, compact) = (state_tables(&tree), compact_tables(&tree));
+            for state in 0..packet.symbol_count as usize - 1 {
+                for index in 0..256 {
+                    assert_eq!(
+                        (&padded).entry(state, index),
+                        (&compact).entry(state, index)
+                    );
+                }
+            }
+            assert_eq!(
+                decode_packet_with_layout::<Compact>(&content),
+                super::decode_packet(&content)
+            );
+            // ~13-16KB against the 48KB of the padded tables.
+            let bytes = size_of_val(&compact.entries) + size_of_val(&compact.symbol_blocks[..]);
+            assert!((13_000..=16_500).contains(&bytes), "{case}: {bytes}");
+            assert_eq!(size_of::<StateTables>(), 48 * 1024);
+        }
+    }
+
+    // Decodes a byte from `start_node` by walking the tree bit by bit.
+    fn decode_bits(
+        mut bits: u8,
+        start_node: &TreeNode,
+        tree: &[TreeNode; MAX_TREE_LEN],
+    ) -> [u8; 9] {
+        let (table_indices, _) = table_indices(tree);
+        let mut symbols = [0; 9];
+        let mut write_index = 1;
+        let mut node = start_node;
+        for _ in 0..8 {
+            node = match bits >> 7 {
+                0 => &tree[node.left_index as usize],
+                _ => &tree[node.right_index as usize],
+            };
+            if let Some(symbol) = node.symbol {
+                symbols[write_index] = symbol;
+                write_index += 1;
+            }
+            bits <<= 1;
+        }
+        if node.symbol.is_none() {
+            symbols[0] = table_indices[node.index.unwrap()];
+        }
+        symbols
+    }
+
+    #[test]
+    fn composed_tables_match_tree_walks() {
+        let requests = ALL_CASES.iter().map(|case| case.request());
+        for content in requests.chain([TEST_BYTES.to_vec()]) {
+            let packet = &Packet::new(&content);
+            let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+            huffman_tree(packet, &mut tree);
+            let state_tables = state_tables(&tree);
+            let (table_indices, state_count) = table_indices(&tree);
+            assert_eq!(state_count, packet.symbol_count as usize - 1);
+            for (node, &table_index) in tree.iter().zip(&table_indices) {
+                if table_index == MAX_TREE_LEN as u8 {
+                    continue;
+                }
+                let table = &state_tables.tables[table_index as usize];
+                for byte in 0..=255 {
+                    let walked = decode_bits(byte, node, &tree);
+                    assert_eq!(table.symbols[byte as usize], walked, "byte {byte:08b}");
+                }
+            }
+        }
+    }
 }
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+use common::tree::parent_frequency;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -428,7 +647,36 @@ mod bench {
         bencher
             .counter(BytesCount::from(packet.decoded_bytes_len))
             .bench_local(move || {
-                super::decode_message(black_box(packet), &table);
+                super::decode_message(black_box(packet), &mut &table);
+            });
+    }
+
+    #[divan::bench(
+        sample_count = 100_000,
+        types = [Padded, Compact],
+        args = [ALL_CASES[0], ALL_CASES[5]]
+    )]
+    fn gen_table_layout<L: Layout>(bencher: Bencher, case: &Case) {
+        let content = case.request();
+        let packet = &Packet::new(&content);
+        bencher.bench_local(move || {
+            let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+            huffman_tree(packet, &mut tree);
+            black_box(L::state_tables(&tree));
+        });
+    }
+
+    #[divan::bench(types = [Padded, Compact], args = &ALL_CASES[..4])]
+    fn decode_message_layout<L: Layout>(bencher: Bencher, case: &Case) {
+        let content = case.request();
+        let packet = &Packet::new(&content);
+        let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+        huffman_tree(packet, &mut tree);
+        let tables = L::state_tables(&tree);
+        bencher
+            .counter(BytesCount::from(packet.decoded_bytes_len))
+            .bench_local(move || {
+                L::decode_message(black_box(packet), &tables);
             });
     }
 
@@ -440,6 +688,14 @@ mod bench {
         });
     }
 
+    #[divan::bench(args = ALL_CASES)]
+    fn decode_packet_lazy(bencher: Bencher, case: &Case) {
+        let content = case.request();
+        bencher.bench_local(move || {
+            super::decode_packet_lazy(black_box(&content));
+        });
+    }
+
     #[divan::bench(args = [ALL_CASES[0]])]
     fn decode_message2x(bencher: Bencher, case: &Case) {
         let content = case.request();
@@ -452,7 +708,7 @@ mod bench {
             .counter(BytesCount::from(2 * packet.decoded_bytes_len))
             .bench_local(move || {
                 black_box(super::decode_packet(black_box(&content2)));
-                black_box(super::decode_message(black_box(packet), &table));
+                black_box(super::decode_message(black_box(packet), &mut &table));
             });
     }
 }
@@ -1,2 +1,10 @@
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
 pub(crate) mod decoder;
-pub use decoder::decode_packet;
+pub use decoder::{
+    decode_packet, decode_packet_lazy, decode_packet_lazy_stats, decode_packet_with_layout,
+    profile, state_table_entries, Compact, Layout, LazyStats, Padded,
+};
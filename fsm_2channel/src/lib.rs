@@ -1,2 +1,7 @@
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
 pub(crate) mod decoder;
-pub use decoder::decode_packet;
+pub use decoder::{decode_packet, profile};
@@ -1,15 +1,33 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
 use bitter::{BigEndianReader, BitReader};
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::{Packet, MAX_SYMBOLS};
+use common::profile::Phase;
 
 const MAX_TREE_LEN: usize = 23;
 
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    let table = &state_tables(&tree);
-    decode_message(packet, table)
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    let table = &time(Phase::Table, || state_tables(&tree));
+    time(Phase::Decode, || decode_message(packet, table))
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree,
+        state_tables,
+        |packet, _, table| decode_message(packet, table),
+    );
 }
 
 fn decode_message(packet: &Packet, table: &StateTables) -> String {
@@ -28,7 +46,7 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
     let mut state2 = 0usize;
     let mut state3 = 0usize;
 
-    let (tail, encoded_bytes) = if packet.encoded_bytes_len as usize % 4 != 0 {
+    let (tail, encoded_bytes) = if !(packet.encoded_bytes_len as usize).is_multiple_of(4) {
         let n = (packet.encoded_bytes_len / 4) * 4;
         let (encoded_bytes, tail) = packet.encoded_message.split_at(n as usize);
         (Some(tail), encoded_bytes)
@@ -114,7 +132,7 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
 
     // Truncate decoded slop.
     let slice = &decoded0[..packet.decoded_bytes_len as usize];
-    std::str::from_utf8(slice).unwrap().to_owned()
+    core::str::from_utf8(slice).unwrap().to_owned()
 }
 
 fn step_state(
@@ -157,6 +175,7 @@ fn copy_symbols(symbols: &[u8; 9], write_index: &mut usize, decoded: &mut [u8])
 }
 
 #[inline(always)]
+#[allow(clippy::too_many_arguments)]
 fn converge(
     bytes1: &[u8],
     mut state0: usize,
@@ -230,7 +249,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
         process_heap_node(&left, tree, tree_index);
 
         // Add a parent node to the heap for ordering
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
         let parent = HeapNode::new_parent(parent_frequency, tree_index as u8);
         heap.push(parent);
     }
@@ -243,8 +262,8 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
     process_heap_node(&left, tree, tree_index);
 }
 
-fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
+fn symbols_heap(packet: &Packet) -> MinHeapChecked<HeapNode> {
+    let mut heap = MinHeapChecked::<HeapNode>::new();
     let bytes = &packet.symbol_frequency_bytes;
     for chunk in bytes.chunks_exact(8) {
         let frequency = u32::from_le_bytes(chunk[..4].try_into().unwrap());
@@ -467,12 +486,12 @@ impl MinHeapNode for HeapNode {
     }
 }
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -518,7 +537,10 @@ mod tests {
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+use common::tree::parent_frequency;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -0,0 +1,291 @@
+// Compile time equivalents of `fsm::huffman_tree` and `fsm::state_tables`.
+//
+// Everything here is a `const fn` so the `huffman_decoder!` macro can evaluate
+// the tree and all state tables into a `static`. Trait methods can't be called
+// in const context so the heap compares frequencies directly, but the sift-up
+// and sift-down steps are the same as `common::min_heap::MinHeapless` and the
+// popping order is identical.
+
+use common::packet::{ALLOWED_SYMBOLS, MAX_SYMBOLS};
+
+pub(crate) const MAX_TREE_LEN: usize = 2 * MAX_SYMBOLS - 1;
+
+#[derive(Clone, Copy)]
+#[repr(align(4096))]
+pub struct SymbolTable {
+    pub(crate) symbols: [[u8; 9]; 256],
+}
+
+/// One `SymbolTable` per internal node of the tree, the root is state `0`.
+pub struct StateTables<const STATES: usize> {
+    pub(crate) tables: [SymbolTable; STATES],
+}
+
+#[derive(Clone, Copy)]
+struct HeapNode {
+    tree_index: u8,
+    symbol: Option<u8>,
+    frequency: u32,
+}
+
+#[derive(Clone, Copy)]
+struct TreeNode {
+    left_index: u8,
+    right_index: u8,
+    symbol: Option<u8>,
+}
+
+struct ConstHeap {
+    nodes: [HeapNode; MAX_SYMBOLS],
+    len: usize,
+}
+
+impl ConstHeap {
+    const fn new() -> Self {
+        let empty = HeapNode {
+            tree_index: 0,
+            symbol: None,
+            frequency: 0,
+        };
+        ConstHeap {
+            nodes: [empty; MAX_SYMBOLS],
+            len: 0,
+        }
+    }
+
+    const fn swap(&mut self, a: usize, b: usize) {
+        let tmp = self.nodes[a];
+        self.nodes[a] = self.nodes[b];
+        self.nodes[b] = tmp;
+    }
+
+    const fn push(&mut self, node: HeapNode) {
+        self.nodes[self.len] = node;
+        self.len += 1;
+        let mut child = self.len - 1;
+
+        while child > 0 {
+            let parent = (child - 1) / 2;
+            if self.nodes[child].frequency < self.nodes[parent].frequency {
+                self.swap(child, parent);
+                child = parent;
+            } else {
+                break;
+            }
+        }
+    }
+
+    const fn pop(&mut self) -> HeapNode {
+        // Same as `Vec::swap_remove(0)`.
+        let root = self.nodes[0];
+        self.len -= 1;
+        self.nodes[0] = self.nodes[self.len];
+
+        let mut parent = 0;
+        let mut child = 1;
+        let end = self.len;
+
+        while child < end {
+            let right = child + 1;
+            if right < end && self.nodes[child].frequency > self.nodes[right].frequency {
+                child += 1;
+            }
+
+            if self.nodes[child].frequency < self.nodes[parent].frequency {
+                self.swap(parent, child);
+                parent = child;
+                child = 2 * parent + 1;
+            } else {
+                break;
+            }
+        }
+        root
+    }
+}
+
+/// The symbol table bytes of a packet with these `(symbol, frequency)` entries.
+///
+/// Each entry is a `u32` frequency followed by the symbol as a 4 byte strz.
+pub const fn frequency_bytes<const LEN: usize>(freqs: &[(u8, u32)]) -> [u8; LEN] {
+    assert!(LEN == 8 * freqs.len(), "LEN must be 8 * freqs.len()");
+
+    let mut bytes = [0u8; LEN];
+    let mut i = 0;
+    while i < freqs.len() {
+        let (symbol, frequency) = freqs[i];
+        let frequency = frequency.to_le_bytes();
+        let pos = 8 * i;
+        bytes[pos] = frequency[0];
+        bytes[pos + 1] = frequency[1];
+        bytes[pos + 2] = frequency[2];
+        bytes[pos + 3] = frequency[3];
+        bytes[pos + 4] = symbol;
+        i += 1;
+    }
+    bytes
+}
+
+const fn process_heap_node(node: &HeapNode, tree: &mut [TreeNode; MAX_TREE_LEN], index: usize) {
+    if let Some(symbol) = node.symbol {
+        tree[index].symbol = Some(symbol);
+        tree[index].left_index = 1;
+        tree[index].right_index = 2;
+    } else {
+        tree[index].left_index = node.tree_index;
+        tree[index].right_index = node.tree_index + 1;
+    }
+}
+
+const fn is_allowed(symbol: u8) -> bool {
+    let mut i = 0;
+    while i < ALLOWED_SYMBOLS.len() {
+        if ALLOWED_SYMBOLS[i] == symbol {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn huffman_tree(freqs: &[(u8, u32)]) -> [TreeNode; MAX_TREE_LEN] {
+    assert!(freqs.len() >= 2, "at least two symbols are required");
+    assert!(freqs.len() <= MAX_SYMBOLS, "too many symbols");
+    let mut i = 0;
+    while i < freqs.len() {
+        assert!(
+            is_allowed(freqs[i].0),
+            "symbols must be one of -0123456789|"
+        );
+        i += 1;
+    }
+
+    let mut tree = [TreeNode {
+        left_index: 0,
+        right_index: 0,
+        symbol: None,
+    }; MAX_TREE_LEN];
+
+    // Set the root node.
+    tree[0].left_index = 1;
+    tree[0].right_index = 2;
+
+    let mut heap = ConstHeap::new();
+    let mut i = 0;
+    while i < freqs.len() {
+        let (symbol, frequency) = freqs[i];
+        heap.push(HeapNode {
+            tree_index: 0,
+            symbol: Some(symbol),
+            frequency,
+        });
+        i += 1;
+    }
+
+    let mut tree_index = 2 * freqs.len() - 1;
+
+    // Successively move two smallest nodes from heap to tree
+    while tree_index > 3 {
+        let left = heap.pop();
+        let right = heap.pop();
+
+        tree_index -= 1;
+        process_heap_node(&right, &mut tree, tree_index);
+        tree_index -= 1;
+        process_heap_node(&left, &mut tree, tree_index);
+
+        // Add a parent node to the heap for ordering
+        let frequency = match left.frequency.checked_add(right.frequency) {
+            Some(frequency) => frequency,
+            None => panic!("symbol frequencies overflow u32"),
+        };
+        heap.push(HeapNode {
+            tree_index: tree_index as u8,
+            symbol: None,
+            frequency,
+        });
+    }
+
+    // Move the last two nodes.
+    let left = heap.pop();
+    let right = heap.pop();
+    tree_index -= 1;
+    process_heap_node(&right, &mut tree, tree_index);
+    tree_index -= 1;
+    process_heap_node(&left, &mut tree, tree_index);
+
+    tree
+}
+
+/// Builds the state tables for `freqs`, `STATES` must be `freqs.len() - 1`.
+pub const fn state_tables<const STATES: usize>(freqs: &[(u8, u32)]) -> StateTables<STATES> {
+    assert!(STATES + 1 == freqs.len(), "STATES must be freqs.len() - 1");
+
+    let tree = huffman_tree(freqs);
+    let tree_len = 2 * freqs.len() - 1;
+
+    // Internal nodes are numbered in tree order, which makes the root state 0.
+    let mut table_indices = [0u8; MAX_TREE_LEN];
+    let mut internal_count = 0;
+    let mut i = 0;
+    while i < tree_len {
+        if tree[i].symbol.is_none() {
+            table_indices[i] = internal_count;
+            internal_count += 1;
+        }
+        i += 1;
+    }
+
+    let mut state_tables = StateTables {
+        tables: [SymbolTable {
+            symbols: [[0u8; 9]; 256],
+        }; STATES],
+    };
+
+    let mut i = 0;
+    while i < tree_len {
+        if tree[i].symbol.is_none() {
+            let table = &mut state_tables.tables[table_indices[i] as usize];
+            let mut byte = 0;
+            while byte < 256 {
+                decode_bits(
+                    byte as u8,
+                    i,
+                    &mut table.symbols[byte],
+                    &tree,
+                    &table_indices,
+                );
+                byte += 1;
+            }
+        }
+        i += 1;
+    }
+    state_tables
+}
+
+const fn decode_bits(
+    mut bits: u8,
+    mut node: usize,
+    symbols: &mut [u8; 9],
+    tree: &[TreeNode; MAX_TREE_LEN],
+    table_indices: &[u8; MAX_TREE_LEN],
+) {
+    let mut write_index = 1;
+    let mut i = 0;
+    while i < 8 {
+        node = match bits >> 7 {
+            0 => tree[node].left_index as usize,
+            _ => tree[node].right_index as usize,
+        };
+        if let Some(symbol) = tree[node].symbol {
+            symbols[write_index] = symbol;
+            write_index += 1;
+        }
+        bits <<= 1;
+        i += 1;
+    }
+    symbols[0] = if tree[node].symbol.is_some() {
+        0
+    } else {
+        table_indices[node]
+    };
+}
@@ -0,0 +1,258 @@
+use crate::builder::StateTables;
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use bitter::{BigEndianReader, BitReader};
+use common::packet::Packet;
+
+/// Generates a `decode_packet` with its FSM state tables built at compile time.
+///
+/// `freqs` is the packet's symbol table as `(symbol, frequency)` pairs in the
+/// order they appear in the packet, its symbols being `ALLOWED_SYMBOLS` or the
+/// tables fail to compile. Packets whose symbol table bytes don't match are
+/// decoded by `fsm::decode_packet` instead.
+///
+/// ```
+/// mod small_min {
+///     fsm_static::huffman_decoder!(freqs = [
+///         (b'-', 6), (b'0', 11), (b'1', 3), (b'2', 1), (b'3', 2), (b'4', 2),
+///         (b'5', 5), (b'6', 2), (b'7', 2), (b'8', 3), (b'9', 1), (b'|', 2),
+///     ]);
+/// }
+///
+/// fn decode(content: &[u8]) -> String {
+///     small_min::decode_packet(content)
+/// }
+/// ```
+#[macro_export]
+macro_rules! huffman_decoder {
+    (freqs = $freqs:expr $(,)?) => {
+        /// The `(symbol, frequency)` pairs the tables were generated from.
+        pub const FREQUENCIES: &[(u8, u32)] = &$freqs;
+
+        static FREQUENCY_BYTES: [u8; 8 * FREQUENCIES.len()] = $crate::frequency_bytes(FREQUENCIES);
+
+        static STATE_TABLES: $crate::StateTables<{ FREQUENCIES.len() - 1 }> =
+            $crate::state_tables(FREQUENCIES);
+
+        /// Whether `packet` can be decoded using the generated tables.
+        #[inline(always)]
+        pub fn matches(packet: &$crate::Packet) -> bool {
+            packet.symbol_frequency_bytes == FREQUENCY_BYTES.as_slice()
+        }
+
+        pub fn decode_packet(content: &[u8]) -> $crate::String {
+            let packet = &$crate::Packet::new(content);
+            if matches(packet) {
+                $crate::decode_message(packet, &STATE_TABLES)
+            } else {
+                $crate::decode_packet_dynamic(content)
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[inline(always)]
+pub fn decode_message<const STATES: usize>(
+    packet: &Packet,
+    table: &'static StateTables<STATES>,
+) -> String {
+    // Add slop space instead of checking write_index against decoded_len on
+    // every step.
+    let decoded_len = packet.decoded_bytes_len as usize;
+    let mut decoded: Vec<u8> = vec![0; decoded_len + 8];
+    let mut index = 0usize;
+    let mut state = 0;
+
+    let mut bit_reader = BigEndianReader::new(packet.encoded_message);
+
+    // Lookahead is 56bits
+    // Consume unbuffered bytes; guaranteed 7 8-bit indices per iteration.
+    // A step writes at most 8 symbols, so an iteration only runs while its 56
+    // fit even if the bitstream has more symbols than the header.
+    while bit_reader.unbuffered_bytes_remaining() > 7 && index + 56 <= decoded_len {
+        bit_reader.refill_lookahead();
+        for _ in 0..7 {
+            state = step(&mut bit_reader, table, &mut index, &mut decoded, state);
+        }
+    }
+
+    // Consume remaining bytes, each step starting below decoded_len so its 8
+    // bytes stay within the slop.
+    while bit_reader.bytes_remaining() > 0 && index < decoded_len {
+        bit_reader.refill_lookahead();
+        state = step(&mut bit_reader, table, &mut index, &mut decoded, state);
+    }
+
+    // Truncate decoded slop. `state_tables` only accepts `ALLOWED_SYMBOLS`, so
+    // the tables and the message are ASCII.
+    let slice = &decoded[..decoded_len];
+    core::str::from_utf8(slice)
+        .expect("the tables only hold ASCII symbols")
+        .to_owned()
+}
+
+#[inline(always)]
+fn step<const STATES: usize>(
+    bit_reader: &mut BigEndianReader,
+    table: &StateTables<STATES>,
+    write_index: &mut usize,
+    decoded: &mut [u8],
+    state: usize,
+) -> usize {
+    let index = bit_reader.peek(8) as usize;
+    let symbols: &[u8; 9] = &table.tables[state].symbols[index];
+    let state = symbols[0] as usize;
+    copy_symbols(symbols, write_index, decoded);
+    bit_reader.consume(8);
+    state
+}
+
+#[inline(always)]
+fn copy_symbols(symbols: &[u8; 9], write_index: &mut usize, decoded: &mut [u8]) {
+    decoded[*write_index..*write_index + 8].copy_from_slice(&symbols[1..9]);
+    let symbol_block = u64::from_le_bytes(symbols[1..9].try_into().unwrap());
+    let len = 8 - (symbol_block.leading_zeros() / 8) as usize;
+    *write_index += len;
+}
+
+#[doc(hidden)]
+pub fn decode_packet_dynamic(content: &[u8]) -> String {
+    fsm::decode_packet(content)
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use common::packet::Packet;
+    use common::test_cases::*;
+
+    mod test_bytes {
+        crate::huffman_decoder!(freqs = common::test_cases::EXPECTED_SYMBOL_FREQUENCIES);
+    }
+
+    #[test]
+    fn decodes_packet() {
+        assert!(test_bytes::matches(&Packet::new(&TEST_BYTES)));
+        let decoded_message = test_bytes::decode_packet(&TEST_BYTES);
+        assert_eq!(decoded_message, EXPECTED_MESSAGE);
+    }
+
+    #[test]
+    fn frequency_bytes_match_packet() {
+        let packet = Packet::new(&TEST_BYTES);
+        let bytes: [u8; 96] = crate::frequency_bytes(&EXPECTED_SYMBOL_FREQUENCIES);
+        assert_eq!(packet.symbol_frequency_bytes, bytes);
+    }
+
+    #[test]
+    fn decodes_sample() {
+        #[rustfmt::skip]
+        mod small_min {
+            crate::huffman_decoder!(freqs = [
+                (b'-', 6), (b'0', 11), (b'1', 3), (b'2', 1), (b'3', 2), (b'4', 2),
+                (b'5', 5), (b'6', 2), (b'7', 2), (b'8', 3), (b'9', 1), (b'|', 2),
+            ]);
+        }
+        let content = ALL_CASES[5].request();
+        assert!(small_min::matches(&Packet::new(&content)));
+        assert_eq!(
+            small_min::decode_packet(&content),
+            fsm::decode_packet(&content)
+        );
+    }
+
+    #[test]
+    fn const_tables_match_fsm_tables() {
+        for case in ALL_CASES {
+            let content = case.request();
+            let packet = Packet::new(&content);
+            let freqs: std::vec::Vec<_> = packet.symbol_frequencies().collect();
+            let tables = std::boxed::Box::new(crate::state_tables::<11>(&freqs));
+            let expected = fsm::state_table_entries(&packet);
+            assert_eq!(expected.len(), 11, "{case}");
+            for (state, (table, expected)) in tables.tables.iter().zip(&expected).enumerate() {
+                assert!(table.symbols == *expected, "{case}: state {state}");
+            }
+        }
+    }
+
+    #[test]
+    fn stops_at_decoded_len() {
+        use common::packet::PacketBuilder;
+
+        // Zero bytes repeat the 3 bit code of `1` far past `len_decoded_data`.
+        let packet = Packet::new(&TEST_BYTES);
+        let zeros = PacketBuilder::from_packet(&packet)
+            .message(&[0; 4000])
+            .build();
+        assert_eq!(
+            test_bytes::decode_packet(&zeros),
+            "1".repeat(packet.decoded_bytes_len as usize)
+        );
+    }
+
+    #[test]
+    fn falls_back_on_mismatch() {
+        for case in ALL_CASES {
+            let content = case.request();
+            assert!(!test_bytes::matches(&Packet::new(&content)));
+            assert_eq!(
+                test_bytes::decode_packet(&content),
+                fsm::decode_packet(&content)
+            );
+        }
+    }
+}
+
+// MARK: Benches
+
+#[cfg(feature = "std")]
+use common::test_cases::BENCH_SAMPLE_COUNT;
+#[cfg(feature = "std")]
+#[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
+mod bench {
+    use common::test_cases::*;
+
+    use divan::{black_box, Bencher};
+
+    // The symbol tables of `ALL_CASES[2]` and `ALL_CASES[5]`.
+    #[rustfmt::skip]
+    mod medium {
+        crate::huffman_decoder!(freqs = [
+            (b'-', 2919), (b'0', 9485), (b'1', 2572), (b'2', 1351), (b'3', 1032), (b'4', 649),
+            (b'5', 657), (b'6', 1358), (b'7', 526), (b'8', 490), (b'9', 537), (b'|', 973),
+        ]);
+    }
+    #[rustfmt::skip]
+    mod small_min {
+        crate::huffman_decoder!(freqs = [
+            (b'-', 6), (b'0', 11), (b'1', 3), (b'2', 1), (b'3', 2), (b'4', 2),
+            (b'5', 5), (b'6', 2), (b'7', 2), (b'8', 3), (b'9', 1), (b'|', 2),
+        ]);
+    }
+
+    #[divan::bench(args = [ALL_CASES[2], ALL_CASES[5]])]
+    fn decode_packet(bencher: Bencher, case: &Case) {
+        let content = case.request();
+        let decode = match case.sub_category {
+            2 => medium::decode_packet,
+            _ => small_min::decode_packet,
+        };
+        bencher.bench_local(move || {
+            decode(black_box(&content));
+        });
+    }
+
+    #[divan::bench(args = [ALL_CASES[2], ALL_CASES[5]])]
+    fn decode_packet_dynamic(bencher: Bencher, case: &Case) {
+        let content = case.request();
+        bencher.bench_local(move || {
+            super::decode_packet_dynamic(black_box(&content));
+        });
+    }
+}
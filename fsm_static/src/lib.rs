@@ -0,0 +1,13 @@
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+extern crate alloc;
+
+pub(crate) mod builder;
+pub(crate) mod decoder;
+pub use builder::{frequency_bytes, state_tables, StateTables};
+pub use decoder::{decode_message, decode_packet_dynamic};
+
+#[doc(hidden)]
+pub use alloc::string::String;
+#[doc(hidden)]
+pub use common::packet::Packet;
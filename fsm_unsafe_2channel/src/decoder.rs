@@ -1,15 +1,33 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
 use bitter::{BigEndianReader, BitReader};
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::{Packet, MAX_SYMBOLS};
+use common::profile::Phase;
 
 const MAX_TREE_LEN: usize = 23;
 
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    let table = &state_tables(&tree);
-    decode_message(packet, table)
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    let table = &time(Phase::Table, || state_tables(&tree));
+    time(Phase::Decode, || decode_message(packet, table))
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree,
+        state_tables,
+        |packet, _, table| decode_message(packet, table),
+    );
 }
 
 fn decode_message(packet: &Packet, table: &StateTables) -> String {
@@ -105,7 +123,7 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
                 .get_unchecked(*last_byte as usize);
 
             let src_ptr = symbols.as_ptr().add(1);
-            std::ptr::copy_nonoverlapping(src_ptr, ptr0, 8);
+            core::ptr::copy_nonoverlapping(src_ptr, ptr0, 8);
 
             let symbols = symbols.last_chunk::<8>().unwrap();
             let symbol_block = u64::from_le_bytes(*symbols);
@@ -116,8 +134,8 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
         let final_len = ptr0.offset_from(decoded0.as_ptr()) as usize;
         decoded0.set_len(final_len);
 
-        let slice = std::slice::from_raw_parts(decoded0.as_ptr(), final_len);
-        let mut decoded = std::str::from_utf8_unchecked(slice).to_owned();
+        let slice = core::slice::from_raw_parts(decoded0.as_ptr(), final_len);
+        let mut decoded = core::str::from_utf8_unchecked(slice).to_owned();
         decoded.truncate(packet.decoded_bytes_len as usize);
         decoded
     }
@@ -248,7 +266,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
         process_heap_node(&left, tree, tree_index);
 
         // Add a parent node to the heap for ordering
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
         let parent = HeapNode::new_parent(parent_frequency, tree_index as u8);
         heap.push(parent);
     }
@@ -485,12 +503,12 @@ impl MinHeapNode for HeapNode {
     }
 }
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -536,7 +554,10 @@ mod tests {
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+use common::tree::parent_frequency;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
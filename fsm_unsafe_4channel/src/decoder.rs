@@ -1,34 +1,144 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
 use bitter::{BigEndianReader, BitReader};
+use common::kernel::Kernel;
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::{Packet, MAX_SYMBOLS};
+use common::profile::Phase;
 
 const MAX_TREE_LEN: usize = 23;
 
 pub fn decode_packet(content: &[u8]) -> String {
+    decode_packet_with::<Classic>(content)
+}
+
+/// `decode_packet` for packets whose encoder breaks frequency ties with `P`.
+pub fn decode_packet_with<P: TieBreak>(content: &[u8]) -> String {
+    decode_packet_with_kernel::<P>(content, Kernel::detect())
+}
+
+/// `decode_packet_with` forced to decode with `kernel`.
+///
+/// Panics if the CPU doesn't support `kernel`.
+pub fn decode_packet_with_kernel<P: TieBreak>(content: &[u8], kernel: Kernel) -> String {
+    assert!(kernel.is_supported(), "{kernel:?} isn't supported");
     let packet = &Packet::new(content);
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    let table = &state_tables(&tree);
-    decode_message(packet, table)
+    time(Phase::Tree, || huffman_tree::<P>(packet, &mut tree));
+    let table = &time(Phase::Table, || state_tables(&tree));
+    let mut channels = Default::default();
+    time(Phase::Decode, || {
+        decode_message(kernel, packet, table, &mut channels)
+    });
+    // The first channel reserves room for a full bitstream of symbols, drop
+    // the spare capacity before handing it out.
+    let [mut decoded, ..] = channels;
+    decoded.shrink_to_fit();
+    unsafe { String::from_utf8_unchecked(decoded) }
 }
 
-fn decode_message(packet: &Packet, table: &StateTables) -> String {
-    let mut decoded0: Vec<u8> = Vec::with_capacity(packet.decoded_bytes_len as usize + 8);
-    let mut decoded1: Vec<u8> = Vec::with_capacity(packet.decoded_bytes_len as usize + 8);
-    let mut decoded2: Vec<u8> = Vec::with_capacity(packet.decoded_bytes_len as usize + 8);
-    let mut decoded3: Vec<u8> = Vec::with_capacity(packet.decoded_bytes_len as usize + 8);
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree::<Classic>,
+        state_tables,
+        |packet, _, table| {
+            let mut channels = Default::default();
+            decode_message(Kernel::detect(), packet, table, &mut channels);
+            channels
+        },
+    );
+}
 
-    let mut ptr0 = decoded0.as_mut_ptr();
-    let mut ptr1 = decoded1.as_mut_ptr();
-    let mut ptr2 = decoded2.as_mut_ptr();
-    let mut ptr3 = decoded3.as_mut_ptr();
+/// The state tables of a packet's symbol table, for decoding packets that
+/// share a symbol table.
+pub struct Decoder {
+    symbol_frequency_bytes: Vec<u8>,
+    tables: Box<StateTables>,
+    kernel: Kernel,
+}
+
+impl Decoder {
+    pub fn new(packet: &Packet) -> Self {
+        Self::new_with::<Classic>(packet)
+    }
+
+    /// `new` for packets whose encoder breaks frequency ties with `P`.
+    pub fn new_with<P: TieBreak>(packet: &Packet) -> Self {
+        let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+        time(Phase::Tree, || huffman_tree::<P>(packet, &mut tree));
+        Decoder {
+            symbol_frequency_bytes: packet.symbol_frequency_bytes.to_vec(),
+            tables: Box::new(time(Phase::Table, || state_tables(&tree))),
+            kernel: Kernel::detect(),
+        }
+    }
 
+    /// The symbol table of the packet the decoder was created from.
+    pub fn symbol_frequency_bytes(&self) -> &[u8] {
+        &self.symbol_frequency_bytes
+    }
+
+    /// Decodes `packet` into `channels[0]`, the other channels are scratch
+    /// space for the channels decoded in parallel. Their allocations are reused.
+    ///
+    /// Panics if `packet` doesn't have the symbol table the decoder was created
+    /// from.
+    pub fn decode_into(&self, packet: &Packet, channels: &mut [Vec<u8>; 4]) {
+        assert_eq!(
+            packet.symbol_frequency_bytes, self.symbol_frequency_bytes,
+            "the packet has a different symbol table"
+        );
+        time(Phase::Decode, || {
+            decode_message(self.kernel, packet, &self.tables, channels)
+        });
+    }
+}
+
+/// `kernel` must be supported by the CPU.
+fn decode_message(
+    kernel: Kernel,
+    packet: &Packet,
+    table: &StateTables,
+    channels: &mut [Vec<u8>; 4],
+) {
+    match kernel {
+        Kernel::Generic => decode_message_kernel::<false>(packet, table, channels),
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Haswell => unsafe { decode_message_haswell(packet, table, channels) },
+    }
+}
+
+// The same loop with BMI2's flagless shifts for the bit readers, LZCNT for the
+// symbol counts and a single 8-byte store per step.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,bmi1,bmi2,lzcnt")]
+unsafe fn decode_message_haswell(
+    packet: &Packet,
+    table: &StateTables,
+    channels: &mut [Vec<u8>; 4],
+) {
+    decode_message_kernel::<true>(packet, table, channels)
+}
+
+#[inline(always)]
+fn decode_message_kernel<const WIDE: bool>(
+    packet: &Packet,
+    table: &StateTables,
+    channels: &mut [Vec<u8>; 4],
+) {
     let mut state0 = 0usize;
     let mut state1 = 0usize;
     let mut state2 = 0usize;
     let mut state3 = 0usize;
 
-    let (tail, encoded_bytes) = if packet.encoded_bytes_len as usize % 4 != 0 {
+    let (tail, encoded_bytes) = if !(packet.encoded_bytes_len as usize).is_multiple_of(4) {
         let n = (packet.encoded_bytes_len / 4) * 4;
         let (encoded_bytes, tail) = packet.encoded_message.split_at(n as usize);
         (Some(tail), encoded_bytes)
@@ -43,6 +153,26 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
     let bytes2 = chunk_iter.next().unwrap();
     let bytes3 = chunk_iter.next().unwrap();
 
+    // A step writes 8 bytes and moves on by the up to 8 symbols of its byte,
+    // so 8 bytes per encoded byte hold a channel's steps whatever the
+    // bitstream. The first channel ends up with the symbols of every byte.
+    let capacities = [
+        packet.encoded_message.len(),
+        chunk_len,
+        chunk_len,
+        chunk_len,
+    ];
+    for (channel, capacity) in channels.iter_mut().zip(capacities) {
+        channel.clear();
+        channel.reserve(8 * capacity);
+    }
+    let [decoded0, decoded1, decoded2, decoded3] = channels;
+
+    let mut ptr0 = decoded0.as_mut_ptr();
+    let mut ptr1 = decoded1.as_mut_ptr();
+    let mut ptr2 = decoded2.as_mut_ptr();
+    let mut ptr3 = decoded3.as_mut_ptr();
+
     let mut bit_reader0 = BigEndianReader::new(bytes0);
     let mut bit_reader1 = BigEndianReader::new(bytes1);
     let mut bit_reader2 = BigEndianReader::new(bytes2);
@@ -56,10 +186,10 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
             bit_reader2.refill_lookahead_unchecked();
             bit_reader3.refill_lookahead_unchecked();
             for _ in 0..7 {
-                state0 = step(&mut bit_reader0, table, &mut ptr0, state0);
-                state1 = step(&mut bit_reader1, table, &mut ptr1, state1);
-                state2 = step(&mut bit_reader2, table, &mut ptr2, state2);
-                state3 = step(&mut bit_reader3, table, &mut ptr3, state3);
+                state0 = step::<WIDE>(&mut bit_reader0, table, &mut ptr0, state0);
+                state1 = step::<WIDE>(&mut bit_reader1, table, &mut ptr1, state1);
+                state2 = step::<WIDE>(&mut bit_reader2, table, &mut ptr2, state2);
+                state3 = step::<WIDE>(&mut bit_reader3, table, &mut ptr3, state3);
             }
         }
 
@@ -74,81 +204,63 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
         let bytes_remaining = bit_reader0.bytes_remaining();
         if bytes_remaining == 7 {
             for _ in 0..7 {
-                state0 = step(&mut bit_reader0, table, &mut ptr0, state0);
-                state1 = step(&mut bit_reader1, table, &mut ptr1, state1);
-                state2 = step(&mut bit_reader2, table, &mut ptr2, state2);
-                state3 = step(&mut bit_reader3, table, &mut ptr3, state3);
+                state0 = step::<WIDE>(&mut bit_reader0, table, &mut ptr0, state0);
+                state1 = step::<WIDE>(&mut bit_reader1, table, &mut ptr1, state1);
+                state2 = step::<WIDE>(&mut bit_reader2, table, &mut ptr2, state2);
+                state3 = step::<WIDE>(&mut bit_reader3, table, &mut ptr3, state3);
             }
         } else if bytes_remaining == 6 {
             for _ in 0..6 {
-                state0 = step(&mut bit_reader0, table, &mut ptr0, state0);
-                state1 = step(&mut bit_reader1, table, &mut ptr1, state1);
-                state2 = step(&mut bit_reader2, table, &mut ptr2, state2);
-                state3 = step(&mut bit_reader3, table, &mut ptr3, state3);
+                state0 = step::<WIDE>(&mut bit_reader0, table, &mut ptr0, state0);
+                state1 = step::<WIDE>(&mut bit_reader1, table, &mut ptr1, state1);
+                state2 = step::<WIDE>(&mut bit_reader2, table, &mut ptr2, state2);
+                state3 = step::<WIDE>(&mut bit_reader3, table, &mut ptr3, state3);
             }
         } else if bytes_remaining == 5 {
             for _ in 0..5 {
-                state0 = step(&mut bit_reader0, table, &mut ptr0, state0);
-                state1 = step(&mut bit_reader1, table, &mut ptr1, state1);
-                state2 = step(&mut bit_reader2, table, &mut ptr2, state2);
-                state3 = step(&mut bit_reader3, table, &mut ptr3, state3);
+                state0 = step::<WIDE>(&mut bit_reader0, table, &mut ptr0, state0);
+                state1 = step::<WIDE>(&mut bit_reader1, table, &mut ptr1, state1);
+                state2 = step::<WIDE>(&mut bit_reader2, table, &mut ptr2, state2);
+                state3 = step::<WIDE>(&mut bit_reader3, table, &mut ptr3, state3);
             }
         } else if bytes_remaining == 4 {
             for _ in 0..4 {
-                state0 = step(&mut bit_reader0, table, &mut ptr0, state0);
-                state1 = step(&mut bit_reader1, table, &mut ptr1, state1);
-                state2 = step(&mut bit_reader2, table, &mut ptr2, state2);
-                state3 = step(&mut bit_reader3, table, &mut ptr3, state3);
+                state0 = step::<WIDE>(&mut bit_reader0, table, &mut ptr0, state0);
+                state1 = step::<WIDE>(&mut bit_reader1, table, &mut ptr1, state1);
+                state2 = step::<WIDE>(&mut bit_reader2, table, &mut ptr2, state2);
+                state3 = step::<WIDE>(&mut bit_reader3, table, &mut ptr3, state3);
             }
         } else if bytes_remaining == 3 {
             for _ in 0..3 {
-                state0 = step(&mut bit_reader0, table, &mut ptr0, state0);
-                state1 = step(&mut bit_reader1, table, &mut ptr1, state1);
-                state2 = step(&mut bit_reader2, table, &mut ptr2, state2);
-                state3 = step(&mut bit_reader3, table, &mut ptr3, state3);
+                state0 = step::<WIDE>(&mut bit_reader0, table, &mut ptr0, state0);
+                state1 = step::<WIDE>(&mut bit_reader1, table, &mut ptr1, state1);
+                state2 = step::<WIDE>(&mut bit_reader2, table, &mut ptr2, state2);
+                state3 = step::<WIDE>(&mut bit_reader3, table, &mut ptr3, state3);
             }
         } else if bytes_remaining == 2 {
             for _ in 0..2 {
-                state0 = step(&mut bit_reader0, table, &mut ptr0, state0);
-                state1 = step(&mut bit_reader1, table, &mut ptr1, state1);
-                state2 = step(&mut bit_reader2, table, &mut ptr2, state2);
-                state3 = step(&mut bit_reader3, table, &mut ptr3, state3);
+                state0 = step::<WIDE>(&mut bit_reader0, table, &mut ptr0, state0);
+                state1 = step::<WIDE>(&mut bit_reader1, table, &mut ptr1, state1);
+                state2 = step::<WIDE>(&mut bit_reader2, table, &mut ptr2, state2);
+                state3 = step::<WIDE>(&mut bit_reader3, table, &mut ptr3, state3);
             }
         } else if bytes_remaining == 1 {
-            state0 = step(&mut bit_reader0, table, &mut ptr0, state0);
-            state1 = step(&mut bit_reader1, table, &mut ptr1, state1);
-            state2 = step(&mut bit_reader2, table, &mut ptr2, state2);
-            state3 = step(&mut bit_reader3, table, &mut ptr3, state3);
+            state0 = step::<WIDE>(&mut bit_reader0, table, &mut ptr0, state0);
+            state1 = step::<WIDE>(&mut bit_reader1, table, &mut ptr1, state1);
+            state2 = step::<WIDE>(&mut bit_reader2, table, &mut ptr2, state2);
+            state3 = step::<WIDE>(&mut bit_reader3, table, &mut ptr3, state3);
         }
 
-        state0 = converge(
-            bytes1,
-            state0,
-            state1,
-            &mut ptr0,
-            &mut ptr1,
-            &mut decoded1,
-            table,
+        state0 = converge::<WIDE>(
+            bytes1, state0, state1, &mut ptr0, &mut ptr1, decoded1, table,
         );
 
-        state0 = converge(
-            bytes2,
-            state0,
-            state2,
-            &mut ptr0,
-            &mut ptr2,
-            &mut decoded2,
-            table,
+        state0 = converge::<WIDE>(
+            bytes2, state0, state2, &mut ptr0, &mut ptr2, decoded2, table,
         );
 
-        state0 = converge(
-            bytes3,
-            state0,
-            state3,
-            &mut ptr0,
-            &mut ptr3,
-            &mut decoded3,
-            table,
+        state0 = converge::<WIDE>(
+            bytes3, state0, state3, &mut ptr0, &mut ptr3, decoded3, table,
         );
 
         if let Some(last_bytes) = tail {
@@ -161,7 +273,7 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
 
                 state0 = symbols[0] as usize;
                 let src_ptr = symbols.as_ptr().add(1);
-                std::ptr::copy_nonoverlapping(src_ptr, ptr0, 8);
+                core::ptr::copy_nonoverlapping(src_ptr, ptr0, 8);
 
                 let symbols = symbols.last_chunk::<8>().unwrap();
                 let symbol_block = u64::from_le_bytes(*symbols);
@@ -171,12 +283,7 @@ fn decode_message(packet: &Packet, table: &StateTables) -> String {
         }
 
         let final_len = ptr0.offset_from(decoded0.as_ptr()) as usize;
-        decoded0.set_len(final_len);
-
-        let slice = std::slice::from_raw_parts(decoded0.as_ptr(), final_len);
-        let mut decoded = std::str::from_utf8_unchecked(slice).to_owned();
-        decoded.truncate(packet.decoded_bytes_len as usize);
-        decoded
+        decoded0.set_len(final_len.min(packet.decoded_bytes_len as usize));
     }
 }
 
@@ -208,7 +315,7 @@ unsafe fn step_state(
 
 // Removing inline(always) from here while funcs above it have it reduced time from 89 (see next funct)
 // to 26.09
-unsafe fn step(
+unsafe fn step<const WIDE: bool>(
     bit_reader: &mut BigEndianReader,
     table: &StateTables,
     write_ptr: &mut *mut u8,
@@ -225,10 +332,14 @@ unsafe fn step(
     bit_reader.consume(8);
 
     let symbols = symbols.last_chunk::<8>().unwrap();
-    symbols
-        .iter()
-        .enumerate()
-        .for_each(|(i, x)| *write_ptr.add(i) = *x);
+    if WIDE {
+        write_ptr.cast::<[u8; 8]>().write_unaligned(*symbols);
+    } else {
+        symbols
+            .iter()
+            .enumerate()
+            .for_each(|(i, x)| *write_ptr.add(i) = *x);
+    }
 
     let symbol_block = u64::from_le_bytes(*symbols);
     let len = 8 - (symbol_block.leading_zeros() / 8) as usize;
@@ -240,7 +351,7 @@ unsafe fn step(
 // Removing inline(always) from here while the funcs above have inline improves time from 120 to 89
 // putting the inline back when step and step state didn't have inline improved time from 26.09 to 25.29 (see above)
 #[inline(always)]
-unsafe fn converge(
+unsafe fn converge<const WIDE: bool>(
     bytes1: &[u8],
     mut state0: usize,
     mut state1: usize,
@@ -259,11 +370,11 @@ unsafe fn converge(
     while bit_reader0.unbuffered_bytes_remaining() > 0 && state0 != state1 {
         bit_reader0.refill_lookahead();
         bit_reader1.refill_lookahead();
-        state0 = step(&mut bit_reader0, table, ptr0, state0);
+        state0 = step::<WIDE>(&mut bit_reader0, table, ptr0, state0);
         state1 = step_state(&mut bit_reader1, table, &mut ptr1_reset, state1);
     }
     while bit_reader0.bytes_remaining() > 0 && state0 != state1 {
-        state0 = step(&mut bit_reader0, table, ptr0, state0);
+        state0 = step::<WIDE>(&mut bit_reader0, table, ptr0, state0);
         state1 = step_state(&mut bit_reader1, table, &mut ptr1_reset, state1);
     }
     if state0 != state1 {
@@ -290,14 +401,14 @@ fn process_heap_node(node: &HeapNode, tree: &mut [TreeNode; MAX_TREE_LEN], index
     tree[index].index = Some(index);
 }
 
-fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
+fn huffman_tree<P: TieBreak>(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
     // Set the root node.
     tree[0].symbol = None;
     tree[0].left_index = 1;
     tree[0].right_index = 2;
     tree[0].index = Some(0);
 
-    let mut heap = unsafe { symbols_heap(packet) };
+    let mut heap = unsafe { symbols_heap::<P>(packet) };
     let mut tree_index = 2 * packet.symbol_count as usize - 1;
 
     // Successively move two smallest nodes from heap to tree
@@ -311,7 +422,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
         process_heap_node(&left, tree, tree_index);
 
         // Add a parent node to the heap for ordering
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
         let parent = HeapNode::new_parent(parent_frequency, tree_index as u8);
         heap.push(parent);
     }
@@ -324,8 +435,8 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
     process_heap_node(&left, tree, tree_index);
 }
 
-unsafe fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
+unsafe fn symbols_heap<P: TieBreak>(packet: &Packet) -> P::Queue<HeapNode> {
+    let mut heap = P::Queue::<HeapNode>::default();
     let ptr = packet.symbol_frequency_bytes.as_ptr();
     for i in 0..packet.symbol_count as usize {
         let freq_ptr = ptr.add(i * 8) as *const (u32, u8);
@@ -353,190 +464,83 @@ struct StateTables {
 }
 
 fn state_tables(tree: &[TreeNode; MAX_TREE_LEN]) -> StateTables {
-    let (table_indices, child_states) = child_states(tree);
+    let (table_indices, state_count) = table_indices(tree);
+    let nibbles = nibble_tables(tree, &table_indices);
 
     let mut state_tables = StateTables {
         tables: [SymbolTable::default(); MAX_SYMBOLS],
     };
 
-    // Find the last 'state 3' node and populate a symbol table to copy entries from later.
-    let reference_index = child_states.iter().rposition(|&x| x == 3).unwrap();
-    let reference = initialize_reference_table(&tree[reference_index], tree, &table_indices);
-    state_tables.tables[table_indices[reference_index] as usize] = reference;
+    // A byte decodes its upper nibble from the state, then its lower nibble from
+    // the state the upper nibble ends in.
+    for (table, upper_nibbles) in state_tables.tables[..state_count].iter_mut().zip(&nibbles) {
+        for (upper, high) in upper_nibbles.iter().enumerate() {
+            for (lower, low) in nibbles[high.state as usize].iter().enumerate() {
+                let symbols = high.symbols as u64 | (low.symbols as u64) << (8 * high.len);
+                let entry = &mut table.symbols[upper << 4 | lower];
+                entry[0] = low.state;
+                entry[1..9].copy_from_slice(&symbols.to_le_bytes());
+            }
+        }
+    }
+    state_tables
+}
+
+/// The symbols decoded from 4 bits of a state and the state they end in.
+#[derive(Clone, Copy, Default)]
+struct NibbleEntry {
+    state: u8,
+    len: u8,
+    // The first symbol in the lowest byte.
+    symbols: u32,
+}
 
-    for i in 0..MAX_TREE_LEN {
-        let table_index = table_indices[i];
-        if table_index == MAX_TREE_LEN as u8 || i == reference_index {
+fn nibble_tables(
+    tree: &[TreeNode; MAX_TREE_LEN],
+    table_indices: &[u8; MAX_TREE_LEN],
+) -> [[NibbleEntry; 16]; MAX_SYMBOLS] {
+    let mut nibbles = [[NibbleEntry::default(); 16]; MAX_SYMBOLS];
+    for (start_node, &table_index) in tree.iter().zip(table_indices) {
+        if table_index == MAX_TREE_LEN as u8 {
             continue;
         }
-
-        // Copy and modify entries from the reference table when we can.
-        let start_node = &tree[i];
-        let table_fn = match child_states[i] {
-            1 => copy_lower_gen_upper,
-            2 => gen_lower_copy_upper,
-            3 => copy_full_range,
-            _ => gen_full_range,
-        };
-        state_tables.tables[table_index as usize] =
-            table_fn(start_node, tree, &table_indices, &reference);
+        for (bits, entry) in nibbles[table_index as usize].iter_mut().enumerate() {
+            let mut node = start_node;
+            for shift in (0..4).rev() {
+                node = match bits >> shift & 1 {
+                    0 => &tree[node.left_index as usize],
+                    _ => &tree[node.right_index as usize],
+                };
+                if let Some(symbol) = node.symbol {
+                    entry.symbols |= (symbol as u32) << (8 * entry.len);
+                    entry.len += 1;
+                }
+            }
+            entry.state = if node.symbol.is_some() {
+                0
+            } else {
+                table_indices[node.index.unwrap()]
+            };
+        }
     }
-    state_tables
+    nibbles
 }
 
-fn child_states(tree: &[TreeNode; MAX_TREE_LEN]) -> ([u8; MAX_TREE_LEN], [u8; MAX_TREE_LEN]) {
+/// The state of each internal node, `MAX_TREE_LEN` for leaves, and the number
+/// of states.
+fn table_indices(tree: &[TreeNode; MAX_TREE_LEN]) -> ([u8; MAX_TREE_LEN], usize) {
     let mut table_indices = [MAX_TREE_LEN as u8; MAX_TREE_LEN];
-    let mut child_states = [MAX_TREE_LEN as u8; MAX_TREE_LEN];
     let mut internal_count = 0;
     tree.iter().enumerate().for_each(|(i, node)| {
         if node.symbol.is_none() && node.index.is_some() {
             table_indices[i] = internal_count;
             internal_count += 1;
         };
-        let left_state = tree[node.left_index as usize].symbol.is_some() as u8;
-        let right_state = tree[node.right_index as usize].symbol.is_some() as u8;
-        let child_state = left_state + 2 * right_state;
-        child_states[i] = child_state;
     });
-    (table_indices, child_states)
+    (table_indices, internal_count as usize)
 }
 
-fn initialize_reference_table(
-    start_node: &TreeNode,
-    tree: &[TreeNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    for byte in 0u8..=127 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
-    }
-    let (first_half, second_half) = table.symbols.split_at_mut(128);
-    second_half.copy_from_slice(first_half);
-    table.symbols[128..=255]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.right_index as usize].symbol.unwrap());
-    table
-}
-
-fn copy_lower_gen_upper(
-    start_node: &TreeNode,
-    tree: &[TreeNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-    reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    table.symbols[0..=127].copy_from_slice(&reference_table.symbols[0..=127]);
-    table.symbols[0..=127]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.left_index as usize].symbol.unwrap());
-
-    for byte in 128u8..=255 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
-    }
-    table
-}
-
-fn gen_lower_copy_upper(
-    start_node: &TreeNode,
-    tree: &[TreeNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-    reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    table.symbols[128..=255].copy_from_slice(&reference_table.symbols[128..=255]);
-    table.symbols[128..=255]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.right_index as usize].symbol.unwrap());
-
-    for byte in 0u8..=127 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
-    }
-    table
-}
-
-fn copy_full_range(
-    start_node: &TreeNode,
-    tree: &[TreeNode; MAX_TREE_LEN],
-    _table_indices: &[u8; MAX_TREE_LEN],
-    reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable {
-        symbols: reference_table.symbols,
-    };
-    table.symbols[0..=127]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.left_index as usize].symbol.unwrap());
-    table.symbols[128..=255]
-        .iter_mut()
-        .for_each(|x| x[1] = tree[start_node.right_index as usize].symbol.unwrap());
-    table
-}
-
-fn gen_full_range(
-    start_node: &TreeNode,
-    tree: &[TreeNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-    _reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    for byte in 0u8..=255 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
-    }
-    table
-}
-
-#[inline(always)]
-fn decode_bits<'a>(
-    mut bits: u8,
-    mut node: &'a TreeNode,
-    symbols: &mut [u8; 9],
-    tree: &'a [TreeNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-) {
-    let mut write_index = 1;
-    for _ in 0..=7 {
-        node = match bits >> 7 {
-            0 => unsafe { tree.get_unchecked(node.left_index as usize) },
-            _ => unsafe { tree.get_unchecked(node.right_index as usize) },
-        };
-        if let Some(symbol) = node.symbol {
-            symbols[write_index] = symbol;
-            write_index += 1;
-        }
-        bits <<= 1;
-    }
-    symbols[0] = if node.symbol.is_some() {
-        0
-    } else {
-        unsafe { *table_indices.get_unchecked(node.index.unwrap()) }
-    };
-}
-
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 struct HeapNode {
     tree_index: u8,
     symbol: Option<u8>,
@@ -548,12 +552,12 @@ impl MinHeapNode for HeapNode {
     }
 }
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -588,18 +592,109 @@ struct TreeNode {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use common::test_cases::*;
 
     #[test]
     fn decodes_packet() {
         let decoded_message = super::decode_packet(&TEST_BYTES);
         assert_eq!(decoded_message, EXPECTED_MESSAGE);
+        assert_eq!(decoded_message.capacity(), decoded_message.len());
+    }
+
+    #[test]
+    fn decoder_reuses_channels() {
+        let content = ALL_CASES[0].request();
+        let packet = &Packet::new(&content);
+        let decoder = Decoder::new(packet);
+        let mut channels = Default::default();
+        for _ in 0..2 {
+            decoder.decode_into(packet, &mut channels);
+            assert_eq!(channels[0], super::decode_packet(&content).as_bytes());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "different symbol table")]
+    fn decoder_rejects_other_tables() {
+        let content = ALL_CASES[0].request();
+        let decoder = Decoder::new(&Packet::new(&content));
+        decoder.decode_into(&Packet::new(&TEST_BYTES), &mut Default::default());
+    }
+
+    // Decodes a byte from `start_node` by walking the tree bit by bit.
+    fn decode_bits(
+        mut bits: u8,
+        start_node: &TreeNode,
+        tree: &[TreeNode; MAX_TREE_LEN],
+    ) -> [u8; 9] {
+        let (table_indices, _) = table_indices(tree);
+        let mut symbols = [0; 9];
+        let mut write_index = 1;
+        let mut node = start_node;
+        for _ in 0..8 {
+            node = match bits >> 7 {
+                0 => &tree[node.left_index as usize],
+                _ => &tree[node.right_index as usize],
+            };
+            if let Some(symbol) = node.symbol {
+                symbols[write_index] = symbol;
+                write_index += 1;
+            }
+            bits <<= 1;
+        }
+        if node.symbol.is_none() {
+            symbols[0] = table_indices[node.index.unwrap()];
+        }
+        symbols
+    }
+
+    #[test]
+    fn composed_tables_match_tree_walks() {
+        let requests = ALL_CASES.iter().map(|case| case.request());
+        for content in requests.chain([TEST_BYTES.to_vec()]) {
+            let packet = &Packet::new(&content);
+            let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+            huffman_tree::<Classic>(packet, &mut tree);
+            let state_tables = state_tables(&tree);
+            let (table_indices, state_count) = table_indices(&tree);
+            assert_eq!(state_count, packet.symbol_count as usize - 1);
+            for (node, &table_index) in tree.iter().zip(&table_indices) {
+                if table_index == MAX_TREE_LEN as u8 {
+                    continue;
+                }
+                let table = &state_tables.tables[table_index as usize];
+                for byte in 0..=255 {
+                    let walked = decode_bits(byte, node, &tree);
+                    assert_eq!(table.symbols[byte as usize], walked, "byte {byte:08b}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn kernels_decode_alike() {
+        let kernels = Kernel::ALL.iter().filter(|kernel| kernel.is_supported());
+        for &kernel in kernels {
+            for case in ALL_CASES {
+                let content = &case.request();
+                assert_eq!(
+                    decode_packet_with_kernel::<Classic>(content, kernel),
+                    decode_packet_with_kernel::<Classic>(content, Kernel::Generic),
+                    "{kernel:?} {}",
+                    case.name
+                );
+            }
+        }
     }
 }
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+use common::tree::parent_frequency;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -608,13 +703,17 @@ mod bench {
     use divan::counter::BytesCount;
     use divan::{black_box, Bencher};
 
-    #[divan::bench(sample_count = 100_000, args = [ALL_CASES[0], ALL_CASES[5]])]
-    fn gen_tree(bencher: Bencher, case: &Case) {
+    #[divan::bench(
+        sample_count = 100_000,
+        types = [Classic, InsertionOrder, ReverseInsertionOrder],
+        args = [ALL_CASES[0], ALL_CASES[5]]
+    )]
+    fn gen_tree<P: TieBreak>(bencher: Bencher, case: &Case) {
         let content = case.request();
         let packet = &Packet::new(&content);
         bencher.bench_local(move || {
             let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-            huffman_tree(packet, &mut tree);
+            huffman_tree::<P>(packet, &mut tree);
             black_box(tree);
         });
     }
@@ -625,7 +724,7 @@ mod bench {
         let packet = &Packet::new(&content);
         bencher.bench_local(move || {
             let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-            huffman_tree(packet, &mut tree);
+            huffman_tree::<Classic>(packet, &mut tree);
             let table = state_tables(&tree);
             black_box(table);
         });
@@ -636,12 +735,36 @@ mod bench {
         let content = case.request();
         let packet = &Packet::new(&content);
         let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-        huffman_tree(packet, &mut tree);
+        huffman_tree::<Classic>(packet, &mut tree);
+        let table = state_tables(&tree);
+        bencher
+            .counter(BytesCount::from(packet.decoded_bytes_len))
+            .bench_local(move || {
+                super::decode_message(
+                    Kernel::detect(),
+                    black_box(packet),
+                    &table,
+                    &mut Default::default(),
+                );
+            });
+    }
+
+    #[divan::bench(args = ALL_CASES)]
+    fn decode_message_generic(bencher: Bencher, case: &Case) {
+        let content = case.request();
+        let packet = &Packet::new(&content);
+        let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+        huffman_tree::<Classic>(packet, &mut tree);
         let table = state_tables(&tree);
         bencher
             .counter(BytesCount::from(packet.decoded_bytes_len))
             .bench_local(move || {
-                super::decode_message(black_box(packet), &table);
+                super::decode_message(
+                    Kernel::Generic,
+                    black_box(packet),
+                    &table,
+                    &mut Default::default(),
+                );
             });
     }
 
@@ -659,13 +782,18 @@ mod bench {
         let content2 = content.clone();
         let packet = &Packet::new(&content);
         let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-        huffman_tree(packet, &mut tree);
+        huffman_tree::<Classic>(packet, &mut tree);
         let table = state_tables(&tree);
         bencher
             .counter(BytesCount::from(2 * packet.decoded_bytes_len))
             .bench_local(move || {
                 black_box(super::decode_packet(black_box(&content2)));
-                black_box(super::decode_message(black_box(packet), &table));
+                super::decode_message(
+                    Kernel::detect(),
+                    black_box(packet),
+                    &table,
+                    &mut Default::default(),
+                );
             });
     }
 }
@@ -1,10 +1,26 @@
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::Packet;
+use common::profile::Phase;
+use common::tree::parent_frequency;
 
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
-    let tree = &huffman_tree(packet);
-    decode_message(packet, tree)
+    let tree = &time(Phase::Tree, || huffman_tree(packet));
+    time(Phase::Decode, || decode_message(packet, tree))
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || None,
+        |packet, tree| *tree = Some(huffman_tree(packet)),
+        |_| (),
+        |packet, tree, _| decode_message(packet, tree.as_ref().unwrap()),
+    );
 }
 
 fn decode_message(packet: &Packet, tree: &HeapNode) -> String {
@@ -56,8 +72,8 @@ fn huffman_tree(packet: &Packet) -> HeapNode {
     heap.pop()
 }
 
-fn symbols_heap(packet: &Packet) -> MinHeap<HeapNode> {
-    let mut heap = MinHeap::<HeapNode>::new();
+fn symbols_heap(packet: &Packet) -> MinHeapChecked<HeapNode> {
+    let mut heap = MinHeapChecked::<HeapNode>::new();
     let bytes = &packet.symbol_frequency_bytes;
     for i in 0..packet.symbol_count {
         let pos = (i as usize) * 8;
@@ -105,7 +121,7 @@ impl HeapNode {
     fn new_parent(left: Self, right: Self) -> Self {
         HeapNode {
             symbol: None,
-            frequency: left.frequency + right.frequency,
+            frequency: parent_frequency(left.frequency, right.frequency),
             left_child: Some(Box::new(left)),
             right_child: Some(Box::new(right)),
         }
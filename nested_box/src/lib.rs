@@ -1,2 +1,4 @@
+#![forbid(unsafe_code)]
+
 pub(crate) mod decoder;
-pub use decoder::decode_packet;
+pub use decoder::{decode_packet, profile};
@@ -1,2 +1,2 @@
 pub(crate) mod decoder;
-pub use decoder::decode_packet;
+pub use decoder::{decode_packet, profile};
@@ -1,450 +1,219 @@
-use common::test_cases::*;
-use std::hint::black_box;
-
-fn main() {
-    let content = ALL_CASES[1].request(); // this is the large medium case
-    for _ in 0..1_000_000 {
-        let result = decode_packet(black_box(&content));
-        black_box(result);
-    }
-}
-
-// NOTE: Copy some crate's decoder code here and put `` where needed for profiling.
-
-// state_table_unsafe
-use bitter::{BigEndianReader, BitReader};
-use common::min_heap::*;
-use common::packet::Packet;
-
-const MAX_TREE_LEN: usize = 23;
-
-pub fn decode_packet(content: &[u8]) -> String {
-    let packet = &Packet::new(content);
-    let mut tree = [HeapNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    let table = &state_tables(&tree);
-    decode_message(packet, table)
-}
-
-// fn decode_message(packet: &Packet, table: &StateTables) -> String {
-//     // Add slop space instead of checking write_index against decoded_len.
-//     let mut decoded: Vec<u8> = vec![0; packet.decoded_bytes_len as usize + 8];
-//     let mut write_index = 0usize;
-//     let mut state = 0;
-
-//     for &byte in packet.encoded_message {
-//         decoded[write_index] = table.tables[state].symbols[byte as usize][0];
-//         write_index += 1;
-//         for i in 1..8 {
-//             if table.tables[state].symbols[byte as usize][i] > 0 {
-//                 decoded[write_index] = table.tables[state].symbols[byte as usize][i];
-//                 write_index += 1;
-//             } else {
-//                 break;
-//             }
-//         }
-//         state = table.tables[state].symbols[byte as usize][8] as usize;
-//     }
-
-//     // Truncate decoded slop.
-//     unsafe {
-//         std::str::from_utf8_unchecked(&decoded[..packet.decoded_bytes_len as usize]).to_owned()
-//     }
-// }
-
-fn decode_message(packet: &Packet, table: &StateTables) -> String {
-    // Add slop space instead of checking write_index against decoded_len.
-    let mut decoded: Vec<u8> = Vec::with_capacity(packet.decoded_bytes_len as usize + 8);
-    let mut index = 0usize;
-    let mut state = 0;
-
-    let mut bit_reader = BigEndianReader::new(packet.encoded_message);
-
-    // Lookahead is 56bits
-    // Consume unbuffered bytes; guaranteed 7 8-bit indices per iteration.
-    // Since each lookup is not guaranteed to consume all bits try processing more.
-    while bit_reader.unbuffered_bytes_remaining() > 7 {
-        unsafe {
-            bit_reader.refill_lookahead_unchecked();
-            state = lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state);
-            state = lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state);
-            state = lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state);
-            state = lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state);
-            state = lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state);
-            state = lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state);
-            state = lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state);
-            // Since the checked `refill_lookahead` is more expensive than the lookup
-            // this improves performance on medium_small+ sized msgs.
-            while bit_reader.lookahead_bits() >= 8 {
-                state = lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state);
-            }
-        }
-    }
-
-    // Drain unbuffered bytes with safe refill.
-    while bit_reader.unbuffered_bytes_remaining() > 0 {
-        bit_reader.refill_lookahead();
-        state = unsafe { lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state) };
-    }
-
-    // Consume lookahead without refill or peek checks until the last byte.
-    while bit_reader.has_bits_remaining(8) {
-        state = unsafe { lookup_byte(&mut bit_reader, table, &mut index, &mut decoded, state) };
-    }
-
-    // Drain partial byte remaining bits with peek checks.
-    // should also use  `&& write_index < packet.decoded_bytes_len as usize` for equality with
-    // the safe and _ptr versions.
-    while bit_reader.has_bits_remaining(1) {
-        state = unsafe {
-            lookup_bits_unchecked(&mut bit_reader, table, &mut index, &mut decoded, state)
-        };
-    }
-
-    // Truncate decoded slop.
-    unsafe {
-        decoded.set_len(index);
-        let slice = std::slice::from_raw_parts(decoded.as_ptr(), decoded.len());
-        let mut decoded = std::str::from_utf8_unchecked(slice).to_owned();
-        decoded.truncate(packet.decoded_bytes_len as usize);
-        decoded
-    }
-}
-
-#[inline(always)]
-unsafe fn lookup_byte(
-    bit_reader: &mut BigEndianReader,
-    table: &StateTables,
-    write_index: &mut usize,
-    decoded: &mut [u8],
-    state: usize,
-) -> usize {
-    let index = bit_reader.peek(8) as usize;
-    let symbols = table
-        .tables
-        .get_unchecked(state)
-        .symbols
-        .get_unchecked(index);
-    let state = *symbols.get_unchecked(symbols.len() - 1);
-    copy_symbols_unchecked(symbols, write_index, decoded);
-    bit_reader.consume(8);
-    state as usize
-}
-
-#[inline(always)]
-unsafe fn lookup_bits_unchecked(
-    bit_reader: &mut BigEndianReader,
-    table: &StateTables,
-    write_index: &mut usize,
-    decoded: &mut [u8],
-    state: usize,
-) -> usize {
-    let lookahead_count = bit_reader.lookahead_bits().min(8);
-    let last_bits = bit_reader.peek(lookahead_count);
-    let index = bit_reader.peek(8) as usize;
-    let symbols = table
-        .tables
-        .get_unchecked(state)
-        .symbols
-        .get_unchecked(index);
-    let state = *symbols.get_unchecked(symbols.len() - 1);
-
-    copy_symbols_unchecked(symbols, write_index, decoded);
-
-    let bits_to_consume = lookahead_count.min(last_bits as u32);
-    bit_reader.consume(bits_to_consume);
-    state as usize
+// Runs one phase of a decoder in a loop for perf, valgrind and the like.
+//
+//     cargo build --release -p profile_bin
+//     perf record target/release/profile_bin --decoder fsm_unsafe_4channel --phase decode
+//
+// The defaults profile the whole `table_unsafe_ptr::decode_packet` on the
+// large_medium case a million times.
+
+use common::corpus::samples;
+use common::profile::Phase;
+use common::test_cases::ALL_CASES;
+use std::process::ExitCode;
+use std::time::Instant;
+
+const USAGE: &str = "\
+usage: profile_bin [--decoder CRATE] [--case NAME|FILE] [--iterations N] [--phase PHASE]
+
+  --decoder     a decoder crate, default table_unsafe_ptr
+  --case        an ALL_CASES group like large_medium, a capture like 55_3,
+                synthetic:<decoded_len> or a packet file, default large_medium
+  --iterations  default 1000000
+  --phase       parse, tree, table, decode or packet (all of them), default packet";
+
+struct Decoder {
+    name: &'static str,
+    /// Whether it builds lookup tables, the table phase is empty otherwise.
+    tables: bool,
+    profile: fn(&[u8], Phase, u64),
+}
+
+macro_rules! decoders {
+    ($($name:ident: $tables:expr),* $(,)?) => {
+        &[$(Decoder { name: stringify!($name), tables: $tables, profile: $name::profile }),*]
+    };
 }
 
-#[inline(always)]
-unsafe fn copy_symbols_unchecked(symbols: &[u8], write_index: &mut usize, decoded: &mut [u8]) {
-    *decoded.as_mut_ptr().add(*write_index) = *symbols.get_unchecked(0);
-    *write_index += 1;
-    for i in 1..8 {
-        if *symbols.get_unchecked(i) > 0 {
-            *decoded.as_mut_ptr().add(*write_index) = *symbols.get_unchecked(i);
-            *write_index += 1;
-        } else {
-            break;
+const DECODERS: &[Decoder] = decoders![
+    baseline: false,
+    nested_box: false,
+    nested_unsafe_box: false,
+    flat_index: false,
+    flat_ptr: false,
+    flat_unsafe_ptr: false,
+    table_index: true,
+    table_ptr: true,
+    table_unsafe_ptr: true,
+    table_single_index: true,
+    table_single_unsafe_ptr: true,
+    fsm: true,
+    fsm_2channel: true,
+    fsm_3channel: true,
+    fsm_4channel: true,
+    fsm_unsafe: true,
+    fsm_unsafe_2channel: true,
+    fsm_unsafe_3channel: true,
+    fsm_unsafe_4channel: true,
+    fsm_unsafe_5channel: true,
+];
+
+struct Options {
+    decoder: &'static Decoder,
+    case: String,
+    iterations: u64,
+    phase: Phase,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            return ExitCode::from(2);
         }
-    }
-}
-
-fn huffman_tree(packet: &Packet, tree: &mut [HeapNode; MAX_TREE_LEN]) {
-    let mut heap = symbols_heap(packet);
-    let mut right_index = 2 * packet.symbol_count as usize - 2;
-
-    // Successively move two smallest nodes from heap to tree
-    loop {
-        let left = heap.pop();
-        let right = heap.pop();
-        let parent_frequency = left.frequency + right.frequency;
-
-        // Add popped nodes to the tree by setting the existing node values
-        tree[right_index - 1].symbol = left.symbol;
-        tree[right_index].symbol = right.symbol;
-        tree[right_index - 1].left_index = left.left_index;
-        tree[right_index].left_index = right.left_index;
-        tree[right_index - 1].right_index = left.right_index;
-        tree[right_index].right_index = right.right_index;
-        tree[right_index - 1].index = Some(right_index - 1);
-        tree[right_index].index = Some(right_index);
-
-        if right_index < 3 {
-            // Move the last node (the root) to the tree
-            tree[0].symbol = None;
-            tree[0].left_index = 1;
-            tree[0].right_index = 2;
-            tree[0].index = Some(0);
-            break;
-        } else {
-            // Add a parent node to the heap for ordering
-            let parent =
-                HeapNode::new_parent(parent_frequency, right_index as u8 - 1, right_index as u8);
-            right_index -= 2;
-            heap.push(parent);
-        }
-    }
-}
-
-fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
-    let bytes = &packet.symbol_frequency_bytes;
-    for chunk in bytes.chunks_exact(8) {
-        let frequency = u32::from_le_bytes(chunk[..4].try_into().unwrap());
-        let symbol = chunk[4];
-        heap.push(HeapNode::new(Some(symbol), frequency));
-    }
-    heap
-}
-
-#[derive(Clone, Copy)]
-struct SymbolTable {
-    symbols: [[u8; 9]; 256],
-}
-
-impl Default for SymbolTable {
-    fn default() -> Self {
-        SymbolTable {
-            symbols: [[0u8; 9]; 256],
+    };
+    let content = match load_case(&options.case) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::from(2);
         }
-    }
-}
-
-#[repr(align(64))]
-struct StateTables {
-    tables: Vec<SymbolTable>,
-}
-
-#[inline(always)]
-fn state_tables(tree: &[HeapNode; MAX_TREE_LEN]) -> StateTables {
-    let (table_indices, child_states, internal_count) = child_states(tree);
-
-    let mut state_tables = StateTables {
-        tables: vec![SymbolTable::default(); internal_count as usize],
     };
 
-    let reference_index = child_states.iter().position(|&x| x == 3).unwrap();
-    let reference = gen_full_range(
-        &tree[reference_index],
-        tree,
-        &table_indices,
-        &SymbolTable::default(),
+    let start = Instant::now();
+    (options.decoder.profile)(&content, options.phase, options.iterations);
+    let elapsed = start.elapsed();
+    eprintln!(
+        "{} {} on {}: {} iterations in {:.3?}, {:.3?} each",
+        options.decoder.name,
+        options.phase,
+        options.case,
+        options.iterations,
+        elapsed,
+        elapsed.div_f64(options.iterations.max(1) as f64),
     );
-
-    for i in 0..MAX_TREE_LEN {
-        let table_index = table_indices[i];
-        if table_index == MAX_TREE_LEN as u8 {
-            continue;
-        }
-        let start_node = &tree[i];
-        let table_fn = match child_states[i] {
-            1 => copy_lower_gen_upper,
-            2 => gen_lower_copy_upper,
-            3 => copy_full_range,
-            _ => gen_full_range,
-        };
-        state_tables.tables[table_index as usize] =
-            table_fn(start_node, tree, &table_indices, &reference);
-    }
-    state_tables
-}
-
-#[inline(always)]
-fn child_states(tree: &[HeapNode; MAX_TREE_LEN]) -> ([u8; MAX_TREE_LEN], [u8; MAX_TREE_LEN], u8) {
-    let mut table_indices = [MAX_TREE_LEN as u8; MAX_TREE_LEN];
-    let mut child_states = [MAX_TREE_LEN as u8; MAX_TREE_LEN];
-    let mut internal_count = 0;
-    tree.iter().enumerate().for_each(|(i, node)| {
-        if node.symbol.is_none() && node.index.is_some() {
-            table_indices[i] = internal_count;
-            internal_count += 1;
-        };
-        let left_state = tree[node.left_index as usize].symbol.is_some() as u8;
-        let right_state = tree[node.right_index as usize].symbol.is_some() as u8;
-        let child_state = left_state + 2 * right_state;
-        child_states[i] = child_state;
-    });
-    (table_indices, child_states, internal_count)
+    ExitCode::SUCCESS
 }
 
-#[inline(always)]
-fn copy_lower_gen_upper(
-    start_node: &HeapNode,
-    tree: &[HeapNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-    reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    table.symbols[0..=127].copy_from_slice(&reference_table.symbols[0..=127]);
-    table.symbols[0..=127]
-        .iter_mut()
-        .for_each(|x| x[0] = tree[start_node.left_index as usize].symbol.unwrap());
-
-    for byte in 128u8..=255 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
-    }
-    table
-}
-
-#[inline(always)]
-fn gen_lower_copy_upper(
-    start_node: &HeapNode,
-    tree: &[HeapNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-    reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    table.symbols[128..=255].copy_from_slice(&reference_table.symbols[128..=255]);
-    table.symbols[128..=255]
-        .iter_mut()
-        .for_each(|x| x[0] = tree[start_node.right_index as usize].symbol.unwrap());
-
-    for byte in 0u8..=127 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
-    }
-    table
-}
-
-#[inline(always)]
-fn copy_full_range(
-    start_node: &HeapNode,
-    tree: &[HeapNode; MAX_TREE_LEN],
-    _table_indices: &[u8; MAX_TREE_LEN],
-    reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable {
-        symbols: reference_table.symbols,
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        decoder: find_decoder("table_unsafe_ptr")?,
+        case: "large_medium".to_owned(),
+        iterations: 1_000_000,
+        phase: Phase::Packet,
     };
-    table.symbols[0..=127]
-        .iter_mut()
-        .for_each(|x| x[0] = tree[start_node.left_index as usize].symbol.unwrap());
-    table.symbols[128..=255]
-        .iter_mut()
-        .for_each(|x| x[0] = tree[start_node.right_index as usize].symbol.unwrap());
-    table
-}
-
-#[inline(always)]
-fn gen_full_range(
-    start_node: &HeapNode,
-    tree: &[HeapNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-    _reference_table: &SymbolTable,
-) -> SymbolTable {
-    let mut table = SymbolTable::default();
-    for byte in 0u8..=255 {
-        decode_bits(
-            byte,
-            start_node,
-            &mut table.symbols[byte as usize],
-            tree,
-            table_indices,
-        );
-    }
-    table
-}
-
-#[inline(always)]
-fn decode_bits<'a>(
-    mut bits: u8,
-    mut node: &'a HeapNode,
-    symbols: &mut [u8; 9],
-    tree: &'a [HeapNode; MAX_TREE_LEN],
-    table_indices: &[u8; MAX_TREE_LEN],
-) {
-    let mut write_index = 0;
-
-    for _ in 0..=7 {
-        node = match bits >> 7 {
-            0 => unsafe { tree.get_unchecked(node.left_index as usize) },
-            _ => unsafe { tree.get_unchecked(node.right_index as usize) },
-        };
-        if let Some(symbol) = node.symbol {
-            symbols[write_index] = symbol;
-            write_index += 1;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            return Err(String::new());
         }
-        bits <<= 1;
-    }
-    symbols[symbols.len() - 1] = if node.symbol.is_some() {
-        0
-    } else {
-        unsafe { *table_indices.get_unchecked(node.index.unwrap()) }
-    };
-}
-
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-struct HeapNode {
-    left_index: u8,
-    right_index: u8,
-    symbol: Option<u8>,
-    frequency: u32,
-    index: Option<usize>,
-}
-impl MinHeapNode for HeapNode {
-    fn frequency(&self) -> u32 {
-        self.frequency
-    }
-}
-impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.frequency.cmp(&other.frequency)
-    }
-}
-impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-impl HeapNode {
-    fn new(symbol: Option<u8>, frequency: u32) -> Self {
-        Self {
-            left_index: 1,
-            right_index: 2,
-            symbol,
-            frequency,
-            index: None,
+        let value = args.next().ok_or(format!("missing value for {arg}"))?;
+        match arg.as_str() {
+            "--decoder" => options.decoder = find_decoder(value)?,
+            "--case" => options.case = value.clone(),
+            "--iterations" => {
+                options.iterations = value
+                    .parse()
+                    .map_err(|_| format!("invalid iterations {value}"))?
+            }
+            "--phase" => {
+                options.phase = Phase::from_name(value).ok_or(format!("unknown phase {value}"))?
+            }
+            _ => return Err(format!("unknown argument {arg}")),
         }
     }
-    fn new_parent(frequency: u32, left_index: u8, right_index: u8) -> Self {
-        Self {
-            left_index,
-            right_index,
-            symbol: None,
-            frequency,
-            index: None,
+    if options.phase == Phase::Table && !options.decoder.tables {
+        return Err(format!("{} builds no tables", options.decoder.name));
+    }
+    Ok(options)
+}
+
+fn find_decoder(name: &str) -> Result<&'static Decoder, String> {
+    DECODERS
+        .iter()
+        .find(|decoder| decoder.name == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = DECODERS.iter().map(|decoder| decoder.name).collect();
+            format!("unknown decoder {name}, one of: {}", names.join(", "))
+        })
+}
+
+fn load_case(case: &str) -> Result<Vec<u8>, String> {
+    if let Some(decoded_len) = case.strip_prefix("synthetic:") {
+        let decoded_len = decoded_len
+            .parse()
+            .map_err(|_| format!("invalid synthetic length {decoded_len}"))?;
+        return Ok(common::synthetic::market_packet(0, decoded_len));
+    }
+    // `ALL_CASES` names are like `large_medium (msg_len=33.3k)`.
+    if let Some(case) = ALL_CASES
+        .iter()
+        .find(|all_case| all_case.name.split(' ').next() == Some(case))
+    {
+        return Ok(case.request());
+    }
+    if let Some(sample) = samples().iter().find(|sample| sample.name == case) {
+        return Ok(sample.request());
+    }
+    std::fs::read(case).map_err(|err| format!("unknown case {case}, reading it as a file: {err}"))
+}
+
+// =========================================================
+// MARK: Unit Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_args() {
+        let options = parse_args(&args(&[
+            "--decoder",
+            "fsm",
+            "--phase",
+            "decode",
+            "--iterations",
+            "10",
+        ]))
+        .unwrap();
+        assert_eq!(options.decoder.name, "fsm");
+        assert_eq!(options.phase, Phase::Decode);
+        assert_eq!(options.iterations, 10);
+        assert_eq!(options.case, "large_medium");
+
+        assert!(parse_args(&args(&["--decoder", "fsm_static"])).is_err());
+        assert!(parse_args(&args(&["--decoder", "flat_index", "--phase", "table"])).is_err());
+        assert!(parse_args(&args(&["--iterations"])).is_err());
+    }
+
+    #[test]
+    fn loads_cases() {
+        let large_medium = load_case("large_medium").unwrap();
+        assert_eq!(load_case("55_3").unwrap(), large_medium);
+        let path = samples()
+            .iter()
+            .find(|sample| sample.name == "55_3")
+            .unwrap();
+        assert_eq!(
+            load_case(path.path.to_str().unwrap()).unwrap(),
+            large_medium
+        );
+        assert!(load_case("synthetic:1000").unwrap().len() > 24);
+        assert!(load_case("huge").is_err());
+    }
+
+    #[test]
+    fn profiles_every_phase() {
+        let content = load_case("small").unwrap();
+        for decoder in DECODERS {
+            for phase in Phase::ALL {
+                (decoder.profile)(&content, phase, 2);
+            }
         }
     }
 }
@@ -1,16 +1,37 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::Packet;
+use common::profile::Phase;
+use common::tree::{parent_frequency, MAX_TABLE_DEPTH};
 
 use bitter::{BigEndianReader, BitReader};
 
 const MAX_TREE_LEN: usize = 23;
 
+/// Panics if a code is longer than `MAX_TABLE_DEPTH` bits.
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
     let mut tree = [HeapNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    let table = &symbols_table(&tree);
-    decode_message(packet, table)
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    let table = &time(Phase::Table, || symbols_table(&tree));
+    time(Phase::Decode, || decode_message(packet, table))
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [HeapNode::default(); MAX_TREE_LEN],
+        huffman_tree,
+        |tree| symbols_table(tree),
+        |packet, _, table| decode_message(packet, table),
+    );
 }
 
 fn decode_message(packet: &Packet, table: &SymbolTable) -> String {
@@ -57,7 +78,7 @@ fn decode_message(packet: &Packet, table: &SymbolTable) -> String {
 
     // Truncate decoded slop.
     let slice = &decoded[..];
-    let mut decoded = std::str::from_utf8(slice).unwrap().to_owned();
+    let mut decoded = core::str::from_utf8(slice).unwrap().to_owned();
     decoded.truncate(packet.decoded_bytes_len as usize);
     decoded
 }
@@ -110,7 +131,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [HeapNode; MAX_TREE_LEN]) {
     loop {
         let left = heap.pop();
         let right = heap.pop();
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
 
         // Add popped nodes to the tree by setting the existing node values
         tree[right_index - 1].symbol = left.symbol;
@@ -136,8 +157,8 @@ fn huffman_tree(packet: &Packet, tree: &mut [HeapNode; MAX_TREE_LEN]) {
     }
 }
 
-fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
+fn symbols_heap(packet: &Packet) -> MinHeapChecked<HeapNode> {
+    let mut heap = MinHeapChecked::<HeapNode>::new();
     let bytes = &packet.symbol_frequency_bytes;
     for chunk in bytes.chunks_exact(8) {
         let frequency = u32::from_le_bytes(chunk[..4].try_into().unwrap());
@@ -195,6 +216,11 @@ fn symbols_table(tree: &[HeapNode]) -> SymbolTable {
             }
             bits <<= 1;
         }
+        // Without a symbol in 8 bits a lookup would consume nothing.
+        assert!(
+            bits_used > 0,
+            "the table decodes codes of up to {MAX_TABLE_DEPTH} bits"
+        );
         table.bits_used[byte as usize] = bits_used;
     }
     table
@@ -213,12 +239,12 @@ impl MinHeapNode for HeapNode {
     }
 }
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -254,6 +280,13 @@ mod tests {
         assert_eq!(decoded_message, EXPECTED_MESSAGE);
     }
 
+    #[test]
+    #[should_panic(expected = "codes of up to 8 bits")]
+    fn rejects_deep_codes() {
+        let content = common::synthetic::encode_packet(&fibonacci_message());
+        super::decode_packet(&content);
+    }
+
     #[test]
     fn gen_table() {
         let content = TEST_BYTES;
@@ -269,7 +302,9 @@ mod tests {
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -1,5 +1,12 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::Packet;
+use common::profile::Phase;
+use common::tree::{parent_frequency, MAX_TABLE_DEPTH};
 
 use bitter::{BigEndianReader, BitReader};
 
@@ -8,9 +15,22 @@ const MAX_TREE_LEN: usize = 23;
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    let table = &symbols_table(&tree);
-    decode_message(packet, table)
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    let table = &time(Phase::Table, || symbols_table(&tree));
+    time(Phase::Decode, || decode_message(packet, table))
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree,
+        symbols_table,
+        |packet, _, table| decode_message(packet, table),
+    );
 }
 
 fn decode_message(packet: &Packet, table: &SymbolTable) -> String {
@@ -57,7 +77,7 @@ fn decode_message(packet: &Packet, table: &SymbolTable) -> String {
 
     // Truncate decoded slop.
     let slice = &decoded[..];
-    let mut decoded = std::str::from_utf8(slice).unwrap().to_owned();
+    let mut decoded = core::str::from_utf8(slice).unwrap().to_owned();
     decoded.truncate(packet.decoded_bytes_len as usize);
     decoded
 }
@@ -110,7 +130,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
     loop {
         let left = heap.pop();
         let right = heap.pop();
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
 
         // Add popped nodes to the tree by setting the existing node values
         tree[right_index - 1].symbol = left.symbol;
@@ -195,6 +215,11 @@ fn symbols_table(tree: &[TreeNode; 23]) -> SymbolTable {
             }
             bits <<= 1;
         }
+        // Without a symbol in 8 bits a lookup would consume nothing.
+        assert!(
+            bits_used > 0,
+            "the table decodes codes of up to {MAX_TABLE_DEPTH} bits"
+        );
         table.bits_used[byte as usize] = bits_used;
     }
     table
@@ -213,12 +238,12 @@ impl MinHeapNode for HeapNode {
     }
 }
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -250,8 +275,8 @@ struct TreeNode {
 impl Default for TreeNode {
     fn default() -> Self {
         Self {
-            left_ptr: std::ptr::null(),
-            right_ptr: std::ptr::null(),
+            left_ptr: core::ptr::null(),
+            right_ptr: core::ptr::null(),
             symbol: None,
         }
     }
@@ -273,7 +298,9 @@ mod tests {
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -1,5 +1,11 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::Packet;
+use common::profile::Phase;
 
 use bitter::{BigEndianReader, BitReader};
 
@@ -8,9 +14,24 @@ const MAX_TREE_LEN: usize = 23;
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
     let mut tree = [HeapNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    let (max_depth, table) = &symbol_table(&tree);
-    decode_message(packet, *max_depth as u32, table)
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    let (max_depth, table) = &time(Phase::Table, || symbol_table(&tree));
+    time(Phase::Decode, || {
+        decode_message(packet, *max_depth as u32, table)
+    })
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [HeapNode::default(); MAX_TREE_LEN],
+        huffman_tree,
+        symbol_table,
+        |packet, _, table| decode_message(packet, table.0 as u32, &table.1),
+    );
 }
 
 fn decode_message(packet: &Packet, peek_count: u32, table: &[(u8, u8)]) -> String {
@@ -76,7 +97,7 @@ fn decode_message(packet: &Packet, peek_count: u32, table: &[(u8, u8)]) -> Strin
 
     // Truncate decoded slop.
     let slice = &decoded[..];
-    let mut decoded = std::str::from_utf8(slice).unwrap().to_owned();
+    let mut decoded = core::str::from_utf8(slice).unwrap().to_owned();
     decoded.truncate(packet.decoded_bytes_len as usize);
     decoded
 }
@@ -111,7 +132,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [HeapNode; MAX_TREE_LEN]) {
         process_heap_node(&left, tree, tree_index);
 
         // Add a parent node to the heap for ordering
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
         let parent = HeapNode::new_parent(parent_frequency, tree_index as u8);
         heap.push(parent);
     }
@@ -125,8 +146,8 @@ fn huffman_tree(packet: &Packet, tree: &mut [HeapNode; MAX_TREE_LEN]) {
 }
 
 #[inline(never)]
-fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
+fn symbols_heap(packet: &Packet) -> MinHeapChecked<HeapNode> {
+    let mut heap = MinHeapChecked::<HeapNode>::new();
     let bytes = &packet.symbol_frequency_bytes;
     for chunk in bytes.chunks_exact(8) {
         let frequency = u32::from_le_bytes(chunk[..4].try_into().unwrap());
@@ -188,12 +209,12 @@ impl MinHeapNode for HeapNode {
     }
 }
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -243,7 +264,10 @@ mod tests {
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+use common::tree::parent_frequency;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -1,5 +1,11 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::Packet;
+use common::profile::Phase;
 
 use bitter::{BigEndianReader, BitReader};
 
@@ -8,9 +14,24 @@ const MAX_TREE_LEN: usize = 23;
 pub fn decode_packet(content: &[u8]) -> String {
     let packet = &Packet::new(content);
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    let (max_depth, table) = &symbol_table(&tree);
-    decode_message(packet, *max_depth as u32, table)
+    time(Phase::Tree, || huffman_tree(packet, &mut tree));
+    let (max_depth, table) = &time(Phase::Table, || symbol_table(&tree));
+    time(Phase::Decode, || {
+        decode_message(packet, *max_depth as u32, table)
+    })
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree,
+        symbol_table,
+        |packet, _, table| decode_message(packet, table.0 as u32, &table.1),
+    );
 }
 
 fn decode_message(packet: &Packet, peek_count: u32, table: &[(u8, u8)]) -> String {
@@ -85,8 +106,8 @@ fn decode_message(packet: &Packet, peek_count: u32, table: &[(u8, u8)]) -> Strin
     // Truncate decoded slop.
     unsafe {
         decoded.set_len(write_index);
-        let slice = std::slice::from_raw_parts(decoded.as_ptr(), decoded.len());
-        let mut decoded = std::str::from_utf8_unchecked(slice).to_owned();
+        let slice = core::slice::from_raw_parts(decoded.as_ptr(), decoded.len());
+        let mut decoded = core::str::from_utf8_unchecked(slice).to_owned();
         decoded.truncate(packet.decoded_bytes_len as usize);
         decoded
     }
@@ -126,7 +147,7 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
         process_heap_node(&left, tree, tree_index);
 
         // Add a parent node to the heap for ordering
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
         let parent = HeapNode::new_parent(parent_frequency, tree_index as u8);
         heap.push(parent);
     }
@@ -159,8 +180,8 @@ fn symbol_table(tree: &[TreeNode; MAX_TREE_LEN]) -> (u8, Vec<(u8, u8)>) {
     for index in 0..MAX_TREE_LEN {
         let node = &tree[index];
         if node.symbol.is_none() {
-            let left_index =
-                (node.left_ptr as usize - tree.as_ptr() as usize) / std::mem::size_of::<TreeNode>();
+            let left_index = (node.left_ptr as usize - tree.as_ptr() as usize)
+                / core::mem::size_of::<TreeNode>();
 
             let depth = depths[index] + 1;
             max_depth = max_depth.max(depth);
@@ -207,12 +228,12 @@ impl MinHeapNode for HeapNode {
 }
 
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -244,8 +265,8 @@ struct TreeNode {
 impl Default for TreeNode {
     fn default() -> Self {
         Self {
-            left_ptr: std::ptr::null(),
-            right_ptr: std::ptr::null(),
+            left_ptr: core::ptr::null(),
+            right_ptr: core::ptr::null(),
             symbol: None,
         }
     }
@@ -278,7 +299,10 @@ mod tests {
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+use common::tree::parent_frequency;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -1,21 +1,135 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use common::kernel::Kernel;
+use common::metrics::time;
 use common::min_heap::*;
 use common::packet::Packet;
+use common::profile::Phase;
+use common::tree::{parent_frequency, MAX_TABLE_DEPTH};
 
 use bitter::{BigEndianReader, BitReader};
 
 const MAX_TREE_LEN: usize = 23;
 
+/// Panics if a code is longer than `MAX_TABLE_DEPTH` bits.
 pub fn decode_packet(content: &[u8]) -> String {
+    decode_packet_with::<Classic>(content)
+}
+
+/// `decode_packet` for packets whose encoder breaks frequency ties with `P`.
+pub fn decode_packet_with<P: TieBreak>(content: &[u8]) -> String {
+    decode_packet_with_kernel::<P>(content, Kernel::detect())
+}
+
+/// `decode_packet_with` forced to decode with `kernel`.
+///
+/// Panics if the CPU doesn't support `kernel`.
+pub fn decode_packet_with_kernel<P: TieBreak>(content: &[u8], kernel: Kernel) -> String {
+    assert!(kernel.is_supported(), "{kernel:?} isn't supported");
     let packet = &Packet::new(content);
     let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-    huffman_tree(packet, &mut tree);
-    let table = &symbols_table(&tree);
-    decode_message(packet, table)
+    time(Phase::Tree, || huffman_tree::<P>(packet, &mut tree));
+    let table = &time(Phase::Table, || symbols_table(&tree));
+    let mut decoded = Vec::new();
+    time(Phase::Decode, || {
+        decode_message(kernel, packet, table, &mut decoded)
+    });
+    unsafe { String::from_utf8_unchecked(decoded) }
+}
+
+/// Runs `phase` of `decode_packet` `iterations` times, for `profile_bin`.
+pub fn profile(content: &[u8], phase: Phase, iterations: u64) {
+    common::profile::run(
+        content,
+        phase,
+        iterations,
+        || [TreeNode::default(); MAX_TREE_LEN],
+        huffman_tree::<Classic>,
+        symbols_table,
+        |packet, _, table| {
+            let mut decoded = Vec::new();
+            decode_message(Kernel::detect(), packet, table, &mut decoded);
+            decoded
+        },
+    );
+}
+
+/// The lookup table of a packet's symbol table, for decoding packets that
+/// share a symbol table.
+pub struct Decoder {
+    symbol_frequency_bytes: Vec<u8>,
+    table: Box<SymbolTable>,
+    kernel: Kernel,
+}
+
+impl Decoder {
+    pub fn new(packet: &Packet) -> Self {
+        Self::new_with::<Classic>(packet)
+    }
+
+    /// `new` for packets whose encoder breaks frequency ties with `P`.
+    pub fn new_with<P: TieBreak>(packet: &Packet) -> Self {
+        let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+        time(Phase::Tree, || huffman_tree::<P>(packet, &mut tree));
+        Decoder {
+            symbol_frequency_bytes: packet.symbol_frequency_bytes.to_vec(),
+            table: Box::new(time(Phase::Table, || symbols_table(&tree))),
+            kernel: Kernel::detect(),
+        }
+    }
+
+    /// The symbol table of the packet the decoder was created from.
+    pub fn symbol_frequency_bytes(&self) -> &[u8] {
+        &self.symbol_frequency_bytes
+    }
+
+    /// Decodes `packet` into `decoded`, reusing its allocation.
+    ///
+    /// Panics if `packet` doesn't have the symbol table the decoder was created
+    /// from.
+    pub fn decode_into(&self, packet: &Packet, decoded: &mut Vec<u8>) {
+        assert_eq!(
+            packet.symbol_frequency_bytes, self.symbol_frequency_bytes,
+            "the packet has a different symbol table"
+        );
+        time(Phase::Decode, || {
+            decode_message(self.kernel, packet, &self.table, decoded)
+        });
+    }
+}
+
+/// `kernel` must be supported by the CPU.
+fn decode_message(kernel: Kernel, packet: &Packet, table: &SymbolTable, decoded: &mut Vec<u8>) {
+    match kernel {
+        Kernel::Generic => decode_message_kernel::<false>(packet, table, decoded),
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Haswell => unsafe { decode_message_haswell(packet, table, decoded) },
+    }
 }
 
-fn decode_message(packet: &Packet, table: &SymbolTable) -> String {
-    // Add slop space instead of checking write_index against decoded_len.
-    let mut decoded: Vec<u8> = Vec::with_capacity(packet.decoded_bytes_len as usize + 8);
+// The same loop with BMI2's flagless shifts for the bit reader, LZCNT and
+// 8-byte symbol stores.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,bmi1,bmi2,lzcnt")]
+unsafe fn decode_message_haswell(packet: &Packet, table: &SymbolTable, decoded: &mut Vec<u8>) {
+    decode_message_kernel::<true>(packet, table, decoded)
+}
+
+#[inline(always)]
+fn decode_message_kernel<const WIDE: bool>(
+    packet: &Packet,
+    table: &SymbolTable,
+    decoded: &mut Vec<u8>,
+) {
+    // Add slop space instead of checking write_index against decoded_len on
+    // every lookup.
+    let decoded_len = packet.decoded_bytes_len as usize;
+    decoded.clear();
+    decoded.reserve(decoded_len + 8);
+    // Write through the `Vec`'s pointer, which covers its whole capacity, not
+    // through the empty slice it derefs to.
+    let out = decoded.as_mut_ptr();
     let mut write_index = 0usize;
 
     let mut bit_reader = BigEndianReader::new(packet.encoded_message);
@@ -23,73 +137,70 @@ fn decode_message(packet: &Packet, table: &SymbolTable) -> String {
     // Lookahead is 56bits
     // Consume unbuffered bytes; guaranteed 7 8-bit indices per iteration.
     // Since each lookup is not guaranteed to consume all bits try processing more.
-    while bit_reader.unbuffered_bytes_remaining() > 7 {
+    // An iteration consumes at most 63 bits, one symbol each, so it only runs
+    // while they fit even if the bitstream has more symbols than the header.
+    while bit_reader.unbuffered_bytes_remaining() > 7 && write_index + 64 <= decoded_len {
         unsafe {
             bit_reader.refill_lookahead_unchecked();
-            lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded);
-            lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded);
-            lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded);
-            lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded);
-            lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded);
-            lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded);
-            lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded);
+            lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out);
+            lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out);
+            lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out);
+            lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out);
+            lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out);
+            lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out);
+            lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out);
             // Since the checked `refill_lookahead` is more expensive than the lookup
             // this improves performance on medium_small+ sized msgs.
             while bit_reader.lookahead_bits() >= 8 {
-                lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded);
+                lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out);
             }
         }
     }
 
+    // The remaining lookups each start below decoded_len, their up to 8 bytes
+    // stay within the `decoded_len + 8` reserved.
+
     // Drain unbuffered bytes with safe refill.
-    while bit_reader.unbuffered_bytes_remaining() > 0 {
+    while bit_reader.unbuffered_bytes_remaining() > 0 && write_index < decoded_len {
         bit_reader.refill_lookahead();
-        unsafe { lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded) };
+        unsafe { lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out) };
     }
 
     // Consume lookahead without refill or peek checks until the last byte.
-    while bit_reader.has_bits_remaining(8) {
-        unsafe { lookup_byte_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded) }
+    while bit_reader.has_bits_remaining(8) && write_index < decoded_len {
+        unsafe { lookup_byte_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out) }
     }
 
     // Drain partial byte remaining bits with peek checks.
-    // should also use  `&& write_index < packet.decoded_bytes_len as usize` for equality with
-    // the safe and _ptr versions.
-    while bit_reader.has_bits_remaining(1) {
-        unsafe { lookup_bits_unchecked(&mut bit_reader, table, &mut write_index, &mut decoded) }
+    while bit_reader.has_bits_remaining(1) && write_index < decoded_len {
+        unsafe { lookup_bits_unchecked::<WIDE>(&mut bit_reader, table, &mut write_index, out) }
     }
 
     // Truncate decoded slop.
-    unsafe {
-        decoded.set_len(write_index);
-        let slice = std::slice::from_raw_parts(decoded.as_ptr(), decoded.len());
-        let mut decoded = std::str::from_utf8_unchecked(slice).to_owned();
-        decoded.truncate(packet.decoded_bytes_len as usize);
-        decoded
-    }
+    unsafe { decoded.set_len(write_index.min(decoded_len)) };
 }
 
 #[inline(always)]
-unsafe fn lookup_byte_unchecked(
+unsafe fn lookup_byte_unchecked<const WIDE: bool>(
     bit_reader: &mut BigEndianReader,
     table: &SymbolTable,
     write_index: &mut usize,
-    decoded: &mut [u8],
+    out: *mut u8,
 ) {
     let index = bit_reader.peek(8) as usize;
     let symbols = table.symbols.get_unchecked(index);
     let used_bits = *table.bits_used.get_unchecked(index);
 
-    copy_symbols_unchecked(symbols, write_index, decoded);
+    copy_symbols_unchecked::<WIDE>(symbols, write_index, out);
     bit_reader.consume(used_bits as u32);
 }
 
 #[inline(always)]
-unsafe fn lookup_bits_unchecked(
+unsafe fn lookup_bits_unchecked<const WIDE: bool>(
     bit_reader: &mut BigEndianReader,
     table: &SymbolTable,
     write_index: &mut usize,
-    decoded: &mut [u8],
+    out: *mut u8,
 ) {
     let lookahead_count = bit_reader.lookahead_bits().min(8);
     let last_bits = bit_reader.peek(lookahead_count);
@@ -98,19 +209,39 @@ unsafe fn lookup_bits_unchecked(
     let symbols = table.symbols.get_unchecked(index);
     let used_bits = *table.bits_used.get_unchecked(index);
 
-    copy_symbols_unchecked(symbols, write_index, decoded);
+    copy_symbols_unchecked::<WIDE>(symbols, write_index, out);
 
     let bits_to_consume = lookahead_count.min(used_bits as u32);
     bit_reader.consume(bits_to_consume);
 }
 
+/// Writes the symbols of a lookup at `write_index` and advances it.
+///
+/// `out` must be valid for writes up to `write_index + 8`, the wide store
+/// writes all 8 bytes whatever the symbol count.
 #[inline(always)]
-unsafe fn copy_symbols_unchecked(symbols: &[u8], write_index: &mut usize, decoded: &mut [u8]) {
-    *decoded.as_mut_ptr().add(*write_index) = *symbols.get_unchecked(0);
+unsafe fn copy_symbols_unchecked<const WIDE: bool>(
+    symbols: &[u8; 6],
+    write_index: &mut usize,
+    out: *mut u8,
+) {
+    if WIDE {
+        // Store all 6 symbols and 2 zeros at once, the bytes past the count are
+        // overwritten by the next lookup or truncated with the slop.
+        let low = symbols.as_ptr().cast::<u32>().read_unaligned();
+        let high = symbols.as_ptr().add(4).cast::<u16>().read_unaligned();
+        let symbol_block = u64::from_le(low as u64 | (high as u64) << 32);
+        out.add(*write_index)
+            .cast::<u64>()
+            .write_unaligned(symbol_block.to_le());
+        *write_index += 8 - (symbol_block.leading_zeros() / 8) as usize;
+        return;
+    }
+    *out.add(*write_index) = *symbols.get_unchecked(0);
     *write_index += 1;
     for i in 1..6 {
         if *symbols.get_unchecked(i) > 0 {
-            *decoded.as_mut_ptr().add(*write_index) = *symbols.get_unchecked(i);
+            *out.add(*write_index) = *symbols.get_unchecked(i);
             *write_index += 1;
         } else {
             break;
@@ -118,15 +249,15 @@ unsafe fn copy_symbols_unchecked(symbols: &[u8], write_index: &mut usize, decode
     }
 }
 
-fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
-    let mut heap = symbols_heap(packet);
+fn huffman_tree<P: TieBreak>(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
+    let mut heap = symbols_heap::<P>(packet);
     let mut right_index = 2 * packet.symbol_count as usize - 2;
 
     // Successively move two smallest nodes from heap to tree
     loop {
         let left = heap.pop();
         let right = heap.pop();
-        let parent_frequency = left.frequency + right.frequency;
+        let parent_frequency = parent_frequency(left.frequency, right.frequency);
 
         // Add popped nodes to the tree by setting the existing node values
         tree[right_index - 1].symbol = left.symbol;
@@ -152,8 +283,8 @@ fn huffman_tree(packet: &Packet, tree: &mut [TreeNode; MAX_TREE_LEN]) {
     }
 }
 
-fn symbols_heap(packet: &Packet) -> MinHeapless<HeapNode> {
-    let mut heap = MinHeapless::<HeapNode>::new();
+fn symbols_heap<P: TieBreak>(packet: &Packet) -> P::Queue<HeapNode> {
+    let mut heap = P::Queue::<HeapNode>::default();
     let ptr = packet.symbol_frequency_bytes.as_ptr();
     for i in 0..packet.symbol_count {
         let freq_ptr = ptr.wrapping_add(i as usize * 8) as *const u32;
@@ -213,12 +344,17 @@ fn symbols_table(tree: &[TreeNode; 23]) -> SymbolTable {
             }
             bits <<= 1;
         }
+        // Without a symbol in 8 bits a lookup would consume nothing.
+        assert!(
+            bits_used > 0,
+            "the table decodes codes of up to {MAX_TABLE_DEPTH} bits"
+        );
         table.bits_used[byte as usize] = bits_used;
     }
     table
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 struct HeapNode {
     left_index: u8,
     right_index: u8,
@@ -231,12 +367,12 @@ impl MinHeapNode for HeapNode {
     }
 }
 impl Ord for HeapNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
 impl PartialOrd for HeapNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -268,8 +404,8 @@ struct TreeNode {
 impl Default for TreeNode {
     fn default() -> Self {
         Self {
-            left_ptr: std::ptr::null(),
-            right_ptr: std::ptr::null(),
+            left_ptr: core::ptr::null(),
+            right_ptr: core::ptr::null(),
             symbol: None,
         }
     }
@@ -280,6 +416,7 @@ impl Default for TreeNode {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use common::test_cases::*;
 
     #[test]
@@ -287,11 +424,55 @@ mod tests {
         let decoded_message = super::decode_packet(&TEST_BYTES);
         assert_eq!(decoded_message, EXPECTED_MESSAGE);
     }
+
+    #[test]
+    #[should_panic(expected = "codes of up to 8 bits")]
+    fn rejects_deep_codes() {
+        let content = common::synthetic::encode_packet(&fibonacci_message());
+        super::decode_packet(&content);
+    }
+
+    #[test]
+    fn decoder_reuses_buffer() {
+        let packet = &Packet::new(&TEST_BYTES);
+        let decoder = Decoder::new(packet);
+        let mut decoded = Vec::new();
+        for _ in 0..2 {
+            decoder.decode_into(packet, &mut decoded);
+            assert_eq!(decoded, EXPECTED_MESSAGE.as_bytes());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "different symbol table")]
+    fn decoder_rejects_other_tables() {
+        let decoder = Decoder::new(&Packet::new(&TEST_BYTES));
+        let content = ALL_CASES[0].request();
+        decoder.decode_into(&Packet::new(&content), &mut Vec::new());
+    }
+
+    #[test]
+    fn kernels_decode_alike() {
+        let kernels = Kernel::ALL.iter().filter(|kernel| kernel.is_supported());
+        for &kernel in kernels {
+            for case in ALL_CASES {
+                let content = &case.request();
+                assert_eq!(
+                    decode_packet_with_kernel::<Classic>(content, kernel),
+                    decode_packet_with_kernel::<Classic>(content, Kernel::Generic),
+                    "{kernel:?} {}",
+                    case.name
+                );
+            }
+        }
+    }
 }
 
 // MARK: Benches
 
+#[cfg(feature = "std")]
 use common::test_cases::BENCH_SAMPLE_COUNT;
+#[cfg(feature = "std")]
 #[divan::bench_group(sample_count = BENCH_SAMPLE_COUNT)]
 mod bench {
     use super::*;
@@ -300,13 +481,17 @@ mod bench {
     use divan::counter::BytesCount;
     use divan::{black_box, Bencher};
 
-    #[divan::bench(sample_count = 100_000, args = [ALL_CASES[0], ALL_CASES[5]])]
-    fn gen_tree(bencher: Bencher, case: &Case) {
+    #[divan::bench(
+        sample_count = 100_000,
+        types = [Classic, InsertionOrder, ReverseInsertionOrder],
+        args = [ALL_CASES[0], ALL_CASES[5]]
+    )]
+    fn gen_tree<P: TieBreak>(bencher: Bencher, case: &Case) {
         let content = case.request();
         let packet = &Packet::new(&content);
         bencher.bench_local(move || {
             let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-            huffman_tree(packet, &mut tree);
+            huffman_tree::<P>(packet, &mut tree);
             black_box(tree);
         });
     }
@@ -317,7 +502,7 @@ mod bench {
         let packet = &Packet::new(&content);
         bencher.bench_local(move || {
             let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-            huffman_tree(packet, &mut tree);
+            huffman_tree::<Classic>(packet, &mut tree);
             let table = symbols_table(&tree);
             black_box(table);
         });
@@ -328,12 +513,26 @@ mod bench {
         let content = case.request();
         let packet = &Packet::new(&content);
         let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-        huffman_tree(packet, &mut tree);
+        huffman_tree::<Classic>(packet, &mut tree);
+        let table = symbols_table(&tree);
+        bencher
+            .counter(BytesCount::from(packet.decoded_bytes_len))
+            .bench_local(move || {
+                super::decode_message(Kernel::detect(), black_box(packet), &table, &mut Vec::new());
+            });
+    }
+
+    #[divan::bench(args = ALL_CASES)]
+    fn decode_message_generic(bencher: Bencher, case: &Case) {
+        let content = case.request();
+        let packet = &Packet::new(&content);
+        let mut tree = [TreeNode::default(); MAX_TREE_LEN];
+        huffman_tree::<Classic>(packet, &mut tree);
         let table = symbols_table(&tree);
         bencher
             .counter(BytesCount::from(packet.decoded_bytes_len))
             .bench_local(move || {
-                super::decode_message(black_box(packet), &table);
+                super::decode_message(Kernel::Generic, black_box(packet), &table, &mut Vec::new());
             });
     }
 
@@ -351,13 +550,13 @@ mod bench {
         let content2 = content.clone();
         let packet = &Packet::new(&content);
         let mut tree = [TreeNode::default(); MAX_TREE_LEN];
-        huffman_tree(packet, &mut tree);
+        huffman_tree::<Classic>(packet, &mut tree);
         let table = symbols_table(&tree);
         bencher
             .counter(BytesCount::from(2 * packet.decoded_bytes_len))
             .bench_local(move || {
                 black_box(super::decode_packet(black_box(&content2)));
-                black_box(super::decode_message(black_box(packet), &table));
+                super::decode_message(Kernel::detect(), black_box(packet), &table, &mut Vec::new());
             });
     }
 }
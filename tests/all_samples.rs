@@ -1,22 +1,103 @@
-use common::packet::Packet;
-use common::test_cases::*;
+use common::corpus::samples;
+use common::kernel::Kernel;
+use common::min_heap::Classic;
+use common::packet::{Packet, PacketBuilder};
+use common::synthetic::{encode_packet, market_packet, SWEEP_DECODED_LENS};
+use common::test_cases::TEST_BYTES;
+
+/// Asserts `result` is `content` decoded by `baseline`, reporting where it
+/// first diverges otherwise.
+fn assert_decodes(content: &[u8], result: &str, context: impl std::fmt::Display) {
+    let expected_result = baseline::decode_packet(content);
+    if result == expected_result {
+        return;
+    }
+    let packet = &Packet::new(content);
+    let trace = baseline::decode_trace(packet);
+    let divergence = baseline::first_divergence(&trace, result.as_bytes()).unwrap();
+    panic!(
+        "{context}: {divergence}\n input bytes len: {}\noutput bytes len: {} (got {})",
+        packet.encoded_bytes_len,
+        packet.decoded_bytes_len,
+        result.len()
+    );
+}
+
+/// A packet passing `Packet::try_new` whose tree's parent frequencies overflow,
+/// which the decoders' tree builders panic on rather than wrap.
+fn overflowing_packet() -> Vec<u8> {
+    PacketBuilder::from_packet(&Packet::new(&TEST_BYTES))
+        .clear_symbols()
+        .symbols([(b'0', u32::MAX), (b'3', u32::MAX), (b'1', 1), (b'2', 1)])
+        .build()
+}
 
 macro_rules! generate_test_cases {
     ($crate_name:ident) => {
         paste::paste! {
             #[test]
             fn [<all_samples_baseline_vs_ $crate_name>]() {
-                for (i, case) in SAMPLE_CASES.iter().enumerate() {
+                for case in samples() {
                     let content = &case.request();
-                    let expected_result = baseline::decode_packet(content);
                     let result = $crate_name::decode_packet(&content);
-                    if expected_result != result {
-                        println!(" failed SAMPLES_CASES[{}]: {:?}", i, case.name);
-                        let packet = &Packet::new(content);
-                        println!(" input bytes len: {}", packet.encoded_bytes_len);
-                        println!("output bytes len: {}", packet.decoded_bytes_len);
+                    assert_decodes(content, &result, case.path.display());
+                }
+            }
+
+            #[test]
+            fn [<synthetic_baseline_vs_ $crate_name>]() {
+                // Up to 256k, the larger sizes only add run time in debug builds.
+                for (seed, decoded_len) in SWEEP_DECODED_LENS[..13].iter().copied().enumerate() {
+                    let content = &market_packet(seed as u64, decoded_len);
+                    let result = $crate_name::decode_packet(content);
+                    assert_decodes(content, &result, format!("decoded_len {decoded_len}"));
+                }
+            }
+
+            #[test]
+            #[should_panic(expected = "overflows a u32")]
+            fn [<overflowing_frequencies_panic_in_ $crate_name>]() {
+                $crate_name::decode_packet(&overflowing_packet());
+            }
+        }
+    };
+}
+
+#[test]
+fn all_samples_baseline_vs_flat_unsafe_ptr_many_small() {
+    let requests: Vec<_> = samples().iter().map(|case| case.request()).collect();
+    let contents: Vec<&[u8]> = requests.iter().map(Vec::as_slice).collect();
+    let results = flat_unsafe_ptr::decode_many_small(&contents);
+    for ((case, content), result) in samples().iter().zip(&contents).zip(results) {
+        assert_decodes(content, &result.unwrap(), &case.name);
+    }
+}
+
+#[test]
+fn encoder_reproduces_samples() {
+    for case in samples() {
+        let content = case.request();
+        let message = baseline::decode_packet(&content);
+        assert!(
+            encode_packet(message.as_bytes()) == content,
+            "{}",
+            case.name
+        );
+    }
+}
+
+macro_rules! generate_kernel_test_cases {
+    ($crate_name:ident) => {
+        paste::paste! {
+            #[test]
+            fn [<all_samples_baseline_vs_ $crate_name _kernels>]() {
+                let kernels = Kernel::ALL.iter().filter(|kernel| kernel.is_supported());
+                for &kernel in kernels {
+                    for case in samples() {
+                        let content = &case.request();
+                        let result = $crate_name::decode_packet_with_kernel::<Classic>(content, kernel);
+                        assert_decodes(content, &result, format!("{kernel:?} {}", case.name));
                     }
-                    assert_eq!(result, expected_result);
                 }
             }
         }
@@ -40,3 +121,7 @@ generate_test_cases!(fsm_unsafe_2channel);
 generate_test_cases!(fsm_unsafe_3channel);
 generate_test_cases!(fsm_unsafe_4channel);
 generate_test_cases!(fsm_unsafe_5channel);
+generate_test_cases!(adaptive);
+
+generate_kernel_test_cases!(table_unsafe_ptr);
+generate_kernel_test_cases!(fsm_unsafe_4channel);
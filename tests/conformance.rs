@@ -0,0 +1,101 @@
+use common::corpus::samples;
+use common::packet::{Packet, PacketBuilder, PacketError, ALLOWED_SYMBOLS};
+use common::tree::{validate_packet, MAX_TABLE_DEPTH};
+
+// The captures in `_sample_data`, or in `BDO_SAMPLES` if set.
+fn sample_files() -> Vec<std::path::PathBuf> {
+    samples().iter().map(|sample| sample.path.clone()).collect()
+}
+
+#[test]
+fn all_samples_conform_to_spec() {
+    let files = sample_files();
+    assert!(!files.is_empty(), "no sample files found");
+
+    let mut failures = Vec::new();
+    for path in &files {
+        let content = std::fs::read(path).unwrap();
+        let name = path.file_name().unwrap().to_string_lossy();
+        match Packet::try_new(&content) {
+            Ok(packet) => {
+                // The record and field delimiters are always in the table.
+                let symbols: Vec<u8> = packet.symbol_frequencies().map(|(s, _)| s).collect();
+                for delimiter in [b'|', b'-'] {
+                    if !symbols.contains(&delimiter) {
+                        failures.push(format!("{name}: missing {:?}", delimiter as char));
+                    }
+                }
+                assert!(symbols.iter().all(|s| ALLOWED_SYMBOLS.contains(s)));
+            }
+            Err(err) => failures.push(format!("{name}: {err}")),
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} samples failed:\n{}",
+        failures.len(),
+        files.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn all_samples_build_valid_trees() {
+    // Every decoder decodes every sample, so the codes fit the table decoders
+    // and `len_bitstream` is the length of the codes.
+    for path in sample_files() {
+        let content = std::fs::read(&path).unwrap();
+        let packet = Packet::try_new(&content).unwrap();
+        let depth = validate_packet(&packet.header(), MAX_TABLE_DEPTH);
+        assert!(depth.is_ok(), "{}: {depth:?}", path.display());
+    }
+}
+
+#[test]
+fn all_samples_rewrite_identically() {
+    for path in sample_files() {
+        let content = std::fs::read(&path).unwrap();
+        let packet = Packet::try_new(&content).unwrap();
+        assert_eq!(
+            PacketBuilder::from_packet(&packet).build(),
+            content,
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn all_samples_reject_corrupted_symbols() {
+    for path in sample_files() {
+        let content = std::fs::read(&path).unwrap();
+        let packet = Packet::try_new(&content).unwrap();
+        let last = packet.symbol_count as usize - 1;
+
+        let mut corrupted = content.clone();
+        corrupted[12 + 8 * last + 4] = b'x';
+        assert_eq!(
+            Packet::try_new(&corrupted).unwrap_err(),
+            PacketError::InvalidSymbol {
+                entry: last,
+                offset: 12 + 8 * last + 4,
+                symbol: b'x',
+            },
+            "{}",
+            path.display()
+        );
+
+        let mut corrupted = content;
+        corrupted[12 + 8 * last + 6] = b'x';
+        assert_eq!(
+            Packet::try_new(&corrupted).unwrap_err(),
+            PacketError::NonZeroPadding {
+                entry: last,
+                offset: 12 + 8 * last + 6,
+                byte: b'x',
+            },
+            "{}",
+            path.display()
+        );
+    }
+}